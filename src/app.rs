@@ -1,15 +1,25 @@
 use crate::error::EditorError;
+use crate::frontmatter::{self, Property, PropertyValue};
+use crate::markdown::{self, ListContinuation};
+use crate::query::{self, NoteQuery, NoteRecord, QuerySource};
+use crate::registers::{self, Register, Registers};
+use crate::table::{self, Table};
+use crate::tasks;
+use crate::undo::{self, Timeline};
+use crate::vim::{self, Motion, Operator};
+use crate::window::{self, Split};
 use chrono::{Duration, Local};
 use ratatui::{
     Frame, Terminal,
     backend::CrosstermBackend,
     layout::{Constraint, Direction, Layout, Margin, Rect},
-    style::{Color, Style},
+    style::{Color, Modifier, Style},
     text::{Line, Span},
-    widgets::{Block, Borders, List, ListItem, ListState, Paragraph},
+    widgets::{Block, Borders, Clear, List, ListItem, ListState, Paragraph},
 };
 use rusqlite::Connection;
 use rusqlite::params;
+use std::collections::HashMap;
 use std::fs;
 use std::io;
 use std::path::Path;
@@ -26,6 +36,21 @@ use tui_textarea::{CursorMove, Input, Key, TextArea};
 use image::DynamicImage;
 use ratatui_image::{FilterType, Resize, picker::Picker, protocol::StatefulProtocol};
 
+// Number of lines of the target note shown in the hover preview popup
+const NOTE_PREVIEW_LINES: usize = 50;
+
+// Textarea edits `u` can go back through; a grouped change takes several
+const UNDO_HISTORY: usize = 1000;
+
+// Macros running macros (`@a` recorded inside `"a`) stop at this depth
+const MAX_MACRO_DEPTH: usize = 10;
+
+// File id of a note that is not written to the vault yet
+const NEW_FILE_ID: i64 = 0;
+
+// Stands for `Ctrl-W` in the key sequence while a window command is pending
+const CTRL_W: char = '\u{17}';
+
 macro_rules! set_textarea_delafult_style {
     ($textarea:expr) => {
         $textarea.set_block(
@@ -38,6 +63,7 @@ macro_rules! set_textarea_delafult_style {
         $textarea.set_cursor_line_style(Style::default());
         $textarea.set_cursor_style(Style::default().bg(Color::White).fg(Color::Black));
         $textarea.set_selection_style(Style::default().bg(Color::LightBlue));
+        $textarea.set_max_histories(UNDO_HISTORY);
     };
 }
 macro_rules! gettitle {
@@ -50,6 +76,64 @@ macro_rules! gettitle {
     };
 }
 
+fn visual_status(mode: Mode) -> &'static str {
+    match mode {
+        Mode::VisualLine => "Visual Line",
+        Mode::VisualBlock => "Visual Block",
+        _ => "Visual",
+    }
+}
+
+// The keys of a change with `count` in place of the count typed with it, so
+// `2.` after `3dd` or `d3w` replays `2dd` or `2dw`. Only operators take a
+// count; other changes come back as they were.
+fn with_count(
+    keys: &[ratatui::crossterm::event::KeyEvent],
+    count: usize,
+) -> Vec<ratatui::crossterm::event::KeyEvent> {
+    let char_at = |i: usize| match keys.get(i).map(|key| key.code) {
+        Some(ratatui::crossterm::event::KeyCode::Char(c)) => Some(c),
+        _ => None,
+    };
+    let skip_count = |mut i: usize| {
+        if char_at(i).is_some_and(|c| matches!(c, '1'..='9')) {
+            while char_at(i).is_some_and(|c| c.is_ascii_digit()) {
+                i += 1;
+            }
+        }
+        i
+    };
+    let skip_register = |i: usize| {
+        if char_at(i) == Some('"') {
+            (i + 2).min(keys.len())
+        } else {
+            i
+        }
+    };
+    // [register][count][register]operator[count]motion
+    let start = skip_register(0);
+    let after_count = skip_count(start);
+    let command = skip_register(after_count);
+    let operator = match (char_at(command), char_at(command + 1)) {
+        (Some('d' | 'c' | 'y' | '>' | '<' | '~'), _) => 1,
+        (Some('g'), Some('u' | 'U')) => 2,
+        _ => return keys.to_vec(),
+    };
+    let mut result = keys[..start].to_vec();
+    result.extend(count.to_string().chars().map(|c| {
+        ratatui::crossterm::event::KeyEvent::new(
+            ratatui::crossterm::event::KeyCode::Char(c),
+            ratatui::crossterm::event::KeyModifiers::NONE,
+        )
+    }));
+    result.extend_from_slice(&keys[after_count..command + operator]);
+    result.extend_from_slice(&keys[skip_count(command + operator)..]);
+    result
+}
+
+// (anchor, cursor) of a Visual selection
+type Selection = ((usize, usize), (usize, usize));
+
 #[derive(PartialEq, Clone, Copy, Debug)]
 pub enum Mode {
     Normal,
@@ -59,10 +143,15 @@ pub enum Mode {
     Search,
     TagFiles,
     Visual,
+    VisualLine,
     VisualBlock,
     BlockInsert,
     FileTree,
     FileTreeVisual,
+    Report,
+    Properties,
+    Table,
+    Outline,
 }
 
 #[derive(PartialEq)]
@@ -87,6 +176,17 @@ pub enum SearchType {
     Files,
 }
 
+#[derive(PartialEq, Clone, Debug)]
+pub enum ReportType {
+    None,
+    Graph,
+    Health,
+    Query,
+    Tasks,
+    Registers,
+    Buffers,
+}
+
 #[derive(PartialEq, Clone, Debug)]
 pub enum InsertPosition {
     Before,
@@ -136,14 +236,26 @@ pub struct App {
     status: String,
     file_id: i64,
     pub should_quit: bool,
-    history: Vec<(String, i64)>, // (file_path, file_id)
-    history_index: usize,        // Current position in history
+    history: Vec<(String, i64, (usize, usize))>, // (file_path, file_id, cursor)
+    history_index: usize,                        // Current position in history
+    marks: HashMap<(String, char), (usize, usize)>, // `ma`-`mz`, keyed by file path
     completion_state: CompletionState,
     search_state: SearchState,
     key_sequence: String, // Tracks key sequence in Normal mode (e.g., "\", "\o", "\ob")
     tag_files: Vec<(String, i64)>, // Files associated with selected tag
     tag_files_state: ListState, // State for selecting tag files
-    yanked: Vec<String>,
+    registers: Registers,
+    pending_register: Option<char>, // Register picked with `"x` for the next yank, delete or paste
+    recording: Option<(char, Vec<ratatui::crossterm::event::KeyEvent>)>, // Macro being recorded with `q`
+    macro_request: Option<(char, usize)>, // `@` waiting to run once the current key is handled
+    last_macro: Option<char>,             // For `@@`
+    macro_depth: usize,                   // Nesting of running macros
+    motion_failed: bool,                  // A motion found nothing to move to; stops macros
+    change_keys: Vec<ratatui::crossterm::event::KeyEvent>, // Keys of the Normal mode command in progress
+    change_made: bool,                                     // Whether that command edited the buffer
+    last_change: Vec<ratatui::crossterm::event::KeyEvent>, // Replayed by `.`
+    repeat_request: Option<Option<usize>>,                 // `.` waiting to run, with its count
+    repeating: bool,
     visual_anchor: Option<(usize, usize)>,
     insert_position: InsertPosition,
     block_insert_col: usize,
@@ -173,6 +285,31 @@ pub struct App {
     last_wikilink: Option<String>,     // Last processed wikilink to avoid redundant loads
     image_full_screen: bool,           // Toggle for full-screen image
     last_image_area: Option<Rect>,     // Track last render area to regenerate protocol
+    preview_state: PreviewState,
+    report_state: ReportState,
+    render_queries: bool, // Show ```query results below their blocks
+    task_done_date: bool, // Stamp `✅ date` when a task is checked off
+    command_range: Option<(usize, usize)>, // Rows selected when `:` was pressed in Visual mode
+    command_selection: Option<((usize, usize), (usize, usize))>, // Same selection, charwise
+    last_visual: Option<(Mode, Selection)>, // Mode and (anchor, cursor) for `gv`
+    outline_visible: bool,
+    outline_state: ListState,
+    closed_folds: Vec<usize>,     // First rows of closed folds
+    last_find: Option<vim::Find>, // Last `f` `t` `F` `T`, repeated by `;` and `,`
+    fold_line_count: usize,       // Line count the fold rows were last synced with
+    query_results: std::collections::HashMap<String, Result<(NoteQuery, Vec<NoteRecord>), String>>,
+    query_row: Option<(usize, usize)>, // (closing fence row, result) picked below a ```query block
+    undo_groups: Vec<(u64, usize)>, // (hash of the buffer after a multi-edit change, textarea edits)
+    redo_groups: Vec<(u64, usize)>,
+    timeline: Timeline,   // States of the buffer over time for `:earlier`/`:later`
+    buffers: Vec<Buffer>, // Open notes in `:ls` order, including the current one
+    undofile: bool,       // Write the timeline to disk on save
+    windows: Vec<Window>, // Every window of the split layout
+    active_window: usize, // Id of the window with the cursor
+    split: Split,
+    editor_area: Rect, // Where the windows were last drawn, for `Ctrl-W h/j/k/l`
+    props_state: PropsState,
+    table_state: TableState,
 }
 
 pub struct CompletionState {
@@ -184,6 +321,127 @@ pub struct CompletionState {
     trigger_start: (usize, usize), // (row, col) where trigger started
 }
 
+pub struct ReportState {
+    report_type: ReportType,
+    title: String,
+    lines: Vec<ReportLine>,
+    list_state: ListState,
+    graph_depth: usize,
+}
+
+#[derive(Clone)]
+pub struct ReportLine {
+    text: String,
+    target: Option<(String, i64)>, // (path, file_id) opened on Enter
+    row: usize,                    // Line in the target file to jump to
+}
+
+#[derive(PartialEq, Clone, Copy, Debug)]
+pub enum PropsField {
+    None,
+    Key,
+    Value,
+}
+
+// `:props` panel over the frontmatter of the current note
+pub struct PropsState {
+    properties: Vec<Property>,
+    list_state: ListState,
+    editing: PropsField,
+    input: String,
+    adding: bool, // Key being typed belongs to a new property
+    vault_keys: Vec<(String, PropertyValue)>, // Keys used in files.metadata with an empty value of their type
+    key_state: ListState,
+    modified: bool,
+}
+
+#[derive(PartialEq, Clone, Copy, Debug)]
+pub enum TableInput {
+    None,
+    Filter,
+    Cell,
+}
+
+// `:table` view: notes as rows, frontmatter keys as columns
+pub struct TableState {
+    title: String,
+    columns: Vec<String>, // Column 0 is the note name, the rest are metadata keys
+    records: Vec<NoteRecord>,
+    rows: Vec<usize>, // Indices into records after filtering and sorting
+    list_state: ListState,
+    column: usize,
+    sort: Option<(usize, bool)>, // (column, descending)
+    filters: Vec<String>,        // Per column, case-insensitive substring
+    editing: TableInput,
+    input: String,
+}
+
+// Note linked to or from another note in the graph view
+pub struct GraphNode {
+    id: i64,
+    path: String,
+    file_name: String,
+    outgoing: bool,
+    incoming: bool,
+}
+
+pub struct PreviewState {
+    active: bool,
+    link: String, // Wikilink the preview was opened for
+    title: String,
+    lines: Vec<String>, // First NOTE_PREVIEW_LINES lines of the target note
+    scroll: usize,
+    anchor_row: usize, // Row of the wikilink in the current buffer
+}
+
+// A file that was open before, kept so `u` still works after switching back
+// An open note. The current one lives in the App fields; its slot is only
+// filled in when switching away.
+struct Buffer {
+    path: String,
+    file_id: i64,
+    textarea: TextArea<'static>,
+    undo_groups: Vec<(u64, usize)>,
+    redo_groups: Vec<(u64, usize)>,
+    timeline: Timeline,
+    closed_folds: Vec<usize>,
+    scroll: (usize, usize), // (scroll_offset, horizontal_scroll_offset)
+}
+
+impl Buffer {
+    fn is_modified(&self) -> bool {
+        self.textarea.lines() != self.timeline.saved_lines()
+    }
+}
+
+// A view of a note in the split layout. The active window's cursor and
+// scroll live in the App fields and are copied back when leaving it.
+#[derive(Clone)]
+struct Window {
+    id: usize,
+    path: String,
+    file_id: i64,
+    cursor: (usize, usize),
+    scroll: (usize, usize),
+}
+
+// What `draw_window` renders: the lines of a note with its cursor and folds
+struct WindowView {
+    lines: Vec<String>,
+    cursor: (usize, usize),
+    selection: Option<Selection>, // (start, end) of the Visual selection
+    closed_folds: Vec<usize>,
+    scroll: (usize, usize),
+    block: Block<'static>,
+    active: bool, // Only the active window draws its cursor
+}
+
+struct DrawnWindow {
+    scroll: (usize, usize),
+    display_rows: Vec<Option<usize>>, // Buffer row of every displayed line
+    visible_lines: usize,
+}
+
 pub struct SearchState {
     active: bool,
     search_type: SearchType,
@@ -200,6 +458,7 @@ impl App {
         let content = fs::read_to_string(file_path).unwrap_or_default();
         let mut textarea = TextArea::new(content.lines().map(|s| s.to_string()).collect());
         set_textarea_delafult_style!(textarea);
+        let timeline = Timeline::new(textarea.lines().to_vec(), Local::now().timestamp());
         let file_id = App::get_file_id(&db, file_path)?;
         let tags = App::load_tags(&db, file_id)?;
         let backlinks = App::load_backlinks(&db, file_id)?;
@@ -231,8 +490,9 @@ impl App {
             status: "Normal".to_string(),
             file_id,
             should_quit: false,
-            history: vec![(file_path.to_string(), file_id)],
+            history: vec![(file_path.to_string(), file_id, (0, 0))],
             history_index: 0,
+            marks: HashMap::new(),
             completion_state: CompletionState {
                 active: false,
                 completion_type: CompletionType::None,
@@ -251,7 +511,18 @@ impl App {
             key_sequence: String::new(),
             tag_files: Vec::new(),
             tag_files_state: ListState::default(),
-            yanked: Vec::new(),
+            registers: Registers::default(),
+            pending_register: None,
+            recording: None,
+            macro_request: None,
+            last_macro: None,
+            macro_depth: 0,
+            motion_failed: false,
+            change_keys: Vec::new(),
+            change_made: false,
+            last_change: Vec::new(),
+            repeat_request: None,
+            repeating: false,
             visual_anchor: None,
             insert_position: InsertPosition::Before,
             block_insert_col: 0,
@@ -279,12 +550,101 @@ impl App {
             last_wikilink: None,
             image_full_screen: false,
             last_image_area: None,
+            preview_state: PreviewState {
+                active: false,
+                link: String::new(),
+                title: String::new(),
+                lines: Vec::new(),
+                scroll: 0,
+                anchor_row: 0,
+            },
+            report_state: ReportState {
+                report_type: ReportType::None,
+                title: String::new(),
+                lines: Vec::new(),
+                list_state: ListState::default(),
+                graph_depth: 2,
+            },
+            render_queries: false,
+            task_done_date: false,
+            command_range: None,
+            command_selection: None,
+            last_visual: None,
+            outline_visible: false,
+            outline_state: ListState::default(),
+            closed_folds: Vec::new(),
+            last_find: None,
+            fold_line_count: 0,
+            query_results: std::collections::HashMap::new(),
+            query_row: None,
+            undo_groups: Vec::new(),
+            redo_groups: Vec::new(),
+            timeline,
+            buffers: Vec::new(),
+            undofile: false,
+            windows: vec![Window {
+                id: 0,
+                path: file_path.to_string(),
+                file_id: 0,
+                cursor: (0, 0),
+                scroll: (0, 0),
+            }],
+            active_window: 0,
+            split: Split::Window(0),
+            editor_area: Rect::default(),
+            props_state: PropsState {
+                properties: Vec::new(),
+                list_state: ListState::default(),
+                editing: PropsField::None,
+                input: String::new(),
+                adding: false,
+                vault_keys: Vec::new(),
+                key_state: ListState::default(),
+                modified: false,
+            },
+            table_state: TableState {
+                title: String::new(),
+                columns: Vec::new(),
+                records: Vec::new(),
+                rows: Vec::new(),
+                list_state: ListState::default(),
+                column: 0,
+                sort: None,
+                filters: Vec::new(),
+                editing: TableInput::None,
+                input: String::new(),
+            },
         };
+        app.load_session();
         app.open_file(file_path.to_string(), file_id)?;
 
         Ok(app)
     }
 
+    fn session_path(&self) -> std::path::PathBuf {
+        Path::new(&self.base_dir).join(".midetor_session.json")
+    }
+
+    // Named registers, and with them recorded macros, are kept between runs
+    fn load_session(&mut self) {
+        if let Ok(text) = fs::read_to_string(self.session_path())
+            && let Ok(json) = serde_json::from_str::<serde_json::Value>(&text)
+        {
+            self.registers.load_json(&json["registers"]);
+            self.undofile = json["undofile"].as_bool().unwrap_or(false);
+        }
+    }
+
+    pub fn save_session(&self) {
+        let json = serde_json::json!({
+            "registers": self.registers.to_json(),
+            "undofile": self.undofile,
+        });
+        if let Ok(text) = serde_json::to_string_pretty(&json) {
+            let _ = fs::write(self.session_path(), text);
+        }
+    }
+
     pub fn handle_paste(&mut self, text: String) -> Result<(), EditorError> {
         match self.mode {
             Mode::Normal => {
@@ -304,6 +664,12 @@ impl App {
                 self.textarea.insert_str(&text);
                 self.status = "Pasted (simple) in BlockInsert".to_string();
             }
+            Mode::Properties if self.props_state.editing != PropsField::None => {
+                self.props_state.input.push_str(text.trim_end_matches('\n'));
+            }
+            Mode::Table if self.table_state.editing != TableInput::None => {
+                self.table_state.input.push_str(text.trim_end_matches('\n'));
+            }
             _ => {}
         }
         Ok(())
@@ -397,31 +763,116 @@ impl App {
     }
 
     fn save_file(&mut self) -> Result<(), EditorError> {
+        if let Some(parent) = Path::new(&self.file_path).parent() {
+            fs::create_dir_all(parent)?;
+        }
         fs::write(&self.file_path, self.textarea.lines().join("\n"))?;
-
-        let output = Command::new("markdown-scanner")
-            .arg(&self.file_path)
-            .arg(&self.base_dir)
-            .output()?;
-
-        if !output.status.success() {
-            let error_msg = String::from_utf8_lossy(&output.stderr).into_owned();
-            return Err(EditorError::Scanner(error_msg));
+        self.run_scanner(&self.file_path)?;
+        if self.file_id == NEW_FILE_ID {
+            self.set_new_file_id(App::get_file_id(&self.db, &self.file_path)?);
         }
 
+        self.query_results.clear();
+        self.timeline
+            .record(self.textarea.lines(), Local::now().timestamp());
+        self.timeline.mark_saved();
+        if self.undofile {
+            self.write_undofile()?;
+        }
         self.status = "Saved".to_string();
         Ok(())
     }
 
+    // A note opened with `:e` before it existed gets its id once it is written
+    fn set_new_file_id(&mut self, file_id: i64) {
+        self.file_id = file_id;
+        let path = &self.file_path;
+        for entry in self.history.iter_mut().filter(|h| h.0 == *path) {
+            entry.1 = file_id;
+        }
+        for window in self.windows.iter_mut().filter(|w| w.path == *path) {
+            window.file_id = file_id;
+        }
+        for buffer in self.buffers.iter_mut().filter(|b| b.path == *path) {
+            buffer.file_id = file_id;
+        }
+    }
+
+    // Switch to a note, keeping the current one in the buffer list
     fn open_file(&mut self, path: String, file_id: i64) -> Result<(), EditorError> {
+        self.stash_buffer();
+        self.load_buffer(path, file_id, false)
+    }
+
+    // Make `path` the current note, from its buffer or, with `reload`, from disk
+    fn load_buffer(&mut self, path: String, file_id: i64, reload: bool) -> Result<(), EditorError> {
         self.file_path = path.clone();
         self.file_id = file_id;
         let content = fs::read_to_string(&self.file_path).unwrap_or_default();
-        let mut textarea = TextArea::new(content.lines().map(|s| s.to_string()).collect());
-        set_textarea_delafult_style!(textarea);
-        textarea.move_cursor(tui_textarea::CursorMove::Jump(0, 0));
-        while textarea.undo() {}
-        self.textarea = textarea;
+        let mut lines: Vec<String> = content.lines().map(|s| s.to_string()).collect();
+        if lines.is_empty() {
+            lines.push(String::new());
+        }
+        let cached = self
+            .buffers
+            .iter_mut()
+            .find(|b| b.path == path && !reload)
+            .map(|b| Buffer {
+                path: b.path.clone(),
+                file_id: b.file_id,
+                textarea: std::mem::take(&mut b.textarea),
+                undo_groups: std::mem::take(&mut b.undo_groups),
+                redo_groups: std::mem::take(&mut b.redo_groups),
+                timeline: std::mem::take(&mut b.timeline),
+                closed_folds: std::mem::take(&mut b.closed_folds),
+                scroll: b.scroll,
+            });
+        // An open note is reloaded when the file changed outside the editor,
+        // unless it has edits of its own
+        let changed_on_disk = cached
+            .as_ref()
+            .is_some_and(|b| b.timeline.saved_lines() != lines.as_slice());
+        let mut status = "Normal".to_string();
+        match cached {
+            Some(buffer) if buffer.is_modified() || !changed_on_disk => {
+                if changed_on_disk {
+                    status = "File changed on disk since it was opened".to_string();
+                }
+                self.textarea = buffer.textarea;
+                self.undo_groups = buffer.undo_groups;
+                self.redo_groups = buffer.redo_groups;
+                self.timeline = buffer.timeline;
+                self.closed_folds = buffer.closed_folds;
+                (self.scroll_offset, self.horizontal_scroll_offset) = buffer.scroll;
+            }
+            _ => {
+                let mut textarea = TextArea::new(lines.clone());
+                set_textarea_delafult_style!(textarea);
+                self.textarea = textarea;
+                self.undo_groups.clear();
+                self.redo_groups.clear();
+                self.timeline = self
+                    .read_undofile()
+                    .filter(|t| t.saved_lines() == lines.as_slice())
+                    .unwrap_or_else(|| Timeline::new(lines, Local::now().timestamp()));
+                self.timeline.go_to_saved();
+                self.closed_folds.clear();
+                (self.scroll_offset, self.horizontal_scroll_offset) = (0, 0);
+                if !self.buffers.iter().any(|b| b.path == path) {
+                    self.buffers.push(Buffer {
+                        path: path.clone(),
+                        file_id,
+                        textarea: TextArea::default(),
+                        undo_groups: Vec::new(),
+                        redo_groups: Vec::new(),
+                        timeline: Timeline::default(),
+                        closed_folds: Vec::new(),
+                        scroll: (0, 0),
+                    });
+                }
+            }
+        }
+        self.fold_line_count = self.textarea.lines().len();
         self.completion_state = CompletionState {
             active: false,
             completion_type: CompletionType::None,
@@ -434,10 +885,11 @@ impl App {
         self.backlinks = App::load_backlinks(&self.db, self.file_id)?;
         self.view = View::Editor;
         self.mode = Mode::Normal;
-        self.status = "Normal".to_string();
+        self.status = status;
         self.current_image_index = 0;
         self.image_paths = self.extract_image_paths();
         self.last_wikilink = None;
+        self.query_results.clear();
 
         // Load image at cursor or first image
         self.load_image_at_cursor()?;
@@ -445,1249 +897,3840 @@ impl App {
         Ok(())
     }
 
-    fn open_wikilink_file(&mut self, wikilink: String) -> Result<(), EditorError> {
-        // Extract file name from the path
-        let wikilink = if wikilink.ends_with(".md") {
-            wikilink
-        } else {
-            format!("{}.md", wikilink)
+    // Move the current note into its slot in the buffer list
+    fn stash_buffer(&mut self) {
+        let buffer = Buffer {
+            path: self.file_path.clone(),
+            file_id: self.file_id,
+            textarea: std::mem::take(&mut self.textarea),
+            undo_groups: std::mem::take(&mut self.undo_groups),
+            redo_groups: std::mem::take(&mut self.redo_groups),
+            timeline: std::mem::take(&mut self.timeline),
+            closed_folds: std::mem::take(&mut self.closed_folds),
+            scroll: (self.scroll_offset, self.horizontal_scroll_offset),
         };
-        let file_name = Path::new(&wikilink)
-            .file_name()
-            .and_then(|s| s.to_str())
-            .map(|s| {
-                // if s.ends_with(".md") {
-                s.to_string()
-                // } else {
-                //     format!("{}.md", s)
-                // }
-            })
-            .unwrap_or_else(|| {
-                if wikilink.ends_with(".md") {
-                    wikilink.clone()
+        match self.buffers.iter_mut().find(|b| b.path == buffer.path) {
+            Some(slot) => *slot = buffer,
+            None => self.buffers.push(buffer),
+        }
+    }
+
+    // Whether `path` is open in another buffer with unsaved edits. Writing it on
+    // disk would be overwritten by that buffer's next `:w`.
+    fn has_unsaved_buffer(&self, path: &Path) -> bool {
+        path != Path::new(&self.file_path)
+            && self
+                .buffers
+                .iter()
+                .any(|b| Path::new(&b.path) == path && b.is_modified())
+    }
+
+    fn unsaved_buffer_status(path: &Path) -> String {
+        format!(
+            "{} has unsaved changes in its buffer, save it first",
+            gettitle!(path)
+        )
+    }
+
+    fn is_modified(&self) -> bool {
+        self.textarea.lines() != self.timeline.saved_lines()
+    }
+
+    // Slot of an open note, which may not be written to disk yet
+    fn buffer_index_of(&self, path: &Path) -> Option<usize> {
+        self.buffers.iter().position(|b| Path::new(&b.path) == path)
+    }
+
+    fn buffer_index(&self) -> usize {
+        self.buffers
+            .iter()
+            .position(|b| b.path == self.file_path)
+            .unwrap_or(0)
+    }
+
+    // `:ls`
+    fn open_buffers_report(&mut self) {
+        let current = self.buffer_index();
+        let lines: Vec<ReportLine> = self
+            .buffers
+            .iter()
+            .enumerate()
+            .map(|(i, buffer)| {
+                let modified = if i == current {
+                    self.is_modified()
                 } else {
-                    format!("{}.md", wikilink)
+                    buffer.is_modified()
+                };
+                let rel = buffer
+                    .path
+                    .strip_prefix(&self.base_dir)
+                    .map(|p| p.trim_start_matches('/'))
+                    .unwrap_or(&buffer.path);
+                ReportLine {
+                    text: format!(
+                        "{:>3} {}{} {}",
+                        i + 1,
+                        if i == current { '%' } else { ' ' },
+                        if modified { '+' } else { ' ' },
+                        rel
+                    ),
+                    target: Some((buffer.path.clone(), buffer.file_id)),
+                    row: 0,
                 }
-            });
-
-        // Try to find the file by file_name in the database
-        let file_result = {
-            let mut stmt = self
-                .db
-                .prepare("SELECT id, path FROM files WHERE file_name = ?")?;
-            stmt.query_row([&file_name], |row| {
-                Ok((row.get::<_, i64>(0)?, row.get::<_, String>(1)?))
             })
-        };
+            .collect();
+        self.show_report(ReportType::Buffers, "Buffers".to_string(), lines);
+        self.status = format!("{} buffers", self.buffers.len());
+    }
 
-        let (file_id, path) = match file_result {
-            Ok((id, path)) => (id, path),
-            Err(rusqlite::Error::QueryReturnedNoRows) => {
-                // File doesn't exist; create it in the base_dir
-                let path = format!("{}/{}", self.base_dir, wikilink); // Use original wikilink as path
-                if let Some(parent) = Path::new(&path).parent() {
-                    fs::create_dir_all(parent)?;
-                }
-                // Only write an empty file if it doesn't already exist
-                if !Path::new(&path).exists() {
-                    fs::write(&path, "")?;
-                }
-                let output = Command::new("markdown-scanner")
-                    .arg(&path)
-                    .arg(&self.base_dir)
-                    .output()?;
-                if !output.status.success() {
-                    let error_msg = String::from_utf8_lossy(&output.stderr).into_owned();
-                    return Err(EditorError::Scanner(error_msg));
-                }
-                let mut stmt = self
-                    .db
-                    .prepare("SELECT id FROM files WHERE file_name = ?")?;
-                let file_id = stmt
-                    .query_row([&file_name], |row| row.get(0))
-                    .map_err(|e| EditorError::Database(e))?;
-                (file_id, path)
-            }
-            Err(e) => return Err(EditorError::Database(e)),
+    // `:bn`, `:bp` and `:b N`
+    fn switch_buffer(&mut self, index: usize) -> Result<(), EditorError> {
+        let Some(buffer) = self.buffers.get(index) else {
+            self.status = format!("No buffer {}", index + 1);
+            return Ok(());
         };
-
-        self.history.truncate(self.history_index + 1);
-        self.history.push((path.clone(), file_id));
-        self.history_index += 1;
-        self.open_file(path, file_id)?;
+        if buffer.path != self.file_path {
+            let (path, file_id) = (buffer.path.clone(), buffer.file_id);
+            self.jump_to_file(path, file_id)?;
+            self.status = format!("Buffer {}: {}", index + 1, gettitle!(&self.file_path));
+        }
         Ok(())
     }
 
-    fn follow_backlink(&mut self, index: usize) -> Result<(), EditorError> {
-        let (current_row, current_col) = self.textarea.cursor();
-        let line = self.textarea.lines()[current_row].clone();
-
-        // If a specific index from the old logic is passed, use it.
-        // Otherwise (when index is usize::MAX), extract the link from the cursor position.
-        let wikilink = if index < self.backlinks.len() {
-            self.backlinks[index].0.clone()
+    fn cycle_buffer(&mut self, forward: bool) -> Result<(), EditorError> {
+        let count = self.buffers.len();
+        if count < 2 {
+            self.status = "Only one buffer".to_string();
+            return Ok(());
+        }
+        let current = self.buffer_index();
+        let index = if forward {
+            (current + 1) % count
         } else {
-            self.extract_wikilink(&line, current_col).ok_or_else(|| {
-                EditorError::InvalidBacklink("No valid wikilink found at cursor".to_string())
-            })?
+            (current + count - 1) % count
         };
+        self.switch_buffer(index)
+    }
 
-        // Clean incomplete autocompletions
-        if line.contains("[[") && !line.contains("]]") {
-            let mut new_lines = self.textarea.lines().to_vec();
-            new_lines[current_row] = line[..line.rfind("[[").unwrap_or(line.len())].to_string();
-            self.textarea = TextArea::new(new_lines);
-            set_textarea_delafult_style!(self.textarea);
-            self.textarea
-                .move_cursor(tui_textarea::CursorMove::Jump(current_row as u16, 0));
+    // `:bd` closes the current note and shows the next one; `:bd!` drops unsaved edits
+    fn delete_buffer(&mut self, force: bool) -> Result<(), EditorError> {
+        if self.is_modified() && !force {
+            self.status = "No write since last change (add ! to override)".to_string();
+            return Ok(());
         }
-
-        self.open_wikilink_file(wikilink)?;
-
+        if self.buffers.len() < 2 {
+            self.status = "Cannot close the last buffer".to_string();
+            return Ok(());
+        }
+        let closed = self.file_path.clone();
+        let current = self.buffer_index();
+        let next = &self.buffers[(current + 1) % self.buffers.len()];
+        let (path, file_id) = (next.path.clone(), next.file_id);
+        self.jump_to_file(path, file_id)?;
+        self.buffers.retain(|b| b.path != closed);
+        self.status = format!("Closed {}", gettitle!(&closed));
         Ok(())
     }
 
-    fn navigate_back(&mut self) -> Result<(), EditorError> {
-        if self.history_index > 0 {
-            self.history_index -= 1;
-            let (path, file_id) = self.history[self.history_index].clone();
-            self.open_file(path, file_id)?;
+    // `:e path` opens a note by vault path or name; `:e` alone reloads the current one.
+    // With `!` the current note drops its unsaved changes.
+    fn edit_path(&mut self, arg: &str, force: bool) -> Result<(), EditorError> {
+        if arg.is_empty() {
+            if self.is_modified() && !force {
+                self.status = "No write since last change (add ! to override)".to_string();
+                return Ok(());
+            }
+            let (path, file_id) = (self.file_path.clone(), self.file_id);
+            return self.load_buffer(path, file_id, true);
+        }
+        if force {
+            let (path, file_id) = (self.file_path.clone(), self.file_id);
+            self.load_buffer(path, file_id, true)?;
+        }
+        let path = if Path::new(arg).is_absolute() {
+            arg.to_string()
         } else {
-            self.status = "No previous file in history".to_string();
+            format!("{}/{}", self.base_dir, arg)
+        };
+        let path = if Path::new(&path).extension().is_none() {
+            format!("{}.md", path)
+        } else {
+            path
+        };
+        // A new note only exists in its buffer until `:w` writes it
+        if !Path::new(&path).exists() {
+            self.jump_to_file(path, NEW_FILE_ID)?;
+            self.status = format!("\"{}\" [New]", gettitle!(&self.file_path));
+            return Ok(());
         }
-        Ok(())
+        let file_id = match App::get_file_id(&self.db, &path) {
+            Ok(id) => id,
+            Err(EditorError::FileNotFound(_)) => {
+                self.run_scanner(&path)?;
+                App::get_file_id(&self.db, &path)?
+            }
+            Err(e) => return Err(e),
+        };
+        self.jump_to_file(path, file_id)
     }
 
-    fn navigate_forward(&mut self) -> Result<(), EditorError> {
-        if self.history_index < self.history.len() - 1 {
-            self.history_index += 1;
-            let (path, file_id) = self.history[self.history_index].clone();
-            self.open_file(path, file_id)?;
-        } else {
-            self.status = "No next file in history".to_string();
+    // Copy the live cursor and scroll back into the active window
+    fn save_active_window(&mut self) {
+        let cursor = self.textarea.cursor();
+        let scroll = (self.scroll_offset, self.horizontal_scroll_offset);
+        if let Some(window) = self.windows.iter_mut().find(|w| w.id == self.active_window) {
+            window.path = self.file_path.clone();
+            window.file_id = self.file_id;
+            window.cursor = cursor;
+            window.scroll = scroll;
         }
-        Ok(())
     }
 
-    fn echo(&mut self, message: &str) -> Result<(), EditorError> {
-        self.status = format!("{}", message);
-        self.view = View::Info;
+    // Make `id` the active window, loading its note when it shows another one
+    fn enter_window(&mut self, id: usize) -> Result<(), EditorError> {
+        let Some(window) = self.windows.iter().find(|w| w.id == id).cloned() else {
+            return Ok(());
+        };
+        self.active_window = id;
+        if window.path != self.file_path {
+            self.open_file(window.path, window.file_id)?;
+        }
+        self.jump_cursor(window.cursor);
+        (self.scroll_offset, self.horizontal_scroll_offset) = window.scroll;
         Ok(())
     }
 
-    fn start_completion(&mut self, completion_type: CompletionType) {
-        self.completion_state.active = true;
-        self.completion_state.completion_type = completion_type;
-        self.completion_state.query = String::new();
-        self.completion_state.suggestions = Vec::new();
-        self.completion_state.list_state = ListState::default();
-        self.mode = Mode::Complete;
-        self.completion_state.trigger_start = self.textarea.cursor();
-        self.status = format!("Completing {:?}", self.completion_state.completion_type);
+    fn focus_window(&mut self, id: usize) -> Result<(), EditorError> {
+        if id == self.active_window {
+            return Ok(());
+        }
+        self.save_active_window();
+        self.enter_window(id)
     }
 
-    fn update_completion(&mut self) -> Result<(), EditorError> {
-        let (row, col) = self.textarea.cursor();
-        let line = self.textarea.lines()[row].clone();
+    // `:sp` and `:vsp`: the new window shows the same note, or `arg` when given
+    fn split_window(&mut self, direction: Direction, arg: &str) -> Result<(), EditorError> {
+        self.save_active_window();
+        let id = self.windows.iter().map(|w| w.id).max().unwrap_or(0) + 1;
+        let Some(current) = self.windows.iter().find(|w| w.id == self.active_window) else {
+            return Ok(());
+        };
+        let window = Window {
+            id,
+            ..current.clone()
+        };
+        self.windows.push(window);
+        self.split.split(self.active_window, id, direction);
+        self.active_window = id;
+        if !arg.is_empty() {
+            self.edit_path(arg, false)?;
+        }
+        Ok(())
+    }
 
-        // Convert character index to byte index
-        let col_bytes = line
-            .char_indices()
-            .nth(col)
-            .map(|(i, _)| i)
-            .unwrap_or(line.len());
+    // `:close` and `Ctrl-W c`; the note stays in the buffer list
+    fn close_window(&mut self) -> Result<(), EditorError> {
+        if self.windows.len() < 2 {
+            self.status = "Cannot close last window".to_string();
+            return Ok(());
+        }
+        let closed = self.active_window;
+        self.split.close(closed);
+        self.windows.retain(|w| w.id != closed);
+        let next = self.split.windows()[0];
+        self.enter_window(next)
+    }
 
-        let query = match self.completion_state.completion_type {
-            CompletionType::File => line
-                .get(..col_bytes)
-                .and_then(|s| s.rfind("[["))
-                .map(|start| line[start + 2..col_bytes].to_string())
-                .unwrap_or_default(),
-            CompletionType::Tag => line
-                .get(..col_bytes)
-                .and_then(|s| s.rfind("#"))
-                .map(|start| line[start + 1..col_bytes].to_string())
-                .unwrap_or_default(),
-            CompletionType::Variable => line
-                .get(..col_bytes)
-                .and_then(|s| s.rfind("@"))
-                .map(|start| line[start + 1..col_bytes].to_string())
-                .unwrap_or_default(),
-            CompletionType::None => return Ok(()),
-        };
+    // `:only` and `Ctrl-W o`
+    fn only_window(&mut self) {
+        self.windows.retain(|w| w.id == self.active_window);
+        self.split = Split::Window(self.active_window);
+    }
 
-        self.completion_state.query = query.clone();
-        self.completion_state.suggestions = match self.completion_state.completion_type {
-            CompletionType::File => {
-                let search_pattern = format!("%{}%", query);
-                let sql = "SELECT DISTINCT result FROM (
-                    SELECT file_name AS result FROM files WHERE file_name LIKE ?
-                    UNION
-                    SELECT backlink AS result FROM backlinks WHERE backlink LIKE ?
-                ) LIMIT 10";
-                let mut stmt = self.db.prepare(sql)?;
-                let closure = |row: &rusqlite::Row| row.get::<_, String>(0);
-                let mapped_rows =
-                    stmt.query_map(params![search_pattern, search_pattern], closure)?;
-                mapped_rows.collect::<Result<Vec<_>, _>>()?
+    // Second key of a `Ctrl-W` command
+    fn window_command(&mut self, key: char) -> Result<(), EditorError> {
+        match key {
+            'h' | 'j' | 'k' | 'l' => {
+                let areas = self.split.areas(self.editor_area);
+                if let Some(id) = window::neighbour(&areas, self.active_window, key) {
+                    self.focus_window(id)?;
+                }
             }
-            CompletionType::Tag => {
-                let search_pattern = format!("%{}%", query);
-                let sql = "SELECT tag FROM tags WHERE tag LIKE ? LIMIT 10";
-                let mut stmt = self.db.prepare(sql)?;
-                let closure = |row: &rusqlite::Row| row.get::<_, String>(0);
-                let mapped_rows = stmt.query_map(params![search_pattern], closure)?;
-                mapped_rows.collect::<Result<Vec<_>, _>>()?
+            'w' | 'W' => {
+                let ids = self.split.windows();
+                let current = ids
+                    .iter()
+                    .position(|&id| id == self.active_window)
+                    .unwrap_or(0);
+                let next = if key == 'w' {
+                    (current + 1) % ids.len()
+                } else {
+                    (current + ids.len() - 1) % ids.len()
+                };
+                self.focus_window(ids[next])?;
             }
-            CompletionType::Variable => {
-                let possible = vec![
-                    "date".to_string(),
-                    "time".to_string(),
-                    "file-name".to_string(),
-                ];
-                possible
-                    .into_iter()
-                    .filter(|s| s.starts_with(&query))
-                    .collect::<Vec<String>>()
+            's' | 'S' => self.split_window(Direction::Vertical, "")?,
+            'v' => self.split_window(Direction::Horizontal, "")?,
+            'c' | 'q' => self.close_window()?,
+            'o' => self.only_window(),
+            '+' => {
+                self.split
+                    .resize(self.active_window, Direction::Vertical, 5);
             }
-            CompletionType::None => Vec::new(),
-        };
-
-        if !self.completion_state.suggestions.is_empty() {
-            self.completion_state.list_state.select(Some(0));
-        } else {
-            self.completion_state.list_state.select(None);
+            '-' => {
+                self.split
+                    .resize(self.active_window, Direction::Vertical, -5);
+            }
+            '>' => {
+                self.split
+                    .resize(self.active_window, Direction::Horizontal, 5);
+            }
+            '<' => {
+                self.split
+                    .resize(self.active_window, Direction::Horizontal, -5);
+            }
+            '=' => self.split.equalize(),
+            _ => self.status = format!("Unknown window command: {}", key),
         }
-
         Ok(())
     }
 
-    fn select_completion(&mut self) -> Result<(), EditorError> {
-        if let Some(selected) = self.completion_state.list_state.selected() {
-            if let Some(suggestion) = self.completion_state.suggestions.get(selected).cloned() {
-                let (current_row, current_col) = self.textarea.cursor();
-                let mut current_lines = self.textarea.lines().to_vec();
-
-                if let Some(original_line) = current_lines.get(current_row) {
-                    // Work with a cloned, owned version of the line
-                    let current_line_owned = original_line.clone();
-
-                    let (trigger, _trigger_len) = match self.completion_state.completion_type {
-                        CompletionType::File => ("[[", 2),
-                        CompletionType::Tag => ("#", 1),
-                        CompletionType::Variable => ("@", 1),
-                        _ => ("", 0),
-                    };
-
-                    // Get the character indices up to the current cursor position.
-                    let char_indices: Vec<(usize, char)> = current_line_owned
-                        .char_indices()
-                        .take(current_col)
-                        .collect();
-
-                    // Find the byte index of the trigger by iterating backwards
-                    let trigger_start_byte_option = char_indices
-                        .iter()
-                        .rev()
-                        .find(|(i, _)| current_line_owned[*i..].starts_with(trigger))
-                        .map(|(i, _)| *i);
-
-                    if let Some(start_byte) = trigger_start_byte_option {
-                        let prefix_text = current_line_owned[..start_byte].to_owned();
-                        let suffix_start_byte = current_line_owned
-                            .char_indices()
-                            .nth(current_col)
-                            .map(|(i, _)| i)
-                            .unwrap_or(current_line_owned.len());
-                        let suffix_text = current_line_owned[suffix_start_byte..].to_owned();
-
-                        let insert_text = match self.completion_state.completion_type {
-                            CompletionType::File => format!("[[{}]]", suggestion),
-                            CompletionType::Tag => format!("#{}", suggestion),
-                            CompletionType::Variable => match suggestion.as_str() {
-                                "date" => chrono::Local::now().format("%Y-%m-%d").to_string(),
-                                "time" => chrono::Local::now().format("%H:%M:%S").to_string(),
-                                "file-name" => Path::new(&self.file_path)
-                                    .file_name()
-                                    .and_then(|s| s.to_str())
-                                    .unwrap_or_default()
-                                    .to_string(),
-                                _ => suggestion,
-                            },
-                            _ => suggestion,
-                        };
-
-                        let new_line = format!("{}{}{}", prefix_text, insert_text, suffix_text);
-
-                        // The mutable borrow is fine now because we're assigning to the element
-                        // in the vector, not the `current_line` reference.
-                        current_lines[current_row] = new_line.clone();
-
-                        let mut new_textarea = TextArea::new(current_lines);
-                        set_textarea_delafult_style!(new_textarea);
-                        let new_cursor_col =
-                            prefix_text.chars().count() + insert_text.chars().count();
-                        new_textarea.move_cursor(tui_textarea::CursorMove::Jump(
-                            current_row as u16,
-                            new_cursor_col as u16,
-                        ));
+    // `.midetor_undo/` in the vault holds one timeline per note
+    fn undofile_path(&self) -> std::path::PathBuf {
+        let rel = self
+            .file_path
+            .strip_prefix(&self.base_dir)
+            .unwrap_or(&self.file_path)
+            .trim_start_matches('/')
+            .replace('%', "%25")
+            .replace('/', "%2F");
+        Path::new(&self.base_dir)
+            .join(".midetor_undo")
+            .join(format!("{}.json", rel))
+    }
 
-                        self.textarea = new_textarea;
-                    }
-                }
-            }
+    fn read_undofile(&self) -> Option<Timeline> {
+        if !self.undofile {
+            return None;
         }
+        let text = fs::read_to_string(self.undofile_path()).ok()?;
+        Timeline::from_json(&serde_json::from_str(&text).ok()?)
+    }
 
-        self.cancel_completion();
-        self.mode = Mode::Insert;
-        self.status = "Insert".to_string();
+    fn write_undofile(&self) -> Result<(), EditorError> {
+        let path = self.undofile_path();
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let text = serde_json::to_string(&self.timeline.to_json())
+            .map_err(|e| EditorError::Io(io::Error::other(e)))?;
+        fs::write(path, text)?;
         Ok(())
     }
 
-    fn cancel_completion(&mut self) {
-        self.completion_state.active = false;
-        self.completion_state.completion_type = CompletionType::None;
-        self.completion_state.query = String::new();
-        self.completion_state.suggestions = Vec::new();
-        self.completion_state.list_state = ListState::default();
-        self.mode = Mode::Insert;
-        self.status = "Insert".to_string();
+    // `:earlier` and `:later` with a count of states or a time like `10m`
+    fn time_travel(&mut self, arg: &str, later: bool) {
+        let Some(step) = undo::parse_step(arg) else {
+            self.status = format!("Invalid time: {}", arg);
+            return;
+        };
+        self.timeline
+            .record(self.textarea.lines(), Local::now().timestamp());
+        let index = self.timeline.travel(step, later);
+        self.timeline.go_to(index);
+        let lines = self.timeline.current().to_vec();
+        self.set_buffer_lines(&lines);
+        let when = chrono::DateTime::from_timestamp(self.timeline.time(index), 0)
+            .map(|t| {
+                t.with_timezone(&Local)
+                    .format("%Y-%m-%d %H:%M:%S")
+                    .to_string()
+            })
+            .unwrap_or_default();
+        self.status = format!(
+            "State {} of {}, from {}",
+            index + 1,
+            self.timeline.state_count(),
+            when
+        );
     }
 
-    fn load_tag_files(&mut self, tag: &str) -> Result<(), EditorError> {
-        let mut stmt = self.db.prepare(
-            "SELECT f.file_name, f.id FROM files f
-             JOIN file_tags ft ON f.id = ft.file_id
-             JOIN tags t ON ft.tag_id = t.id
-             WHERE t.tag = ?",
-        )?;
-        let files = stmt
-            .query_map([tag], |row| {
-                let file_name: String = row.get(0)?;
-                let file_id: i64 = row.get(1)?;
-                Ok((file_name, file_id))
-            })?
-            .collect::<Result<Vec<_>, _>>()?;
-        self.tag_files = files;
-        self.tag_files_state = ListState::default();
-        if !self.tag_files.is_empty() {
-            self.tag_files_state.select(Some(0));
-            self.status = format!("Select file for tag '{}'", tag);
-        } else {
-            self.status = format!("No files found for tag '{}'", tag);
+    // Make the buffer `lines`, replacing only the rows that differ
+    fn set_buffer_lines(&mut self, lines: &[String]) {
+        let current = self.textarea.lines().to_vec();
+        let prefix = current
+            .iter()
+            .zip(lines)
+            .take_while(|(a, b)| a == b)
+            .count();
+        let suffix = current[prefix..]
+            .iter()
+            .rev()
+            .zip(lines[prefix..].iter().rev())
+            .take_while(|(a, b)| a == b)
+            .count();
+        if prefix == current.len() && prefix == lines.len() {
+            return;
         }
-        Ok(())
+        self.replace_lines(
+            prefix,
+            current.len() - suffix,
+            &lines[prefix..lines.len() - suffix],
+        );
+        let row = prefix.min(self.textarea.lines().len() - 1);
+        self.textarea.move_cursor(CursorMove::Jump(row as u16, 0));
     }
 
-    fn select_tag_file(&mut self) -> Result<(), EditorError> {
-        if let Some(selected) = self.tag_files_state.selected() {
-            if let Some((_file_name, file_id)) = self.tag_files.get(selected) {
-                // Retrieve full path from database
-                let path: String = self
-                    .db
-                    .query_row("SELECT path FROM files WHERE id = ?", [file_id], |row| {
-                        row.get(0)
-                    })
-                    .map_err(|e| EditorError::Database(e))?;
-                self.history.truncate(self.history_index + 1);
-                self.history.push((path.clone(), *file_id));
-                self.history_index += 1;
-                self.open_file(path, *file_id)?;
+    // Replace rows start..end with new_lines so that `u` undoes it in one step.
+    // Returns the number of textarea edits it took.
+    fn replace_lines(&mut self, start: usize, end: usize, new_lines: &[String]) -> usize {
+        let line_count = self.textarea.lines().len();
+        let end = end.min(line_count);
+        let line_end = |row: usize| self.textarea.lines()[row].chars().count();
+        let mut text = new_lines.join("\n");
+        let (from, to) = if end < line_count {
+            if !new_lines.is_empty() {
+                text.push('\n');
+            }
+            ((start, 0), (end, 0))
+        } else if start > 0 {
+            if !new_lines.is_empty() {
+                text.insert(0, '\n');
             }
+            (
+                (start - 1, line_end(start - 1)),
+                (line_count - 1, line_end(line_count - 1)),
+            )
+        } else {
+            ((0, 0), (line_count - 1, line_end(line_count - 1)))
+        };
+
+        // Each non-empty delete and insert is one entry in the textarea history
+        let steps = usize::from(from != to) + usize::from(!text.is_empty());
+        if steps == 0 {
+            return 0;
         }
-        self.cancel_tag_files();
-        Ok(())
+        let cursor = self.textarea.cursor();
+        self.textarea.cancel_selection();
+        self.textarea
+            .move_cursor(CursorMove::Jump(from.0 as u16, from.1 as u16));
+        self.textarea.start_selection();
+        self.textarea
+            .move_cursor(CursorMove::Jump(to.0 as u16, to.1 as u16));
+        self.textarea.insert_str(&text);
+        self.textarea
+            .move_cursor(CursorMove::Jump(cursor.0 as u16, cursor.1 as u16));
+        if steps > 1 {
+            self.push_group(false, steps);
+        }
+        steps
     }
 
-    fn cancel_tag_files(&mut self) {
-        self.tag_files.clear();
-        self.tag_files_state = ListState::default();
-        self.mode = Mode::Normal;
-        self.status = "Normal".to_string();
-        self.key_sequence.clear();
+    // Undo `extra` earlier textarea edits together with a replace_lines that took `steps`
+    fn extend_undo_group(&mut self, steps: usize, extra: usize) {
+        if steps > 1 {
+            self.undo_groups.pop();
+        }
+        if steps + extra > 1 {
+            self.push_group(false, steps + extra);
+        }
     }
 
-    fn start_search(&mut self, search_type: SearchType) -> Result<(), EditorError> {
-        self.search_state.active = true;
-        self.search_state.search_type = search_type.clone();
-        self.search_state.query = String::new();
-        self.search_state.results = Vec::new();
-        self.search_state.list_state = ListState::default();
-        self.mode = Mode::Search;
-        self.view = View::Editor;
-        self.status = format!("Searching {:?}", search_type);
-        self.key_sequence.clear();
-        self.update_search_results()?;
-        Ok(())
+    fn buffer_hash(&self) -> u64 {
+        use std::hash::{Hash, Hasher};
+        let mut hasher = std::collections::hash_map::DefaultHasher::new();
+        self.textarea.lines().hash(&mut hasher);
+        hasher.finish()
     }
 
-    fn update_search_results(&mut self) -> Result<(), EditorError> {
-        match self.search_state.search_type {
-            SearchType::Backlinks => self.search_backlinks()?,
-            SearchType::Tags => self.search_tags()?,
-            SearchType::Files => self.search_files()?,
-            SearchType::None => {}
-        }
-        if !self.search_state.results.is_empty() {
-            self.search_state.list_state.select(Some(0));
+    // Remember that the last `steps` textarea edits (or undos, for `redo`) go together.
+    // Groups older than the textarea history could not be undone anyway.
+    fn push_group(&mut self, redo: bool, steps: usize) {
+        let hash = self.buffer_hash();
+        let groups = if redo {
+            &mut self.redo_groups
         } else {
-            self.search_state.list_state.select(None);
+            &mut self.undo_groups
+        };
+        groups.push((hash, steps));
+        if groups.len() > UNDO_HISTORY / 2 {
+            groups.remove(0);
         }
-        Ok(())
     }
 
-    fn search_backlinks(&mut self) -> Result<(), EditorError> {
-        let (row, current_col) = self.textarea.cursor();
-        let line = self.textarea.lines()[row].clone();
-        let mut target = if let Some(wikilink) = self.extract_wikilink(&line, current_col) {
-            wikilink
+    fn undo(&mut self) -> bool {
+        let grouped =
+            matches!(self.undo_groups.last(), Some(&(hash, _)) if hash == self.buffer_hash());
+        let steps = if grouped {
+            self.undo_groups.pop().map(|(_, steps)| steps).unwrap_or(1)
         } else {
-            // Use file_name from files table for the current file
-            let file_name: String = Path::new(&self.file_path)
-                .file_name()
-                .and_then(|s| s.to_str())
-                .unwrap_or_default()
-                .to_string();
-            file_name
+            1
         };
+        let mut undone = false;
+        for _ in 0..steps {
+            undone |= self.textarea.undo();
+        }
+        if undone && steps > 1 {
+            self.push_group(true, steps);
+        }
+        undone
+    }
 
-        if !target.ends_with(".md") {
-            target = format!("{}.md", target).clone();
+    fn redo(&mut self) -> bool {
+        let grouped =
+            matches!(self.redo_groups.last(), Some(&(hash, _)) if hash == self.buffer_hash());
+        let steps = if grouped {
+            self.redo_groups.pop().map(|(_, steps)| steps).unwrap_or(1)
+        } else {
+            1
         };
-        let query = "SELECT DISTINCT f.file_name, f.id
-                     FROM backlinks b
-                     JOIN files f ON b.file_id = f.id
-                     JOIN files fp ON b.backlink_id = fp.id
-                     WHERE fp.file_name LIKE ? AND f.id != ?";
-        let mut stmt = self.db.prepare(query)?;
-        let results = stmt
-            .query_map(params![format!("{}", target), self.file_id], |row| {
-                let file_name: String = row.get(0)?;
-                let file_id: i64 = row.get(1)?;
-                Ok((file_name, Some(file_id)))
-            })?
-            .collect::<Result<Vec<_>, _>>()?;
-
-        self.search_state.results = results;
-        self.search_state.list_state = ListState::default();
-        if !self.search_state.results.is_empty() {
-            self.search_state.list_state.select(Some(0));
+        let mut redone = false;
+        for _ in 0..steps {
+            redone |= self.textarea.redo();
         }
-        Ok(())
+        if redone && steps > 1 {
+            self.push_group(false, steps);
+        }
+        redone
     }
 
-    fn search_tags(&mut self) -> Result<(), EditorError> {
-        let query = "SELECT DISTINCT tag FROM tags WHERE tag LIKE ?";
-        let mut stmt = self.db.prepare(query)?;
-        let search_pattern = if self.search_state.query.is_empty() {
-            "%".to_string()
+    fn open_wikilink_file(&mut self, wikilink: String) -> Result<(), EditorError> {
+        // Extract file name from the path
+        let wikilink = if wikilink.ends_with(".md") {
+            wikilink
         } else {
-            format!("%{}%", self.search_state.query)
+            format!("{}.md", wikilink)
         };
-        let results = stmt
-            .query_map(params![search_pattern], |row| {
-                let tag: String = row.get(0)?;
-                Ok((tag, None))
-            })?
-            .collect::<Result<Vec<_>, _>>()?;
+        let file_name = Path::new(&wikilink)
+            .file_name()
+            .and_then(|s| s.to_str())
+            .map(|s| {
+                // if s.ends_with(".md") {
+                s.to_string()
+                // } else {
+                //     format!("{}.md", s)
+                // }
+            })
+            .unwrap_or_else(|| {
+                if wikilink.ends_with(".md") {
+                    wikilink.clone()
+                } else {
+                    format!("{}.md", wikilink)
+                }
+            });
 
-        self.search_state.results = results;
+        // Try to find the file by file_name in the database
+        let (file_id, path) = match self.resolve_wikilink(&file_name)? {
+            Some((id, path)) => (id, path),
+            None => {
+                // File doesn't exist; create it in the base_dir
+                let path = format!("{}/{}", self.base_dir, wikilink); // Use original wikilink as path
+                if let Some(parent) = Path::new(&path).parent() {
+                    fs::create_dir_all(parent)?;
+                }
+                // Only write an empty file if it doesn't already exist
+                if !Path::new(&path).exists() {
+                    fs::write(&path, "")?;
+                }
+                let output = Command::new("markdown-scanner")
+                    .arg(&path)
+                    .arg(&self.base_dir)
+                    .output()?;
+                if !output.status.success() {
+                    let error_msg = String::from_utf8_lossy(&output.stderr).into_owned();
+                    return Err(EditorError::Scanner(error_msg));
+                }
+                let mut stmt = self
+                    .db
+                    .prepare("SELECT id FROM files WHERE file_name = ?")?;
+                let file_id = stmt
+                    .query_row([&file_name], |row| row.get(0))
+                    .map_err(|e| EditorError::Database(e))?;
+                (file_id, path)
+            }
+        };
+
+        self.jump_to_file(path, file_id)?;
         Ok(())
     }
 
-    fn search_files(&mut self) -> Result<(), EditorError> {
-        let query = "SELECT file_name, id FROM files WHERE file_name LIKE ?";
-        let mut stmt = self.db.prepare(query)?;
-        let search_pattern = if self.search_state.query.is_empty() {
-            "%".to_string()
+    fn resolve_wikilink(&self, file_name: &str) -> Result<Option<(i64, String)>, EditorError> {
+        let mut stmt = self
+            .db
+            .prepare("SELECT id, path FROM files WHERE file_name = ?")?;
+        match stmt.query_row([file_name], |row| {
+            Ok((row.get::<_, i64>(0)?, row.get::<_, String>(1)?))
+        }) {
+            Ok(found) => Ok(Some(found)),
+            Err(rusqlite::Error::QueryReturnedNoRows) => Ok(None),
+            Err(e) => Err(EditorError::Database(e)),
+        }
+    }
+
+    fn open_note_preview(&mut self) -> Result<(), EditorError> {
+        let (row, col) = self.textarea.cursor();
+        let line = self.textarea.lines()[row].clone();
+        let Some(link) = self.extract_wikilink(&line, col) else {
+            self.status = "No wikilink under cursor".to_string();
+            return Ok(());
+        };
+
+        // Drop an alias and split off a heading: [[Note#Heading|alias]]
+        let target = link.split('|').next().unwrap_or_default();
+        let (note, heading) = match target.split_once('#') {
+            Some((note, heading)) => (note.trim(), Some(heading.trim())),
+            None => (target.trim(), None),
+        };
+        let file_name = if note.ends_with(".md") {
+            note.to_string()
         } else {
-            format!("%{}%", self.search_state.query)
+            format!("{}.md", note)
         };
-        let results = stmt
-            .query_map(params![search_pattern], |row| {
-                let file_name: String = row.get(0)?;
-                let file_id: i64 = row.get(1)?;
-                Ok((file_name, Some(file_id)))
-            })?
-            .collect::<Result<Vec<_>, _>>()?;
+        let file_name = Path::new(&file_name)
+            .file_name()
+            .and_then(|s| s.to_str())
+            .unwrap_or_default()
+            .to_string();
 
-        self.search_state.results = results;
+        let Some((_, path)) = self.resolve_wikilink(&file_name)? else {
+            self.status = format!("Note not found: {}", note);
+            return Ok(());
+        };
+        // The database can still list a note that was moved or deleted
+        let Ok(content) = fs::read_to_string(&path) else {
+            self.status = format!("Cannot read {}", gettitle!(&path));
+            return Ok(());
+        };
+        let lines: Vec<&str> = content.lines().collect();
+        let start = heading
+            .and_then(|heading| {
+                lines
+                    .iter()
+                    .position(|l| l.starts_with('#') && l.trim_start_matches('#').trim() == heading)
+            })
+            .unwrap_or(0);
+
+        self.preview_state = PreviewState {
+            active: true,
+            link: link.clone(),
+            title: gettitle!(&path),
+            lines: lines
+                .iter()
+                .skip(start)
+                .take(NOTE_PREVIEW_LINES)
+                .map(|s| s.to_string())
+                .collect(),
+            scroll: 0,
+            anchor_row: row,
+        };
+        self.status = format!("Preview: {}", link);
         Ok(())
     }
 
-    fn extract(&self, line: &str, cursor_col: usize, start: &str, end: &str) -> Option<String> {
-        for (start_byte_index, _) in line.match_indices(start) {
-            let mut content_start_byte = start_byte_index + start.len();
-            // Skip whitespace after start delimiter
-            while content_start_byte < line.len() && line[content_start_byte..].starts_with(' ') {
-                content_start_byte += 1;
-            }
-            // Use end of line if end delimiter is empty, otherwise find end delimiter
-            let end_byte_index = if end.is_empty() {
-                line.len()
-            } else {
-                line[content_start_byte..]
-                    .find(end)
-                    .map(|relative| content_start_byte + relative)
-                    .unwrap_or(line.len())
-            };
-            let start_char_index = line[..start_byte_index].chars().count();
-            let end_char_index = line[..end_byte_index].chars().count();
-            if cursor_col >= start_char_index && cursor_col <= end_char_index {
-                if content_start_byte <= end_byte_index {
-                    let content = line[content_start_byte..end_byte_index].trim();
-                    if !content.is_empty() {
-                        return Some(content.to_string());
-                    }
-                }
-            }
-        }
-        None
+    fn close_note_preview(&mut self) {
+        self.preview_state.active = false;
+        self.preview_state.lines.clear();
+        self.preview_state.scroll = 0;
     }
 
-    fn extract_tag(&self, line: &str, cursor_col: usize) -> Option<String> {
-        // Try normal tag (#tag)
-        if let Some(result) = self.extract(line, cursor_col, "#", " ") {
-            return Some(result);
+    fn follow_backlink(&mut self, index: usize) -> Result<(), EditorError> {
+        let (current_row, current_col) = self.textarea.cursor();
+        let line = self.textarea.lines()[current_row].clone();
+
+        // If a specific index from the old logic is passed, use it.
+        // Otherwise (when index is usize::MAX), extract the link from the cursor position.
+        let wikilink = if index < self.backlinks.len() {
+            self.backlinks[index].0.clone()
+        } else {
+            self.extract_wikilink(&line, current_col).ok_or_else(|| {
+                EditorError::InvalidBacklink("No valid wikilink found at cursor".to_string())
+            })?
+        };
+
+        // Clean incomplete autocompletions
+        if line.contains("[[") && !line.contains("]]") {
+            let mut new_lines = self.textarea.lines().to_vec();
+            new_lines[current_row] = line[..line.rfind("[[").unwrap_or(line.len())].to_string();
+            self.textarea = TextArea::new(new_lines);
+            set_textarea_delafult_style!(self.textarea);
+            self.textarea
+                .move_cursor(tui_textarea::CursorMove::Jump(current_row as u16, 0));
         }
-        // Fallback to YAML tag (- tag)
-        self.extract(line, cursor_col, " - ", "")
+
+        self.open_wikilink_file(wikilink)?;
+
+        Ok(())
     }
 
-    fn extract_wikilink(&self, line: &str, cursor_col: usize) -> Option<String> {
-        self.extract(line, cursor_col, "[[", "]]")
+    fn navigate_back(&mut self) -> Result<(), EditorError> {
+        if self.history_index > 0 {
+            self.remember_cursor();
+            self.history_index -= 1;
+            self.go_to_history_entry()?;
+        } else {
+            self.status = "No previous file in history".to_string();
+        }
+        Ok(())
     }
-    fn select_search_result(&mut self) -> Result<(), EditorError> {
-        if let Some(selected) = self.search_state.list_state.selected() {
-            if let Some((file_name, file_id)) = self.search_state.results.get(selected).cloned() {
-                match self.search_state.search_type {
-                    SearchType::Backlinks | SearchType::Files => {
-                        if let Some(file_id) = file_id {
-                            // Retrieve full path from database
-                            let path: String = self
-                                .db
-                                .query_row(
-                                    "SELECT path FROM files WHERE id = ?",
-                                    [file_id],
-                                    |row| row.get(0),
-                                )
-                                .map_err(|e| EditorError::Database(e))?;
-                            self.history.truncate(self.history_index + 1);
-                            self.history.push((path.clone(), file_id));
-                            self.history_index += 1;
-                            self.open_file(path, file_id)?;
-                        }
-                    }
-                    SearchType::Tags => {
-                        self.load_tag_files(&file_name)?;
-                        if !self.tag_files.is_empty() {
-                            self.mode = Mode::TagFiles;
-                        } else {
-                            self.cancel_search();
-                        }
-                    }
-                    SearchType::None => {}
-                }
-            }
+
+    fn navigate_forward(&mut self) -> Result<(), EditorError> {
+        if self.history_index < self.history.len() - 1 {
+            self.remember_cursor();
+            self.history_index += 1;
+            self.go_to_history_entry()?;
         } else {
-            self.status = "No result selected".to_string();
+            self.status = "No next file in history".to_string();
         }
-        if self.mode != Mode::TagFiles {
-            self.cancel_search();
+        Ok(())
+    }
+
+    // Keep the cursor in the current history entry so coming back restores it
+    fn remember_cursor(&mut self) {
+        let cursor = self.textarea.cursor();
+        if let Some(entry) = self.history.get_mut(self.history_index)
+            && entry.0 == self.file_path
+        {
+            entry.2 = cursor;
+        }
+    }
+
+    fn go_to_history_entry(&mut self) -> Result<(), EditorError> {
+        let (path, file_id, cursor) = self.history[self.history_index].clone();
+        if path != self.file_path {
+            self.open_file(path, file_id)?;
         }
+        self.jump_cursor(cursor);
         Ok(())
     }
 
-    fn cancel_search(&mut self) {
-        self.search_state.active = false;
-        self.search_state.search_type = SearchType::None;
-        self.search_state.query = String::new();
-        self.search_state.results = Vec::new();
-        self.search_state.list_state = ListState::default();
-        self.mode = Mode::Normal;
-        self.status = "Normal".to_string();
-        self.key_sequence.clear();
+    // Open a file as a new entry in the jump list
+    fn jump_to_file(&mut self, path: String, file_id: i64) -> Result<(), EditorError> {
+        self.remember_cursor();
+        self.history.truncate(self.history_index + 1);
+        self.history.push((path.clone(), file_id, (0, 0)));
+        self.history_index += 1;
+        self.open_file(path, file_id)
     }
 
-    fn build_root(&self) -> Vec<TreeNode> {
-        let mut root = Vec::new();
-        if let Ok(iter) = fs::read_dir(&self.base_dir) {
-            for entry in iter {
-                if let Ok(entry) = entry {
-                    let name = entry.file_name().to_string_lossy().to_string();
-                    if name.starts_with(".") {
-                        continue;
-                    }
-                    let p = entry.path();
-                    let rel = name;
-                    if p.is_dir() {
-                        root.push(TreeNode::Dir {
-                            path: rel,
-                            expanded: false,
-                            children: Vec::new(),
-                        });
-                    } else if p.extension().and_then(|s| s.to_str()) == Some("md") {
-                        root.push(TreeNode::File(rel));
-                    }
-                }
-            }
-        }
-        let mut sorted = root;
-        Self::sort_entries(
-            &mut sorted,
-            self.sort_by.clone(),
-            self.sort_asc,
-            &self.base_dir,
-        );
-        sorted
+    // Move within the file and record both ends in the jump list
+    fn jump_within_file(&mut self, cursor: (usize, usize)) {
+        self.remember_cursor();
+        self.jump_cursor(cursor);
+        self.history.truncate(self.history_index + 1);
+        self.history
+            .push((self.file_path.clone(), self.file_id, self.textarea.cursor()));
+        self.history_index += 1;
     }
 
-    fn sort_nodes(nodes: &mut Vec<TreeNode>, sort_by: SortBy, sort_asc: bool, base_dir: &str) {
-        Self::sort_entries(nodes, sort_by.clone(), sort_asc, base_dir);
-        for node in nodes.iter_mut() {
-            if let TreeNode::Dir { children, .. } = node {
-                Self::sort_nodes(children, sort_by.clone(), sort_asc, base_dir);
-            }
-        }
+    // Put the cursor at (row, col), clamped to the buffer
+    fn jump_cursor(&mut self, (row, col): (usize, usize)) {
+        let row = row.min(self.textarea.lines().len() - 1);
+        let col = col.min(self.textarea.lines()[row].chars().count());
+        self.textarea
+            .move_cursor(CursorMove::Jump(row as u16, col as u16));
     }
 
-    fn build_tree_node(
-        full_path: &Path,
-        rel_path: &str,
-        sort_by: SortBy,
-        sort_asc: bool,
-        base_dir: &str,
-    ) -> Vec<TreeNode> {
-        let mut entries = Vec::new();
-        if let Ok(iter) = fs::read_dir(full_path) {
-            for entry in iter {
-                if let Ok(entry) = entry {
-                    let name = entry.file_name().to_string_lossy().to_string();
-                    if name.starts_with(".") {
-                        continue;
-                    }
-                    let p = entry.path();
-                    let rel = if rel_path.is_empty() {
-                        name
-                    } else {
-                        format!("{}/{}", rel_path, name)
-                    };
-                    if p.is_dir() {
-                        entries.push(TreeNode::Dir {
-                            path: rel,
-                            expanded: false,
-                            children: Vec::new(),
-                        });
-                    } else if p.extension().and_then(|s| s.to_str()) == Some("md") {
-                        entries.push(TreeNode::File(rel));
-                    }
-                }
-            }
-        }
-        let mut sorted = entries;
-        Self::sort_entries(&mut sorted, sort_by, sort_asc, base_dir);
-        sorted
+    fn vault_relative<'a>(&self, path: &'a str) -> &'a str {
+        path.strip_prefix(&self.base_dir)
+            .map(|p| p.trim_start_matches('/'))
+            .unwrap_or(path)
     }
 
-    fn sort_entries(entries: &mut Vec<TreeNode>, sort_by: SortBy, sort_asc: bool, base_dir: &str) {
-        entries.sort_by(|a, b| {
-            let (name_a, is_dir_a) = match a {
-                TreeNode::File(p) => (p.as_str(), false),
-                TreeNode::Dir { path, .. } => (path.as_str(), true),
-            };
-            let (name_b, is_dir_b) = match b {
-                TreeNode::File(p) => (p.as_str(), false),
-                TreeNode::Dir { path, .. } => (path.as_str(), true),
+    // Global marks (`mA`-`mZ`) live in the vault database next to the scanner's
+    // tables, keyed by vault-relative path so a rescan of the note keeps them
+    fn ensure_marks_table(&self) -> Result<(), EditorError> {
+        let keyed_by_id: i64 = self.db.query_row(
+            "SELECT COUNT(*) FROM pragma_table_info('marks') WHERE name = 'file_id'",
+            [],
+            |row| row.get(0),
+        )?;
+        let transaction = self.db.unchecked_transaction()?;
+        if keyed_by_id > 0 {
+            self.db
+                .execute("ALTER TABLE marks RENAME TO marks_by_id", [])?;
+        }
+        self.db.execute(
+            "CREATE TABLE IF NOT EXISTS marks (
+                name TEXT PRIMARY KEY,
+                path TEXT NOT NULL,
+                row INTEGER NOT NULL,
+                col INTEGER NOT NULL
+            )",
+            [],
+        )?;
+        if keyed_by_id > 0 {
+            let old = {
+                let mut stmt = self.db.prepare(
+                    "SELECT m.name, f.path, m.row, m.col FROM marks_by_id m
+                     JOIN files f ON f.id = m.file_id",
+                )?;
+                stmt.query_map([], |row| {
+                    Ok((
+                        row.get::<_, String>(0)?,
+                        row.get::<_, String>(1)?,
+                        row.get::<_, i64>(2)?,
+                        row.get::<_, i64>(3)?,
+                    ))
+                })?
+                .collect::<Result<Vec<_>, _>>()?
             };
-            if is_dir_a != is_dir_b {
-                is_dir_b.cmp(&is_dir_a) // dirs first
-            } else {
-                if sort_by == SortBy::Name {
-                    if sort_asc {
-                        name_a.cmp(name_b)
-                    } else {
-                        name_b.cmp(name_a)
-                    }
-                } else {
-                    let path_a = Path::new(base_dir).join(name_a);
-                    let path_b = Path::new(base_dir).join(name_b);
-                    let time_a = fs::metadata(&path_a)
-                        .and_then(|m| m.modified())
-                        .map_err(|e| {
-                            eprintln!("Error getting metadata for {}: {}", path_a.display(), e)
-                        })
-                        .unwrap_or(SystemTime::UNIX_EPOCH);
-                    let time_b = fs::metadata(&path_b)
-                        .and_then(|m| m.modified())
-                        .map_err(|e| {
-                            eprintln!("Error getting metadata for {}: {}", path_b.display(), e)
-                        })
-                        .unwrap_or(SystemTime::UNIX_EPOCH);
-                    if sort_asc {
-                        time_a.cmp(&time_b)
-                    } else {
-                        time_b.cmp(&time_a)
-                    }
-                }
+            for (name, path, row, col) in old {
+                self.db.execute(
+                    "INSERT OR REPLACE INTO marks (name, path, row, col) VALUES (?, ?, ?, ?)",
+                    params![name, self.vault_relative(&path), row, col],
+                )?;
             }
-        });
+            self.db.execute("DROP TABLE marks_by_id", [])?;
+        }
+        transaction.commit()?;
+        Ok(())
     }
 
-    fn toggle_sort_modified(&mut self) {
-        if self.sort_by == SortBy::Modified {
-            self.sort_asc = !self.sort_asc;
+    // `m{a-z}` marks a spot in this file, `m{A-Z}` one that `'` can reach from any file
+    fn set_mark(&mut self, name: char) -> Result<(), EditorError> {
+        let cursor = self.textarea.cursor();
+        if name.is_ascii_lowercase() {
+            self.marks.insert((self.file_path.clone(), name), cursor);
+        } else if name.is_ascii_uppercase() {
+            self.ensure_marks_table()?;
+            self.db.execute(
+                "INSERT OR REPLACE INTO marks (name, path, row, col) VALUES (?, ?, ?, ?)",
+                params![
+                    name.to_string(),
+                    self.vault_relative(&self.file_path),
+                    cursor.0,
+                    cursor.1
+                ],
+            )?;
         } else {
-            self.sort_by = SortBy::Modified; // Keep enum variant as Created for compatibility
-            self.sort_asc = false;
+            self.status = format!("Invalid mark: {}", name);
+            return Ok(());
         }
-        self.status = format!(
-            "Sorted by modification time ({})",
-            if self.sort_asc {
-                "ascending"
-            } else {
-                "descending"
-            }
-        );
-    }
-
-    fn update_tree_sort(&mut self) {
-        Self::sort_nodes(
-            &mut self.file_tree,
-            self.sort_by.clone(),
-            self.sort_asc,
-            &self.base_dir,
-        );
-        self.update_visible();
+        self.status = format!("Mark {} set", name);
+        Ok(())
     }
 
-    fn toggle_sort_name(&mut self) {
-        if self.sort_by == SortBy::Name {
-            self.sort_asc = !self.sort_asc;
+    // `'a` jumps to the line of a mark, `` `a `` to its exact position
+    fn jump_to_mark(&mut self, name: char, linewise: bool) -> Result<(), EditorError> {
+        let target = if name.is_ascii_lowercase() {
+            self.marks
+                .get(&(self.file_path.clone(), name))
+                .map(|&cursor| (self.file_path.clone(), cursor))
+        } else if name.is_ascii_uppercase() {
+            self.ensure_marks_table()?;
+            self.db
+                .query_row(
+                    "SELECT path, row, col FROM marks WHERE name = ?",
+                    [name.to_string()],
+                    |row| {
+                        Ok((
+                            format!("{}/{}", self.base_dir, row.get::<_, String>(0)?),
+                            (row.get::<_, usize>(1)?, row.get::<_, usize>(2)?),
+                        ))
+                    },
+                )
+                .ok()
         } else {
-            self.sort_by = SortBy::Name;
-            self.sort_asc = true;
+            None
+        };
+        let Some((path, (row, col))) = target else {
+            self.status = format!("Mark {} not set", name);
+            return Ok(());
+        };
+        let other_file = path != self.file_path;
+        if other_file {
+            // The note may have been rescanned under a new id since the mark was set
+            let file_id = match self.buffer_index_of(Path::new(&path)) {
+                Some(index) => self.buffers[index].file_id,
+                None if Path::new(&path).exists() => match App::get_file_id(&self.db, &path) {
+                    Ok(id) => id,
+                    Err(EditorError::FileNotFound(_)) => {
+                        self.run_scanner(&path)?;
+                        App::get_file_id(&self.db, &path)?
+                    }
+                    Err(e) => return Err(e),
+                },
+                None => {
+                    self.status = format!("Mark {}: {} no longer exists", name, gettitle!(&path));
+                    return Ok(());
+                }
+            };
+            self.jump_to_file(path, file_id)?;
+        }
+        let col = if linewise {
+            let row = row.min(self.textarea.lines().len() - 1);
+            self.textarea.lines()[row]
+                .chars()
+                .take_while(|c| c.is_whitespace())
+                .count()
+        } else {
+            col
+        };
+        if other_file {
+            self.jump_cursor((row, col));
+        } else {
+            self.jump_within_file((row, col));
         }
+        self.status = format!("Mark {}", name);
+        Ok(())
     }
 
-    fn update_visible(&mut self) {
-        let mut visible = Vec::new();
-        Self::add_nodes_to_visible(&self.file_tree, 0, &mut visible);
-        self.visible_items = visible;
+    fn echo(&mut self, message: &str) -> Result<(), EditorError> {
+        self.status = format!("{}", message);
+        self.view = View::Info;
+        Ok(())
     }
 
-    fn add_nodes_to_visible(nodes: &[TreeNode], depth: usize, visible: &mut Vec<TreeItem>) {
-        for node in nodes {
-            match node {
-                TreeNode::File(p) => {
-                    let display = format!(
-                        "{}{} {}",
-                        "  ".repeat(depth),
-                        '',
-                        Path::new(p)
-                            .file_name()
-                            .and_then(|s| s.to_str())
-                            .unwrap_or("")
-                    );
-                    visible.push(TreeItem {
-                        display,
-                        path: p.clone(),
-                        is_dir: false,
-                        depth,
-                    });
-                }
-                TreeNode::Dir {
-                    path,
-                    expanded,
-                    children,
-                } => {
-                    let display = format!(
-                        "{}{} {}/",
-                        "  ".repeat(depth),
-                        '',
-                        Path::new(path)
-                            .file_name()
-                            .and_then(|s| s.to_str())
-                            .unwrap_or("")
-                    );
-                    visible.push(TreeItem {
-                        display,
-                        path: path.clone(),
-                        is_dir: true,
-                        depth,
-                    });
-                    if *expanded {
-                        Self::add_nodes_to_visible(children, depth + 1, visible);
-                    }
-                }
-            }
-        }
+    fn start_completion(&mut self, completion_type: CompletionType) {
+        self.completion_state.active = true;
+        self.completion_state.completion_type = completion_type;
+        self.completion_state.query = String::new();
+        self.completion_state.suggestions = Vec::new();
+        self.completion_state.list_state = ListState::default();
+        self.mode = Mode::Complete;
+        self.completion_state.trigger_start = self.textarea.cursor();
+        self.status = format!("Completing {:?}", self.completion_state.completion_type);
     }
 
-    fn find_node_mut<'a>(
-        nodes: &'a mut Vec<TreeNode>,
-        path_segments: &[&str],
-    ) -> Option<&'a mut TreeNode> {
-        if path_segments.is_empty() {
-            return None;
-        }
-        let mut current: &mut Vec<TreeNode> = nodes;
-        for (i, &name) in path_segments.iter().enumerate() {
-            let mut found_idx = None;
-            for (idx, node) in current.iter_mut().enumerate() {
-                let node_name = match node {
-                    TreeNode::File(p) => Path::new(p.as_str())
-                        .file_name()
-                        .and_then(|s| s.to_str())
-                        .unwrap_or(""),
-                    TreeNode::Dir { path, .. } => Path::new(path.as_str())
-                        .file_name()
-                        .and_then(|s| s.to_str())
-                        .unwrap_or(""),
-                };
-                if node_name == name {
-                    found_idx = Some(idx);
-                    break;
-                }
-            }
-            if let Some(idx) = found_idx {
-                if i == path_segments.len() - 1 {
-                    return Some(&mut current[idx]);
-                } else {
-                    if let TreeNode::Dir { children, .. } = &mut current[idx] {
-                        current = children;
-                    } else {
-                        return None;
-                    }
-                }
-            } else {
-                return None;
+    fn update_completion(&mut self) -> Result<(), EditorError> {
+        let (row, col) = self.textarea.cursor();
+        let line = self.textarea.lines()[row].clone();
+
+        // Convert character index to byte index
+        let col_bytes = line
+            .char_indices()
+            .nth(col)
+            .map(|(i, _)| i)
+            .unwrap_or(line.len());
+
+        let query = match self.completion_state.completion_type {
+            CompletionType::File => line
+                .get(..col_bytes)
+                .and_then(|s| s.rfind("[["))
+                .map(|start| line[start + 2..col_bytes].to_string())
+                .unwrap_or_default(),
+            CompletionType::Tag => line
+                .get(..col_bytes)
+                .and_then(|s| s.rfind("#"))
+                .map(|start| line[start + 1..col_bytes].to_string())
+                .unwrap_or_default(),
+            CompletionType::Variable => line
+                .get(..col_bytes)
+                .and_then(|s| s.rfind("@"))
+                .map(|start| line[start + 1..col_bytes].to_string())
+                .unwrap_or_default(),
+            CompletionType::None => return Ok(()),
+        };
+
+        self.completion_state.query = query.clone();
+        self.completion_state.suggestions = match self.completion_state.completion_type {
+            CompletionType::File => {
+                let search_pattern = format!("%{}%", query);
+                let sql = "SELECT DISTINCT result FROM (
+                    SELECT file_name AS result FROM files WHERE file_name LIKE ?
+                    UNION
+                    SELECT backlink AS result FROM backlinks WHERE backlink LIKE ?
+                ) LIMIT 10";
+                let mut stmt = self.db.prepare(sql)?;
+                let closure = |row: &rusqlite::Row| row.get::<_, String>(0);
+                let mapped_rows =
+                    stmt.query_map(params![search_pattern, search_pattern], closure)?;
+                mapped_rows.collect::<Result<Vec<_>, _>>()?
+            }
+            CompletionType::Tag => {
+                let search_pattern = format!("%{}%", query);
+                let sql = "SELECT tag FROM tags WHERE tag LIKE ? LIMIT 10";
+                let mut stmt = self.db.prepare(sql)?;
+                let closure = |row: &rusqlite::Row| row.get::<_, String>(0);
+                let mapped_rows = stmt.query_map(params![search_pattern], closure)?;
+                mapped_rows.collect::<Result<Vec<_>, _>>()?
+            }
+            CompletionType::Variable => {
+                let possible = vec![
+                    "date".to_string(),
+                    "time".to_string(),
+                    "file-name".to_string(),
+                ];
+                possible
+                    .into_iter()
+                    .filter(|s| s.starts_with(&query))
+                    .collect::<Vec<String>>()
             }
+            CompletionType::None => Vec::new(),
+        };
+
+        if !self.completion_state.suggestions.is_empty() {
+            self.completion_state.list_state.select(Some(0));
+        } else {
+            self.completion_state.list_state.select(None);
         }
-        None
+
+        Ok(())
     }
 
-    fn toggle_expand_dir(&mut self, index: usize) -> Result<(), EditorError> {
-        if index >= self.visible_items.len() {
-            return Ok(());
-        }
-        let item = self.visible_items[index].clone();
-        if item.is_dir {
-            let segments: Vec<&str> = item.path.split('/').collect();
-            if let Some(node) = Self::find_node_mut(&mut self.file_tree, &segments) {
-                if let TreeNode::Dir {
-                    ref mut expanded,
-                    ref mut children,
-                    ref path,
-                } = *node
-                {
-                    *expanded = !*expanded;
-                    if *expanded && children.is_empty() {
-                        let full = Path::new(&self.base_dir).join(path);
-                        *children = Self::build_tree_node(
-                            &full,
-                            path.as_str(),
-                            self.sort_by.clone(),
-                            self.sort_asc,
-                            &self.base_dir,
-                        );
+    fn select_completion(&mut self) -> Result<(), EditorError> {
+        if let Some(selected) = self.completion_state.list_state.selected() {
+            if let Some(suggestion) = self.completion_state.suggestions.get(selected).cloned() {
+                let (current_row, current_col) = self.textarea.cursor();
+                let mut current_lines = self.textarea.lines().to_vec();
+
+                if let Some(original_line) = current_lines.get(current_row) {
+                    // Work with a cloned, owned version of the line
+                    let current_line_owned = original_line.clone();
+
+                    let (trigger, _trigger_len) = match self.completion_state.completion_type {
+                        CompletionType::File => ("[[", 2),
+                        CompletionType::Tag => ("#", 1),
+                        CompletionType::Variable => ("@", 1),
+                        _ => ("", 0),
+                    };
+
+                    // Get the character indices up to the current cursor position.
+                    let char_indices: Vec<(usize, char)> = current_line_owned
+                        .char_indices()
+                        .take(current_col)
+                        .collect();
+
+                    // Find the byte index of the trigger by iterating backwards
+                    let trigger_start_byte_option = char_indices
+                        .iter()
+                        .rev()
+                        .find(|(i, _)| current_line_owned[*i..].starts_with(trigger))
+                        .map(|(i, _)| *i);
+
+                    if let Some(start_byte) = trigger_start_byte_option {
+                        let prefix_text = current_line_owned[..start_byte].to_owned();
+                        let suffix_start_byte = current_line_owned
+                            .char_indices()
+                            .nth(current_col)
+                            .map(|(i, _)| i)
+                            .unwrap_or(current_line_owned.len());
+                        let suffix_text = current_line_owned[suffix_start_byte..].to_owned();
+
+                        let insert_text = match self.completion_state.completion_type {
+                            CompletionType::File => format!("[[{}]]", suggestion),
+                            CompletionType::Tag => format!("#{}", suggestion),
+                            CompletionType::Variable => match suggestion.as_str() {
+                                "date" => chrono::Local::now().format("%Y-%m-%d").to_string(),
+                                "time" => chrono::Local::now().format("%H:%M:%S").to_string(),
+                                "file-name" => Path::new(&self.file_path)
+                                    .file_name()
+                                    .and_then(|s| s.to_str())
+                                    .unwrap_or_default()
+                                    .to_string(),
+                                _ => suggestion,
+                            },
+                            _ => suggestion,
+                        };
+
+                        let new_line = format!("{}{}{}", prefix_text, insert_text, suffix_text);
+
+                        // The mutable borrow is fine now because we're assigning to the element
+                        // in the vector, not the `current_line` reference.
+                        current_lines[current_row] = new_line.clone();
+
+                        let mut new_textarea = TextArea::new(current_lines);
+                        set_textarea_delafult_style!(new_textarea);
+                        let new_cursor_col =
+                            prefix_text.chars().count() + insert_text.chars().count();
+                        new_textarea.move_cursor(tui_textarea::CursorMove::Jump(
+                            current_row as u16,
+                            new_cursor_col as u16,
+                        ));
+
+                        self.textarea = new_textarea;
                     }
-                    self.update_visible();
                 }
             }
         }
+
+        self.cancel_completion();
+        self.mode = Mode::Insert;
+        self.status = "Insert".to_string();
         Ok(())
     }
 
-    fn expand_dir(&mut self, index: usize) -> Result<(), EditorError> {
-        if index >= self.visible_items.len() {
-            return Ok(());
+    fn cancel_completion(&mut self) {
+        self.completion_state.active = false;
+        self.completion_state.completion_type = CompletionType::None;
+        self.completion_state.query = String::new();
+        self.completion_state.suggestions = Vec::new();
+        self.completion_state.list_state = ListState::default();
+        self.mode = Mode::Insert;
+        self.status = "Insert".to_string();
+    }
+
+    fn load_tag_files(&mut self, tag: &str) -> Result<(), EditorError> {
+        let mut stmt = self.db.prepare(
+            "SELECT f.file_name, f.id FROM files f
+             JOIN file_tags ft ON f.id = ft.file_id
+             JOIN tags t ON ft.tag_id = t.id
+             WHERE t.tag = ?",
+        )?;
+        let files = stmt
+            .query_map([tag], |row| {
+                let file_name: String = row.get(0)?;
+                let file_id: i64 = row.get(1)?;
+                Ok((file_name, file_id))
+            })?
+            .collect::<Result<Vec<_>, _>>()?;
+        self.tag_files = files;
+        self.tag_files_state = ListState::default();
+        if !self.tag_files.is_empty() {
+            self.tag_files_state.select(Some(0));
+            self.status = format!("Select file for tag '{}'", tag);
+        } else {
+            self.status = format!("No files found for tag '{}'", tag);
         }
-        let item = self.visible_items[index].clone();
-        if item.is_dir {
-            let segments: Vec<&str> = item.path.split('/').collect();
-            if let Some(node) = Self::find_node_mut(&mut self.file_tree, &segments) {
-                if let TreeNode::Dir {
-                    ref mut expanded,
-                    ref mut children,
-                    ref path,
-                } = *node
-                {
-                    if !*expanded {
-                        *expanded = true;
-                        if children.is_empty() {
-                            let full = Path::new(&self.base_dir).join(path);
-                            *children = Self::build_tree_node(
-                                &full,
-                                path.as_str(),
-                                self.sort_by.clone(),
-                                self.sort_asc,
-                                &self.base_dir,
-                            );
-                        }
-                        self.update_visible();
-                    }
-                }
+        Ok(())
+    }
+
+    fn select_tag_file(&mut self) -> Result<(), EditorError> {
+        if let Some(selected) = self.tag_files_state.selected() {
+            if let Some((_file_name, file_id)) = self.tag_files.get(selected) {
+                // Retrieve full path from database
+                let path: String = self
+                    .db
+                    .query_row("SELECT path FROM files WHERE id = ?", [file_id], |row| {
+                        row.get(0)
+                    })
+                    .map_err(|e| EditorError::Database(e))?;
+                self.jump_to_file(path, *file_id)?;
             }
         }
+        self.cancel_tag_files();
         Ok(())
     }
 
-    fn collapse_dir(&mut self, index: usize) -> Result<(), EditorError> {
-        if index >= self.visible_items.len() {
-            return Ok(());
-        }
-        let item = self.visible_items[index].clone();
-        if item.is_dir {
-            let segments: Vec<&str> = item.path.split('/').collect();
-            if let Some(node) = Self::find_node_mut(&mut self.file_tree, &segments) {
-                if let TreeNode::Dir {
-                    ref mut expanded, ..
-                } = *node
-                {
-                    if *expanded {
-                        *expanded = false;
-                        self.update_visible();
-                    }
-                }
-            }
-        }
-        Ok(())
+    fn cancel_tag_files(&mut self) {
+        self.tag_files.clear();
+        self.tag_files_state = ListState::default();
+        self.mode = Mode::Normal;
+        self.status = "Normal".to_string();
+        self.key_sequence.clear();
     }
 
-    fn delete_file(&mut self, path: &str) -> Result<(), EditorError> {
-        let full_path = Path::new(&self.base_dir)
-            .join(path)
-            .to_string_lossy()
-            .to_string();
-        fs::remove_file(&full_path)?;
-        let output = Command::new("markdown-scanner")
-            .arg("--delete")
-            .arg(&full_path)
-            .arg(&self.base_dir)
-            .output()?;
-        if !output.status.success() {
-            let error_msg = String::from_utf8_lossy(&output.stderr).into_owned();
-            return Err(EditorError::Scanner(error_msg));
-        }
-        self.remove_node(path);
+    fn start_search(&mut self, search_type: SearchType) -> Result<(), EditorError> {
+        self.search_state.active = true;
+        self.search_state.search_type = search_type.clone();
+        self.search_state.query = String::new();
+        self.search_state.results = Vec::new();
+        self.search_state.list_state = ListState::default();
+        self.mode = Mode::Search;
+        self.view = View::Editor;
+        self.status = format!("Searching {:?}", search_type);
+        self.key_sequence.clear();
+        self.update_search_results()?;
         Ok(())
     }
 
-    fn delete_selected_file(&mut self) -> Result<(), EditorError> {
-        if let Some(selected) = self.tree_state.selected() {
-            let item = self.visible_items[selected].clone();
-            if !item.is_dir {
-                self.delete_file(&item.path)?;
-                self.update_visible();
-            } else {
-                self.status = "Cannot delete directories".to_string();
-            }
+    fn update_search_results(&mut self) -> Result<(), EditorError> {
+        match self.search_state.search_type {
+            SearchType::Backlinks => self.search_backlinks()?,
+            SearchType::Tags => self.search_tags()?,
+            SearchType::Files => self.search_files()?,
+            SearchType::None => {}
+        }
+        if !self.search_state.results.is_empty() {
+            self.search_state.list_state.select(Some(0));
+        } else {
+            self.search_state.list_state.select(None);
         }
         Ok(())
     }
 
-    fn delete_selected_files(&mut self) -> Result<(), EditorError> {
-        let current = self.tree_state.selected().unwrap_or(0);
-        let anchor = self.tree_visual_anchor.unwrap_or(current);
-        let min = anchor.min(current);
-        let max = anchor.max(current);
-        let mut to_delete = Vec::new();
-        for i in min..=max {
-            let item = self.visible_items[i].clone();
-            if !item.is_dir {
-                to_delete.push(item.path);
-            }
-        }
-        for path in to_delete {
-            self.delete_file(&path)?;
+    fn search_backlinks(&mut self) -> Result<(), EditorError> {
+        let (row, current_col) = self.textarea.cursor();
+        let line = self.textarea.lines()[row].clone();
+        let mut target = if let Some(wikilink) = self.extract_wikilink(&line, current_col) {
+            wikilink
+        } else {
+            // Use file_name from files table for the current file
+            let file_name: String = Path::new(&self.file_path)
+                .file_name()
+                .and_then(|s| s.to_str())
+                .unwrap_or_default()
+                .to_string();
+            file_name
+        };
+
+        if !target.ends_with(".md") {
+            target = format!("{}.md", target).clone();
+        };
+        let query = "SELECT DISTINCT f.file_name, f.id
+                     FROM backlinks b
+                     JOIN files f ON b.file_id = f.id
+                     JOIN files fp ON b.backlink_id = fp.id
+                     WHERE fp.file_name LIKE ? AND f.id != ?";
+        let mut stmt = self.db.prepare(query)?;
+        let results = stmt
+            .query_map(params![format!("{}", target), self.file_id], |row| {
+                let file_name: String = row.get(0)?;
+                let file_id: i64 = row.get(1)?;
+                Ok((file_name, Some(file_id)))
+            })?
+            .collect::<Result<Vec<_>, _>>()?;
+
+        self.search_state.results = results;
+        self.search_state.list_state = ListState::default();
+        if !self.search_state.results.is_empty() {
+            self.search_state.list_state.select(Some(0));
         }
-        self.update_visible();
-        self.tree_state.select(Some(min));
         Ok(())
     }
 
-    fn remove_node(&mut self, path: &str) {
-        let segments: Vec<&str> = path.split('/').collect();
-        if segments.len() == 1 {
-            self.file_tree.retain(|n| match n {
-                TreeNode::File(p) => p.as_str() != path,
-                TreeNode::Dir { path: d, .. } => d.as_str() != path,
-            });
+    fn search_tags(&mut self) -> Result<(), EditorError> {
+        let query = "SELECT DISTINCT tag FROM tags WHERE tag LIKE ?";
+        let mut stmt = self.db.prepare(query)?;
+        let search_pattern = if self.search_state.query.is_empty() {
+            "%".to_string()
         } else {
-            let parent_segments = &segments[0..segments.len() - 1];
-            if let Some(parent) = Self::find_node_mut(&mut self.file_tree, parent_segments) {
-                if let TreeNode::Dir {
-                    ref mut children, ..
-                } = *parent
-                {
-                    children.retain(|n| match n {
-                        TreeNode::File(p) => {
-                            p.rsplit('/').next().unwrap() != *segments.last().unwrap()
-                        }
-                        TreeNode::Dir { path: d, .. } => {
-                            d.rsplit('/').next().unwrap() != *segments.last().unwrap()
-                        }
-                    });
-                }
-            }
-        }
-    }
+            format!("%{}%", self.search_state.query)
+        };
+        let results = stmt
+            .query_map(params![search_pattern], |row| {
+                let tag: String = row.get(0)?;
+                Ok((tag, None))
+            })?
+            .collect::<Result<Vec<_>, _>>()?;
 
-    fn yank_selected(&mut self) {
-        let current = self.tree_state.selected().unwrap_or(0);
-        let anchor = self.tree_visual_anchor.unwrap_or(current);
-        let min = anchor.min(current);
-        let max = anchor.max(current);
-        self.yanked_paths.clear();
-        for i in min..=max {
-            let item = &self.visible_items[i];
-            if !item.is_dir {
-                self.yanked_paths.push(item.path.clone());
-            }
-        }
-        self.status = format!("Yanked {} paths", self.yanked_paths.len());
+        self.search_state.results = results;
+        Ok(())
     }
 
-    fn cut_selected(&mut self) {
-        self.yank_selected();
-        self.buffer_mode = Some(BufferMode::Cut);
-        self.status = format!("Cut {} paths to buffer", self.yanked_paths.len());
+    fn search_files(&mut self) -> Result<(), EditorError> {
+        let query = "SELECT file_name, id FROM files WHERE file_name LIKE ?";
+        let mut stmt = self.db.prepare(query)?;
+        let search_pattern = if self.search_state.query.is_empty() {
+            "%".to_string()
+        } else {
+            format!("%{}%", self.search_state.query)
+        };
+        let results = stmt
+            .query_map(params![search_pattern], |row| {
+                let file_name: String = row.get(0)?;
+                let file_id: i64 = row.get(1)?;
+                Ok((file_name, Some(file_id)))
+            })?
+            .collect::<Result<Vec<_>, _>>()?;
+
+        self.search_state.results = results;
+        Ok(())
     }
 
-    fn copy_selected(&mut self) {
-        self.yank_selected();
-        self.buffer_mode = Some(BufferMode::Copy);
-        self.status = format!("Copied {} paths to buffer", self.yanked_paths.len());
+    fn extract(&self, line: &str, cursor_col: usize, start: &str, end: &str) -> Option<String> {
+        let (_, content_start, content_end, _) = self.extract_span(line, cursor_col, start, end)?;
+        Some(line[content_start..content_end].trim().to_string())
     }
 
-    fn paste_buffer(&mut self) -> Result<(), EditorError> {
-        if self.yanked_paths.is_empty() {
-            self.status = "No paths in buffer".to_string();
-            return Ok(());
-        }
-        if let Some(selected) = self.tree_state.selected() {
-            let item = self.visible_items[selected].clone();
-            let target_dir = if item.is_dir {
-                item.path
+    // Byte offsets of the delimited text around the cursor: (start delimiter,
+    // content start, content end, end of the end delimiter)
+    fn extract_span(
+        &self,
+        line: &str,
+        cursor_col: usize,
+        start: &str,
+        end: &str,
+    ) -> Option<(usize, usize, usize, usize)> {
+        for (start_byte_index, _) in line.match_indices(start) {
+            let mut content_start_byte = start_byte_index + start.len();
+            // Skip whitespace after start delimiter
+            while content_start_byte < line.len() && line[content_start_byte..].starts_with(' ') {
+                content_start_byte += 1;
+            }
+            // Use end of line if end delimiter is empty, otherwise find end delimiter
+            let end_byte_index = if end.is_empty() {
+                line.len()
             } else {
-                let path = Path::new(&item.path)
-                    .parent()
-                    .unwrap_or(Path::new(""))
-                    .to_string_lossy()
-                    .to_string();
-                if path == "" {
-                    self.base_dir.clone()
-                } else {
-                    path
-                }
+                line[content_start_byte..]
+                    .find(end)
+                    .map(|relative| content_start_byte + relative)
+                    .unwrap_or(line.len())
             };
-            match self.buffer_mode {
-                Some(BufferMode::Cut) => {
-                    self.move_paths(self.yanked_paths.clone(), target_dir)?;
-                    self.yanked_paths.clear();
-                    self.buffer_mode = None;
-                    self.status = "Pasted (moved) from buffer".to_string();
-                }
-                Some(BufferMode::Copy) => {
-                    self.copy_paths(self.yanked_paths.clone(), target_dir)?;
-                    // Do not clear for copy, allow multiple pastes
-                    self.status = "Pasted (cloned) from buffer".to_string();
+            let start_char_index = line[..start_byte_index].chars().count();
+            let end_char_index = line[..end_byte_index].chars().count();
+            if cursor_col >= start_char_index && cursor_col <= end_char_index {
+                if content_start_byte <= end_byte_index {
+                    let content = line[content_start_byte..end_byte_index].trim();
+                    if !content.is_empty() {
+                        let close_end = (end_byte_index + end.len()).min(line.len());
+                        return Some((
+                            start_byte_index,
+                            content_start_byte,
+                            end_byte_index,
+                            close_end,
+                        ));
+                    }
                 }
-                None => {
-                    self.status = "No buffer mode set".to_string();
+            }
+        }
+        None
+    }
+
+    fn extract_tag(&self, line: &str, cursor_col: usize) -> Option<String> {
+        // Try normal tag (#tag)
+        if let Some(result) = self.extract(line, cursor_col, "#", " ") {
+            return Some(result);
+        }
+        // Fallback to YAML tag (- tag)
+        self.extract(line, cursor_col, " - ", "")
+    }
+
+    fn extract_wikilink(&self, line: &str, cursor_col: usize) -> Option<String> {
+        self.extract(line, cursor_col, "[[", "]]")
+    }
+    fn select_search_result(&mut self) -> Result<(), EditorError> {
+        if let Some(selected) = self.search_state.list_state.selected() {
+            if let Some((file_name, file_id)) = self.search_state.results.get(selected).cloned() {
+                match self.search_state.search_type {
+                    SearchType::Backlinks | SearchType::Files => {
+                        if let Some(file_id) = file_id {
+                            // Retrieve full path from database
+                            let path: String = self
+                                .db
+                                .query_row(
+                                    "SELECT path FROM files WHERE id = ?",
+                                    [file_id],
+                                    |row| row.get(0),
+                                )
+                                .map_err(|e| EditorError::Database(e))?;
+                            self.jump_to_file(path, file_id)?;
+                        }
+                    }
+                    SearchType::Tags => {
+                        self.load_tag_files(&file_name)?;
+                        if !self.tag_files.is_empty() {
+                            self.mode = Mode::TagFiles;
+                        } else {
+                            self.cancel_search();
+                        }
+                    }
+                    SearchType::None => {}
                 }
             }
-            self.file_tree = self.build_root();
-            self.update_visible();
+        } else {
+            self.status = "No result selected".to_string();
+        }
+        if self.mode != Mode::TagFiles {
+            self.cancel_search();
         }
         Ok(())
     }
 
-    fn create_new_file(&mut self, name: String) -> Result<(), EditorError> {
-        if let Some(selected) = self.tree_state.selected() {
-            let item = self.visible_items[selected].clone();
-            let target_dir = if item.is_dir {
-                item.path
-            } else {
-                Path::new(&item.path)
-                    .parent()
-                    .unwrap_or(Path::new(""))
-                    .to_string_lossy()
-                    .to_string()
-            };
-            let new_path = if target_dir.is_empty() {
-                format!("{}.md", name)
-            } else {
-                format!("{}/{}.md", target_dir, name)
-            };
-            let full_path = Path::new(&self.base_dir)
-                .join(&new_path)
-                .to_string_lossy()
-                .to_string();
-            fs::write(&full_path, "")?;
-            let output = Command::new("markdown-scanner")
-                .arg(&full_path)
-                .arg(&self.base_dir)
-                .output()?;
-            if !output.status.success() {
-                let error_msg = String::from_utf8_lossy(&output.stderr).into_owned();
-                return Err(EditorError::Scanner(error_msg));
-            }
-            self.file_tree = self.build_root();
-            self.update_visible();
-            self.status = "Created new file".to_string();
+    fn cancel_search(&mut self) {
+        self.search_state.active = false;
+        self.search_state.search_type = SearchType::None;
+        self.search_state.query = String::new();
+        self.search_state.results = Vec::new();
+        self.search_state.list_state = ListState::default();
+        self.mode = Mode::Normal;
+        self.status = "Normal".to_string();
+        self.key_sequence.clear();
+    }
+
+    fn show_report(&mut self, report_type: ReportType, title: String, lines: Vec<ReportLine>) {
+        self.report_state.report_type = report_type;
+        self.report_state.title = title;
+        self.report_state.lines = lines;
+        self.report_state.list_state = ListState::default();
+        if !self.report_state.lines.is_empty() {
+            self.report_state.list_state.select(Some(0));
+        }
+        self.mode = Mode::Report;
+        self.view = View::Editor;
+        self.key_sequence.clear();
+    }
+
+    fn select_report_line(&mut self) -> Result<(), EditorError> {
+        let Some(selected) = self.report_state.list_state.selected() else {
+            return Ok(());
+        };
+        let Some(line) = self.report_state.lines.get(selected).cloned() else {
+            return Ok(());
+        };
+        if let Some((path, file_id)) = line.target {
+            self.cancel_report();
+            self.jump_to_file(path, file_id)?;
+            self.textarea
+                .move_cursor(CursorMove::Jump(line.row as u16, 0));
         }
         Ok(())
     }
 
-    fn rename_selected(&mut self, new_name: String) -> Result<(), EditorError> {
-        if let Some(selected) = self.tree_state.selected() {
-            let item = self.visible_items[selected].clone();
-            if item.is_dir {
-                self.status = "Cannot rename directories".to_string();
-                return Ok(());
-            }
-            let old_path = item.path;
-            let old_full = Path::new(&self.base_dir)
-                .join(&old_path)
-                .to_string_lossy()
-                .to_string();
-            let parent = Path::new(&old_path)
-                .parent()
-                .and_then(|p| p.to_str())
-                .unwrap_or("")
-                .to_string();
-            let new_path = if parent.is_empty() {
-                new_name.clone()
+    fn cancel_report(&mut self) {
+        self.report_state.report_type = ReportType::None;
+        self.report_state.lines.clear();
+        self.report_state.list_state = ListState::default();
+        self.mode = Mode::Normal;
+        self.status = "Normal".to_string();
+    }
+
+    fn graph_neighbours(&self, file_id: i64) -> Result<Vec<GraphNode>, EditorError> {
+        let mut stmt = self.db.prepare(
+            "SELECT f.id, f.path, f.file_name, 1 FROM backlinks b
+             JOIN files f ON b.backlink_id = f.id
+             WHERE b.file_id = ?1 AND f.id != ?1
+             UNION
+             SELECT f.id, f.path, f.file_name, 0 FROM backlinks b
+             JOIN files f ON b.file_id = f.id
+             WHERE b.backlink_id = ?1 AND f.id != ?1",
+        )?;
+        let rows = stmt
+            .query_map([file_id], |row| {
+                Ok((
+                    row.get::<_, i64>(0)?,
+                    row.get::<_, String>(1)?,
+                    row.get::<_, String>(2)?,
+                    row.get::<_, i64>(3)? == 1,
+                ))
+            })?
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let mut neighbours: Vec<GraphNode> = Vec::new();
+        for (id, path, file_name, outgoing) in rows {
+            if let Some(existing) = neighbours.iter_mut().find(|n| n.id == id) {
+                existing.outgoing |= outgoing;
+                existing.incoming |= !outgoing;
             } else {
-                format!("{}/{}", parent, new_name)
-            };
-            let new_full = Path::new(&self.base_dir)
-                .join(&new_path)
-                .to_string_lossy()
-                .to_string();
-            fs::rename(&old_full, &new_full)?;
-            let output_delete = Command::new("markdown-scanner")
-                .arg("--delete")
-                .arg(&old_full)
-                .arg(&self.base_dir)
-                .output()?;
-            if !output_delete.status.success() {
-                let error_msg = String::from_utf8_lossy(&output_delete.stderr).into_owned();
-                return Err(EditorError::Scanner(error_msg));
-            }
-            let output_scan = Command::new("markdown-scanner")
-                .arg(&new_full)
-                .arg(&self.base_dir)
-                .output()?;
-            if !output_scan.status.success() {
-                let error_msg = String::from_utf8_lossy(&output_scan.stderr).into_owned();
-                return Err(EditorError::Scanner(error_msg));
+                neighbours.push(GraphNode {
+                    id,
+                    path,
+                    file_name,
+                    outgoing,
+                    incoming: !outgoing,
+                });
             }
-            self.remove_node(&old_path);
-            self.file_tree = self.build_root();
-            self.update_visible();
         }
-        Ok(())
+        neighbours.sort_by(|a, b| a.file_name.cmp(&b.file_name));
+        Ok(neighbours)
     }
 
-    fn move_paths(&mut self, paths: Vec<String>, target_dir: String) -> Result<(), EditorError> {
-        for old_path in paths {
-            let old_full = Path::new(&self.base_dir)
-                .join(&old_path)
-                .to_string_lossy()
-                .to_string();
-            let filename = Path::new(&old_path)
-                .file_name()
-                .and_then(|s| s.to_str())
-                .unwrap_or("")
-                .to_string();
-            let new_path = format!("{}/{}", target_dir, filename);
-            let new_full = Path::new(&self.base_dir)
-                .join(&new_path)
-                .to_string_lossy()
-                .to_string();
-            fs::rename(&old_full, &new_full)?;
-            let output_delete = Command::new("markdown-scanner")
-                .arg("--delete")
-                .arg(&old_full)
-                .arg(&self.base_dir)
-                .output()?;
-            if !output_delete.status.success() {
-                let error_msg = String::from_utf8_lossy(&output_delete.stderr).into_owned();
-                return Err(EditorError::Scanner(error_msg));
-            }
-            let output_scan = Command::new("markdown-scanner")
-                .arg(&new_full)
-                .arg(&self.base_dir)
-                .output()?;
-            if !output_scan.status.success() {
-                let error_msg = String::from_utf8_lossy(&output_scan.stderr).into_owned();
-                return Err(EditorError::Scanner(error_msg));
+    fn add_graph_lines(
+        &self,
+        file_id: i64,
+        prefix: &str,
+        depth: usize,
+        visited: &mut std::collections::HashSet<i64>,
+        lines: &mut Vec<ReportLine>,
+    ) -> Result<(), EditorError> {
+        if depth == 0 {
+            return Ok(());
+        }
+        let neighbours = self.graph_neighbours(file_id)?;
+        let count = neighbours.len();
+        for (i, node) in neighbours.into_iter().enumerate() {
+            let last = i + 1 == count;
+            let arrow = match (node.outgoing, node.incoming) {
+                (true, true) => "⇄",
+                (true, false) => "▶",
+                _ => "◀",
+            };
+            let seen = !visited.insert(node.id);
+            lines.push(ReportLine {
+                text: format!(
+                    "{}{}{} {}{}",
+                    prefix,
+                    if last { "└─" } else { "├─" },
+                    arrow,
+                    node.file_name.trim_end_matches(".md"),
+                    if seen { " ↺" } else { "" }
+                ),
+                target: Some((node.path, node.id)),
+                row: 0,
+            });
+            if !seen {
+                let child_prefix = format!("{}{}", prefix, if last { "   " } else { "│  " });
+                self.add_graph_lines(node.id, &child_prefix, depth - 1, visited, lines)?;
             }
-            self.remove_node(&old_path);
         }
         Ok(())
     }
 
-    fn copy_paths(&mut self, paths: Vec<String>, target_dir: String) -> Result<(), EditorError> {
-        for old_path in paths {
-            let old_full = Path::new(&self.base_dir)
-                .join(&old_path)
-                .to_string_lossy()
-                .to_string();
-            let filename = Path::new(&old_path)
-                .file_name()
-                .and_then(|s| s.to_str())
-                .unwrap_or("")
-                .to_string();
-            let new_path = format!("{}/{}", target_dir, filename);
-            let new_full = Path::new(&self.base_dir)
-                .join(&new_path)
-                .to_string_lossy()
-                .to_string();
-            fs::copy(&old_full, &new_full)?;
-            let output_scan = Command::new("markdown-scanner")
-                .arg(&new_full)
-                .arg(&self.base_dir)
-                .output()?;
-            if !output_scan.status.success() {
-                let error_msg = String::from_utf8_lossy(&output_scan.stderr).into_owned();
-                return Err(EditorError::Scanner(error_msg));
+    fn open_graph(&mut self, depth: usize) -> Result<(), EditorError> {
+        let depth = depth.max(1);
+        let mut visited = std::collections::HashSet::new();
+        visited.insert(self.file_id);
+        let mut lines = vec![ReportLine {
+            text: format!("● {}", gettitle!(&self.file_path)),
+            target: Some((self.file_path.clone(), self.file_id)),
+            row: 0,
+        }];
+        self.add_graph_lines(self.file_id, "", depth, &mut visited, &mut lines)?;
+        self.report_state.graph_depth = depth;
+        let title = format!("Graph (depth {}, +/- to change)", depth);
+        self.show_report(ReportType::Graph, title, lines);
+        self.status = format!("Graph: {} notes", visited.len());
+        Ok(())
+    }
+
+    fn export_graph(&mut self, target: &str) -> Result<(), EditorError> {
+        let nodes = {
+            let mut stmt = self
+                .db
+                .prepare("SELECT id, path, file_name FROM files ORDER BY id")?;
+            stmt.query_map([], |row| {
+                Ok((
+                    row.get::<_, i64>(0)?,
+                    row.get::<_, String>(1)?,
+                    row.get::<_, String>(2)?,
+                ))
+            })?
+            .collect::<Result<Vec<_>, _>>()?
+        };
+        let edges = {
+            let mut stmt = self.db.prepare(
+                "SELECT DISTINCT file_id, backlink_id, backlink FROM backlinks
+                 WHERE file_id IS NOT NULL AND backlink_id IS NOT NULL",
+            )?;
+            stmt.query_map([], |row| {
+                Ok((
+                    row.get::<_, i64>(0)?,
+                    row.get::<_, i64>(1)?,
+                    row.get::<_, String>(2)?,
+                ))
+            })?
+            .collect::<Result<Vec<_>, _>>()?
+        };
+
+        let content = if target.ends_with(".dot") {
+            let mut dot = String::from("digraph vault {\n");
+            for (id, _, file_name) in &nodes {
+                dot.push_str(&format!(
+                    "  n{} [label=\"{}\"];\n",
+                    id,
+                    file_name.trim_end_matches(".md").replace('"', "\\\"")
+                ));
             }
-        }
+            for (source, target, _) in &edges {
+                dot.push_str(&format!("  n{} -> n{};\n", source, target));
+            }
+            dot.push_str("}\n");
+            dot
+        } else if target.ends_with(".json") {
+            let json = serde_json::json!({
+                "nodes": nodes
+                    .iter()
+                    .map(|(id, path, file_name)| {
+                        serde_json::json!({ "id": id, "path": path, "name": file_name })
+                    })
+                    .collect::<Vec<_>>(),
+                "edges": edges
+                    .iter()
+                    .map(|(source, target, link)| {
+                        serde_json::json!({ "source": source, "target": target, "link": link })
+                    })
+                    .collect::<Vec<_>>(),
+            });
+            serde_json::to_string_pretty(&json)
+                .map_err(|e| EditorError::InvalidPath(format!("Graph export failed: {}", e)))?
+        } else {
+            self.status = "Graph export needs a .dot or .json file".to_string();
+            return Ok(());
+        };
+
+        let path = Path::new(&self.base_dir).join(target);
+        fs::write(&path, content)?;
+        self.status = format!(
+            "Exported {} notes and {} links to {}",
+            nodes.len(),
+            edges.len(),
+            path.display()
+        );
         Ok(())
     }
 
-    pub fn handle_input(
-        &mut self,
-        event: ratatui::crossterm::event::KeyEvent,
-    ) -> Result<(), EditorError> {
-        match self.mode {
-            Mode::Normal => {
-                // Handle key sequence first if it has started
-                if !self.key_sequence.is_empty() {
-                    if let ratatui::crossterm::event::KeyCode::Char(c) = event.code {
-                        self.key_sequence.push(c);
-                        let sequence = self.key_sequence.clone(); // Clone to avoid borrow issues
-                        match sequence.as_str() {
-                            "gg" => {
-                                self.textarea.move_cursor(CursorMove::Top);
-                                self.key_sequence.clear();
-                                self.status = "Moved to top".to_string();
-                            }
-                            "yy" => {
-                                let row = self.textarea.cursor().0;
-                                self.yanked = vec![self.textarea.lines()[row].clone()];
-                                self.key_sequence.clear();
-                                self.status = "Yanked line (not undoable)".to_string();
-                            }
-                            "dd" => {
-                                self.textarea.move_cursor(CursorMove::Head);
-                                self.textarea.delete_line_by_end();
-                                self.key_sequence.clear();
-                                self.status = "Deleted line".to_string();
-                            }
+    fn query_notes(
+        &self,
+        sql: &str,
+        params: impl rusqlite::Params,
+    ) -> Result<Vec<(i64, String, String)>, EditorError> {
+        let mut stmt = self.db.prepare(sql)?;
+        let rows = stmt
+            .query_map(params, |row| {
+                Ok((
+                    row.get::<_, i64>(0)?,
+                    row.get::<_, String>(1)?,
+                    row.get::<_, String>(2)?,
+                ))
+            })?
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(rows)
+    }
+
+    fn open_health_report(&mut self) -> Result<(), EditorError> {
+        let mut lines = Vec::new();
+        let header = |text: String| ReportLine {
+            text,
+            target: None,
+            row: 0,
+        };
+
+        // Notes nobody links to
+        let orphans = self.query_notes(
+            "SELECT f.id, f.path, f.file_name FROM files f
+             WHERE f.file_name LIKE '%.md'
+             AND NOT EXISTS (
+                 SELECT 1 FROM backlinks b WHERE b.backlink_id = f.id AND b.file_id != f.id
+             )
+             ORDER BY f.file_name",
+            [],
+        )?;
+        lines.push(header(format!("── Orphans ({}) ──", orphans.len())));
+        for (id, path, file_name) in orphans {
+            lines.push(ReportLine {
+                text: format!("  {}", file_name),
+                target: Some((path, id)),
+                row: 0,
+            });
+        }
+
+        // [[links]] whose target file is missing
+        let dead_links = {
+            let mut stmt = self.db.prepare(
+                "SELECT DISTINCT b.backlink, f.id, f.path, f.file_name FROM backlinks b
+                 JOIN files f ON b.file_id = f.id
+                 LEFT JOIN files t ON b.backlink_id = t.id
+                 WHERE t.id IS NULL
+                 ORDER BY f.file_name",
+            )?;
+            stmt.query_map([], |row| {
+                Ok((
+                    row.get::<_, String>(0)?,
+                    row.get::<_, i64>(1)?,
+                    row.get::<_, String>(2)?,
+                    row.get::<_, String>(3)?,
+                ))
+            })?
+            .collect::<Result<Vec<_>, _>>()?
+        };
+        lines.push(header(format!("── Dead links ({}) ──", dead_links.len())));
+        for (link, id, path, file_name) in dead_links {
+            let row = fs::read_to_string(&path)
+                .unwrap_or_default()
+                .lines()
+                .position(|l| l.contains(&format!("[[{}", link)))
+                .unwrap_or(0);
+            lines.push(ReportLine {
+                text: format!("  {} → [[{}]]", file_name, link),
+                target: Some((path, id)),
+                row,
+            });
+        }
+
+        // Most linked notes
+        let hubs = {
+            let mut stmt = self.db.prepare(
+                "SELECT t.id, t.path, t.file_name, COUNT(DISTINCT b.file_id) AS incoming
+                 FROM backlinks b
+                 JOIN files t ON b.backlink_id = t.id
+                 WHERE b.file_id != t.id
+                 GROUP BY t.id
+                 ORDER BY incoming DESC, t.file_name
+                 LIMIT 10",
+            )?;
+            stmt.query_map([], |row| {
+                Ok((
+                    row.get::<_, i64>(0)?,
+                    row.get::<_, String>(1)?,
+                    row.get::<_, String>(2)?,
+                    row.get::<_, i64>(3)?,
+                ))
+            })?
+            .collect::<Result<Vec<_>, _>>()?
+        };
+        lines.push(header(format!("── Hubs ({}) ──", hubs.len())));
+        for (id, path, file_name, incoming) in hubs {
+            lines.push(ReportLine {
+                text: format!("  {} ({} links in)", file_name, incoming),
+                target: Some((path, id)),
+                row: 0,
+            });
+        }
+
+        // Empty notes, usually created by following a link to a missing note
+        let empty: Vec<(i64, String, String)> = self
+            .query_notes(
+                "SELECT id, path, file_name FROM files
+                 WHERE file_name LIKE '%.md' ORDER BY file_name",
+                [],
+            )?
+            .into_iter()
+            .filter(|(_, path, _)| {
+                fs::read_to_string(path)
+                    .map(|content| content.trim().is_empty())
+                    .unwrap_or(false)
+            })
+            .collect();
+        lines.push(header(format!("── Empty notes ({}) ──", empty.len())));
+        for (id, path, file_name) in empty {
+            lines.push(ReportLine {
+                text: format!("  {}", file_name),
+                target: Some((path, id)),
+                row: 0,
+            });
+        }
+
+        // Several files sharing a file_name make [[links]] ambiguous
+        let duplicates = self.query_notes(
+            "SELECT id, path, file_name FROM files
+             WHERE file_name IN (
+                 SELECT file_name FROM files GROUP BY file_name HAVING COUNT(*) > 1
+             )
+             ORDER BY file_name, path",
+            [],
+        )?;
+        lines.push(header(format!(
+            "── Duplicate names ({}) ──",
+            duplicates.len()
+        )));
+        for (id, path, file_name) in duplicates {
+            let rel = path
+                .strip_prefix(&self.base_dir)
+                .map(|p| p.trim_start_matches('/'))
+                .unwrap_or(&path)
+                .to_string();
+            lines.push(ReportLine {
+                text: format!("  {} ({})", file_name, rel),
+                target: Some((path, id)),
+                row: 0,
+            });
+        }
+
+        self.show_report(ReportType::Health, "Vault health".to_string(), lines);
+        self.status = "Vault health report".to_string();
+        Ok(())
+    }
+
+    fn load_query_records(&self, source: &QuerySource) -> Result<Vec<NoteRecord>, EditorError> {
+        let base = "SELECT DISTINCT f.id, f.path, f.file_name, f.metadata FROM files f";
+        let (sql, arg) = match source {
+            QuerySource::All => (
+                format!("{} WHERE f.file_name LIKE '%.md'", base),
+                String::new(),
+            ),
+            QuerySource::Tag(tag) => (
+                format!(
+                    "{} JOIN file_tags ft ON ft.file_id = f.id
+                     JOIN tags t ON t.id = ft.tag_id
+                     WHERE f.file_name LIKE '%.md'
+                     AND (TRIM(t.tag, '#') = ?1 OR TRIM(t.tag, '#') LIKE ?1 || '/%')",
+                    base
+                ),
+                tag.clone(),
+            ),
+            QuerySource::Folder(folder) => (
+                format!(
+                    "{} WHERE f.file_name LIKE '%.md'
+                     AND (f.path LIKE ?1 || '/%' OR f.path LIKE '%/' || ?1 || '/%')",
+                    base
+                ),
+                folder.clone(),
+            ),
+        };
+        let mut stmt = self.db.prepare(&sql)?;
+        let map_row = |row: &rusqlite::Row| {
+            Ok((
+                row.get::<_, i64>(0)?,
+                row.get::<_, String>(1)?,
+                row.get::<_, String>(2)?,
+                row.get::<_, Option<String>>(3)?,
+            ))
+        };
+        let rows = if arg.is_empty() {
+            stmt.query_map([], map_row)?
+                .collect::<Result<Vec<_>, _>>()?
+        } else {
+            stmt.query_map([&arg], map_row)?
+                .collect::<Result<Vec<_>, _>>()?
+        };
+
+        let mut records = Vec::new();
+        for (id, path, file_name, metadata) in rows {
+            let modified = fs::metadata(&path)
+                .and_then(|m| m.modified())
+                .map(|t| {
+                    chrono::DateTime::<Local>::from(t)
+                        .format("%Y-%m-%d %H:%M")
+                        .to_string()
+                })
+                .unwrap_or_default();
+            records.push(NoteRecord {
+                id,
+                path,
+                name: file_name.trim_end_matches(".md").to_string(),
+                modified,
+                tags: App::load_tags(&self.db, id)?,
+                metadata: serde_json::from_str(metadata.as_deref().unwrap_or("{}"))
+                    .unwrap_or(serde_json::Value::Null),
+            });
+        }
+        Ok(records)
+    }
+
+    fn run_note_query(&self, text: &str) -> Result<(NoteQuery, Vec<NoteRecord>), String> {
+        let query = query::parse(text)?;
+        let records = self
+            .load_query_records(&query.source)
+            .map_err(|e| e.to_string())?;
+        let results = query.run(records);
+        Ok((query, results))
+    }
+
+    fn open_query_report(&mut self, text: &str) -> Result<(), EditorError> {
+        let (query, records) = match self.run_note_query(text) {
+            Ok(result) => result,
+            Err(e) => {
+                self.status = format!("Query error: {}", e);
+                return Ok(());
+            }
+        };
+        let (header, rows) = query.format_results(&records);
+        let mut lines: Vec<ReportLine> = header
+            .into_iter()
+            .map(|text| ReportLine {
+                text,
+                target: None,
+                row: 0,
+            })
+            .collect();
+        lines.extend(records.iter().zip(rows).map(|(record, text)| ReportLine {
+            text,
+            target: Some((record.path.clone(), record.id)),
+            row: 0,
+        }));
+        let title = format!("Query: {} results", records.len());
+        self.show_report(ReportType::Query, title, lines);
+        self.status = text.to_string();
+        Ok(())
+    }
+
+    // Header height and notes of the results drawn below the ```query block
+    // that closes at `row`
+    fn query_results_at(&self, row: usize) -> Option<(usize, Vec<(String, i64)>)> {
+        if !self.render_queries {
+            return None;
+        }
+        let (_, _, text) = query::find_blocks(self.textarea.lines())
+            .into_iter()
+            .find(|(_, end, _)| *end == row)?;
+        let Ok((query, records)) = self.query_results.get(&text)? else {
+            return None;
+        };
+        let (header, _) = query.format_results(records);
+        let targets = records.iter().map(|r| (r.path.clone(), r.id)).collect();
+        Some((header.len(), targets))
+    }
+
+    // `j`/`k`: one row, stepping over closed folds and through the results
+    // drawn below ```query blocks
+    fn move_line(&mut self, down: bool) {
+        if self.step_query_row(down) {
+            return;
+        }
+        let row = self.textarea.cursor().0;
+        self.textarea.move_cursor(if down {
+            CursorMove::Down
+        } else {
+            CursorMove::Up
+        });
+        self.skip_folds(down);
+        self.motion_failed = self.textarea.cursor().0 == row;
+    }
+
+    // `j`/`k` through the results drawn below a ```query block; false when the
+    // cursor should move over buffer rows instead
+    fn step_query_row(&mut self, down: bool) -> bool {
+        let row = self.textarea.cursor().0;
+        let count = |app: &App, row: usize| app.query_results_at(row).map_or(0, |(_, t)| t.len());
+        match (self.query_row, down) {
+            (Some((row, index)), true) if index + 1 < count(self, row) => {
+                self.query_row = Some((row, index + 1));
+                true
+            }
+            (Some(_), true) => {
+                self.query_row = None;
+                false
+            }
+            (Some((row, index)), false) => {
+                self.query_row = index.checked_sub(1).map(|index| (row, index));
+                true
+            }
+            (None, true) if count(self, row) > 0 => {
+                self.query_row = Some((row, 0));
+                true
+            }
+            (None, false) if row > 0 && self.closed_fold_at(row - 1).is_none() => {
+                let results = count(self, row - 1);
+                if results == 0 {
+                    return false;
+                }
+                let col = self.textarea.cursor().1;
+                self.textarea
+                    .move_cursor(CursorMove::Jump((row - 1) as u16, col as u16));
+                self.query_row = Some((row - 1, results - 1));
+                true
+            }
+            (None, _) => false,
+        }
+    }
+
+    // Result lines drawn below each ```query block, keyed by closing fence row
+    fn query_block_lines(&mut self, lines: &[String]) -> Vec<(usize, Vec<Line<'static>>)> {
+        let mut rendered = Vec::new();
+        for (_, end, text) in query::find_blocks(lines) {
+            if !self.query_results.contains_key(&text) {
+                let result = self.run_note_query(&text);
+                self.query_results.insert(text.clone(), result);
+            }
+            let style = Style::default().fg(Color::Cyan);
+            let lines: Vec<Line<'static>> = match &self.query_results[&text] {
+                Ok((query, records)) => {
+                    let (header, rows) = query.format_results(records);
+                    let mut lines: Vec<Line<'static>> = header
+                        .into_iter()
+                        .map(|l| Line::from(Span::styled(l, style.fg(Color::LightBlue))))
+                        .collect();
+                    lines.extend(rows.into_iter().map(|l| Line::from(Span::styled(l, style))));
+                    if records.is_empty() {
+                        lines.push(Line::from(Span::styled("(no results)", style)));
+                    }
+                    lines
+                }
+                Err(e) => vec![Line::from(Span::styled(
+                    format!("⚠ {}", e),
+                    Style::default().fg(Color::Red),
+                ))],
+            };
+            rendered.push((end, lines));
+        }
+        rendered
+    }
+
+    // Tasks live in their own table next to the scanner's, keyed by file. The
+    // whole vault is read when the table is created; after that each note is
+    // indexed again when it is scanned.
+    fn ensure_tasks_table(&self) -> Result<(), EditorError> {
+        let exists: i64 = self.db.query_row(
+            "SELECT COUNT(*) FROM sqlite_master WHERE type = 'table' AND name = 'tasks'",
+            [],
+            |row| row.get(0),
+        )?;
+        if exists > 0 {
+            return Ok(());
+        }
+        self.db.execute(
+            "CREATE TABLE tasks (
+                id INTEGER PRIMARY KEY,
+                file_id INTEGER NOT NULL,
+                line INTEGER NOT NULL,
+                state TEXT NOT NULL,
+                text TEXT NOT NULL,
+                due TEXT,
+                FOREIGN KEY(file_id) REFERENCES files(id) ON DELETE CASCADE
+            )",
+            [],
+        )?;
+        self.index_all_tasks()
+    }
+
+    fn index_tasks(&self, file_id: i64, lines: &[String]) -> Result<(), EditorError> {
+        self.ensure_tasks_table()?;
+        self.write_tasks(file_id, lines)
+    }
+
+    fn write_tasks(&self, file_id: i64, lines: &[String]) -> Result<(), EditorError> {
+        self.db
+            .execute("DELETE FROM tasks WHERE file_id = ?", [file_id])?;
+        let mut stmt = self.db.prepare(
+            "INSERT INTO tasks (file_id, line, state, text, due) VALUES (?, ?, ?, ?, ?)",
+        )?;
+        for task in tasks::extract(lines) {
+            stmt.execute(params![
+                file_id,
+                task.line as i64,
+                task.state.to_string(),
+                task.text,
+                task.due
+            ])?;
+        }
+        Ok(())
+    }
+
+    fn index_all_tasks(&self) -> Result<(), EditorError> {
+        let files = self.query_notes(
+            "SELECT id, path, file_name FROM files WHERE file_name LIKE '%.md'",
+            [],
+        )?;
+        let transaction = self.db.unchecked_transaction()?;
+        self.db.execute("DELETE FROM tasks", [])?;
+        for (id, path, _) in files {
+            let Ok(content) = fs::read_to_string(&path) else {
+                continue;
+            };
+            let lines: Vec<String> = content.lines().map(|l| l.to_string()).collect();
+            self.write_tasks(id, &lines)?;
+        }
+        transaction.commit()?;
+        Ok(())
+    }
+
+    // `r` in the tasks report: rescan the vault so notes edited outside the
+    // editor are read again
+    fn refresh_tasks(&mut self) -> Result<(), EditorError> {
+        let scanned = self.run_scanner(&self.base_dir);
+        self.ensure_tasks_table()?;
+        self.index_all_tasks()?;
+        self.open_tasks_report()?;
+        // Notes the database already knows are re-read even if the scan failed
+        if let Err(e) = scanned {
+            self.status = format!("Vault scan failed, new notes may be missing: {}", e);
+        }
+        Ok(())
+    }
+
+    // Tasks as saved in the notes; unsaved edits show up once written
+    fn open_tasks_report(&mut self) -> Result<(), EditorError> {
+        self.ensure_tasks_table()?;
+        let rows = {
+            let mut stmt = self.db.prepare(
+                "SELECT t.line, t.state, t.text, t.due, f.id, f.path, f.file_name
+                 FROM tasks t JOIN files f ON t.file_id = f.id
+                 ORDER BY t.due IS NULL, t.due, f.file_name, t.line",
+            )?;
+            stmt.query_map([], |row| {
+                Ok((
+                    tasks::Task {
+                        line: row.get::<_, i64>(0)? as usize,
+                        state: row.get::<_, String>(1)?.chars().next().unwrap_or(' '),
+                        text: row.get(2)?,
+                        due: row.get(3)?,
+                    },
+                    row.get::<_, i64>(4)?,
+                    row.get::<_, String>(5)?,
+                    row.get::<_, String>(6)?,
+                ))
+            })?
+            .collect::<Result<Vec<_>, _>>()?
+        };
+
+        let today = Local::now().format("%Y-%m-%d").to_string();
+        let group_of = |task: &tasks::Task| match &task.due {
+            _ if task.is_done() => 4,
+            Some(due) if *due < today => 0,
+            Some(due) if *due == today => 1,
+            Some(_) => 2,
+            None => 3,
+        };
+
+        let mut lines = Vec::new();
+        for (group, name) in ["Overdue", "Today", "Upcoming", "Undated", "Done"]
+            .iter()
+            .enumerate()
+        {
+            let tasks: Vec<_> = rows
+                .iter()
+                .filter(|(task, ..)| group_of(task) == group)
+                .collect();
+            lines.push(ReportLine {
+                text: format!("── {} ({}) ──", name, tasks.len()),
+                target: None,
+                row: 0,
+            });
+            for (task, id, path, file_name) in tasks {
+                lines.push(ReportLine {
+                    text: format!(
+                        "  [{}] {} · {}",
+                        task.state,
+                        task.text,
+                        file_name.trim_end_matches(".md")
+                    ),
+                    target: Some((path.clone(), *id)),
+                    row: task.line,
+                });
+            }
+        }
+
+        let selected = self.report_state.list_state.selected();
+        let open = rows.iter().filter(|(task, ..)| !task.is_done()).count();
+        self.show_report(ReportType::Tasks, format!("Tasks: {} open", open), lines);
+        if let Some(selected) = selected.filter(|&i| i < self.report_state.lines.len()) {
+            self.report_state.list_state.select(Some(selected));
+        }
+        self.status = "Tasks".to_string();
+        Ok(())
+    }
+
+    // Flip the selected task between open and done in its source file
+    fn toggle_report_task(&mut self) -> Result<(), EditorError> {
+        let Some(line) = self
+            .report_state
+            .list_state
+            .selected()
+            .and_then(|i| self.report_state.lines.get(i))
+            .cloned()
+        else {
+            return Ok(());
+        };
+        let Some((path, _)) = line.target else {
+            return Ok(());
+        };
+        if self.has_unsaved_buffer(Path::new(&path)) {
+            self.status = Self::unsaved_buffer_status(Path::new(&path));
+            return Ok(());
+        }
+
+        let is_current = path == self.file_path;
+        let mut lines: Vec<String> = if is_current {
+            self.textarea.lines().to_vec()
+        } else {
+            match fs::read_to_string(&path) {
+                Ok(content) => content.lines().map(|l| l.to_string()).collect(),
+                Err(e) => {
+                    self.status = format!("Cannot read {}: {}", gettitle!(&path), e);
+                    return Ok(());
+                }
+            }
+        };
+        let Some(task) = tasks::extract(&lines)
+            .into_iter()
+            .find(|t| t.line == line.row)
+        else {
+            self.status = "Task moved, refresh with :tasks".to_string();
+            return Ok(());
+        };
+        let state = if task.is_done() { ' ' } else { 'x' };
+        let Some(new_line) = tasks::set_state(&lines[line.row], state) else {
+            return Ok(());
+        };
+
+        if is_current {
+            self.replace_lines(line.row, line.row + 1, &[new_line]);
+        } else {
+            lines[line.row] = new_line;
+            if let Err(e) = fs::write(&path, lines.join("\n")) {
+                self.status = format!("Cannot write {}: {}", gettitle!(&path), e);
+                return Ok(());
+            }
+            self.run_scanner(&path)?;
+        }
+        self.open_tasks_report()?;
+        self.status = if is_current {
+            format!(
+                "Task {} in the buffer (not saved)",
+                if state == 'x' { "done" } else { "reopened" }
+            )
+        } else {
+            format!(
+                "Task {} in {}",
+                if state == 'x' { "done" } else { "reopened" },
+                gettitle!(&path)
+            )
+        };
+        Ok(())
+    }
+
+    // `\x` toggles and `\s` cycles the task on the cursor line or on every
+    // line of the Visual selection, turning other lines into tasks first
+    fn edit_task_lines(&mut self, cycle: bool) {
+        let cursor_row = self.textarea.cursor().0;
+        let (start, end) = match (&self.mode, self.visual_anchor) {
+            (Mode::Visual | Mode::VisualLine | Mode::VisualBlock, Some(anchor)) => {
+                (anchor.0.min(cursor_row), anchor.0.max(cursor_row))
+            }
+            _ => (cursor_row, cursor_row),
+        };
+        let today = Local::now().format("%Y-%m-%d").to_string();
+        let done_date = self.task_done_date.then_some(today.as_str());
+        let new_lines: Vec<String> = self.textarea.lines()[start..=end]
+            .iter()
+            .map(|line| {
+                if cycle {
+                    tasks::cycle_line(line, done_date)
+                } else {
+                    tasks::toggle_line(line, done_date)
+                }
+            })
+            .collect();
+        self.textarea.cancel_selection();
+        self.replace_lines(start, end + 1, &new_lines);
+        if self.mode != Mode::Normal {
+            self.visual_anchor = None;
+            self.mode = Mode::Normal;
+        }
+        self.status = format!(
+            "{} {} task{}",
+            if cycle { "Cycled" } else { "Toggled" },
+            new_lines.len(),
+            if new_lines.len() == 1 { "" } else { "s" }
+        );
+    }
+
+    fn table_at_cursor(&self) -> Option<(usize, usize, Table)> {
+        let lines = self.textarea.lines();
+        let (start, end) = table::table_range(lines, self.textarea.cursor().0)?;
+        Some((start, end, Table::parse(&lines[start..end])?))
+    }
+
+    // Re-align the table under the cursor, keeping the cursor in its cell.
+    // Returns the number of textarea edits it took.
+    fn realign_table(&mut self) -> usize {
+        let Some((start, end, table)) = self.table_at_cursor() else {
+            return 0;
+        };
+        // A lone `|` line is not a table yet
+        if end - start < 2 {
+            return 0;
+        }
+        let (row, col) = self.textarea.cursor();
+        let line = self.textarea.lines()[row].clone();
+        let cell = table::cell_at(&line, col);
+        let offset = col.saturating_sub(table::cell_text_start(&line, cell));
+        // A missing separator row is added under the header
+        let has_separator = table.rows.len() + 2 == end - start;
+        let new_row = if row == start || has_separator {
+            row
+        } else {
+            row + 1
+        };
+
+        let rendered = table.render();
+        if rendered.as_slice() == &self.textarea.lines()[start..end] {
+            return 0;
+        }
+        let steps = self.replace_lines(start, end, &rendered);
+        let new_line = &rendered[new_row - start];
+        let text_len = table::parse_row(new_line)
+            .get(cell)
+            .map(|c| c.chars().count())
+            .unwrap_or(0);
+        let col = table::cell_text_start(new_line, cell) + offset.min(text_len);
+        self.textarea
+            .move_cursor(CursorMove::Jump(new_row as u16, col as u16));
+        steps
+    }
+
+    // Tab / Shift-Tab between cells; Tab past the last cell adds a row.
+    // Returns false when the cursor is not in a table.
+    fn move_table_cell(&mut self, forward: bool) -> bool {
+        if self.table_at_cursor().is_none() {
+            return false;
+        }
+        self.realign_table();
+        let Some((start, end, mut table)) = self.table_at_cursor() else {
+            return false;
+        };
+        let (row, col) = self.textarea.cursor();
+        let columns = table.columns();
+        let cell = table::cell_at(&self.textarea.lines()[row], col);
+        // Cells in reading order, skipping the separator row
+        let row = if row == start + 1 { start } else { row };
+        let index = if row == start { 0 } else { row - start - 1 } * columns + cell;
+        let count = (table.rows.len() + 1) * columns;
+
+        let target = if forward {
+            if index + 1 >= count {
+                table.insert_row(table.rows.len());
+                let rendered = table.render();
+                self.replace_lines(start, end, &rendered);
+                count
+            } else {
+                index + 1
+            }
+        } else {
+            index.saturating_sub(1)
+        };
+        let target_row = match target / columns {
+            0 => start,
+            body => start + 1 + body,
+        };
+        let line = self.textarea.lines()[target_row].clone();
+        let col = table::cell_text_start(&line, target % columns);
+        self.textarea
+            .move_cursor(CursorMove::Jump(target_row as u16, col as u16));
+        true
+    }
+
+    // `\|` chords: r/R add/delete row, c/C add/delete column, s/S sort, f align
+    fn table_command(&mut self, command: char) {
+        if self.table_at_cursor().is_none() {
+            self.status = "Not in a table".to_string();
+            return;
+        }
+        let aligned = self.realign_table();
+        let Some((start, end, mut table)) = self.table_at_cursor() else {
+            return;
+        };
+        let (row, col) = self.textarea.cursor();
+        let cell = table::cell_at(&self.textarea.lines()[row], col);
+        let body_row = row.checked_sub(start + 2);
+
+        let (target_body, target_cell, status) = match command {
+            'r' => {
+                let at = body_row.map_or(0, |r| r + 1);
+                table.insert_row(at);
+                (Some(at), 0, "Added row")
+            }
+            'R' => {
+                let Some(r) = body_row else {
+                    self.status = "Cannot delete the header row".to_string();
+                    return;
+                };
+                table.delete_row(r);
+                let target = r.min(table.rows.len().saturating_sub(1));
+                (
+                    Some(target).filter(|_| !table.rows.is_empty()),
+                    cell,
+                    "Deleted row",
+                )
+            }
+            'c' => {
+                table.insert_column(cell + 1);
+                (body_row, cell + 1, "Added column")
+            }
+            'C' => {
+                if table.columns() == 1 {
+                    self.status = "Cannot delete the only column".to_string();
+                    return;
+                }
+                table.delete_column(cell);
+                (body_row, cell.min(table.columns() - 1), "Deleted column")
+            }
+            's' | 'S' => {
+                table.sort_by(cell, command == 'S');
+                (body_row, cell, "Sorted")
+            }
+            _ => (body_row, cell, "Aligned"),
+        };
+
+        let rendered = table.render();
+        let steps = self.replace_lines(start, end, &rendered);
+        self.extend_undo_group(steps, aligned);
+        let target_row = target_body.map_or(start, |r| start + 2 + r);
+        let col = table::cell_text_start(&rendered[target_row - start], target_cell);
+        self.textarea
+            .move_cursor(CursorMove::Jump(target_row as u16, col as u16));
+        self.status = status.to_string();
+    }
+
+    // Rows for :csv2table / :table2csv: the Visual selection, else the table
+    // or paragraph under the cursor
+    fn conversion_range(&mut self) -> (usize, usize) {
+        if let Some((start, end)) = self.command_range.take() {
+            return (start, end + 1);
+        }
+        let lines = self.textarea.lines();
+        let row = self.textarea.cursor().0;
+        if let Some(range) = table::table_range(lines, row) {
+            return range;
+        }
+        let mut start = row;
+        while start > 0 && !lines[start - 1].trim().is_empty() {
+            start -= 1;
+        }
+        let mut end = row;
+        while end < lines.len() && !lines[end].trim().is_empty() {
+            end += 1;
+        }
+        (start, end)
+    }
+
+    fn convert_table(&mut self, to_csv: bool) {
+        let (start, end) = self.conversion_range();
+        let lines = &self.textarea.lines()[start..end];
+        let converted = if to_csv {
+            Table::parse(lines).map(|t| t.to_csv())
+        } else {
+            Table::from_csv(lines).map(|t| t.render())
+        };
+        match converted {
+            Some(new_lines) if end > start => {
+                self.replace_lines(start, end, &new_lines);
+                self.textarea.move_cursor(CursorMove::Jump(start as u16, 0));
+                self.status = format!("Converted {} lines", end - start);
+            }
+            _ => self.status = "Nothing to convert".to_string(),
+        }
+    }
+
+    fn open_properties(&mut self) -> Result<(), EditorError> {
+        self.props_state.properties = frontmatter::parse(self.textarea.lines());
+        self.props_state.vault_keys = self.load_property_keys()?;
+        self.props_state.list_state = ListState::default();
+        if !self.props_state.properties.is_empty() {
+            self.props_state.list_state.select(Some(0));
+        }
+        self.props_state.editing = PropsField::None;
+        self.props_state.input.clear();
+        self.props_state.modified = false;
+        self.mode = Mode::Properties;
+        self.status = "Properties".to_string();
+        Ok(())
+    }
+
+    // Frontmatter keys used anywhere in the vault, as stored by markdown-scanner
+    fn load_property_keys(&self) -> Result<Vec<(String, PropertyValue)>, EditorError> {
+        let mut stmt = self
+            .db
+            .prepare("SELECT metadata FROM files WHERE metadata IS NOT NULL")?;
+        let rows = stmt
+            .query_map([], |row| row.get::<_, String>(0))?
+            .collect::<Result<Vec<_>, _>>()?;
+        let mut keys: std::collections::BTreeMap<String, PropertyValue> =
+            std::collections::BTreeMap::new();
+        for metadata in rows {
+            if let Ok(serde_json::Value::Object(map)) = serde_json::from_str(&metadata) {
+                for (key, value) in map {
+                    keys.entry(key)
+                        .or_insert_with(|| PropertyValue::empty_like(&value));
+                }
+            }
+        }
+        Ok(keys.into_iter().collect())
+    }
+
+    fn property_key_suggestions(&self) -> Vec<&(String, PropertyValue)> {
+        let input = self.props_state.input.to_lowercase();
+        self.props_state
+            .vault_keys
+            .iter()
+            .filter(|(key, _)| key.to_lowercase().contains(&input))
+            .filter(|(key, _)| !self.props_state.properties.iter().any(|p| &p.key == key))
+            .collect()
+    }
+
+    fn start_property_edit(&mut self, field: PropsField) {
+        let selected = self.props_state.list_state.selected();
+        let property = selected.and_then(|i| self.props_state.properties.get(i));
+        self.props_state.input = match (field, property) {
+            (PropsField::Key, Some(p)) if !self.props_state.adding => p.key.clone(),
+            (PropsField::Value, Some(p)) => p.value.edit_text(),
+            _ => String::new(),
+        };
+        if field == PropsField::Value
+            && matches!(property, Some(p) if matches!(p.value, PropertyValue::Raw(_)))
+        {
+            self.status = "Nested YAML, edit it in the buffer".to_string();
+            return;
+        }
+        if field == PropsField::Key
+            && !self.props_state.adding
+            && matches!(property, Some(p) if p.key.is_empty())
+        {
+            self.status = "Not a property, edit it in the buffer".to_string();
+            return;
+        }
+        if field == PropsField::Value && property.is_none() {
+            return;
+        }
+        self.props_state.editing = field;
+        self.props_state.key_state.select(None);
+    }
+
+    fn commit_property_input(&mut self) {
+        let input = self.props_state.input.trim().to_string();
+        match self.props_state.editing {
+            PropsField::Key => {
+                let key = match self.props_state.key_state.selected() {
+                    Some(i) => self
+                        .property_key_suggestions()
+                        .get(i)
+                        .map(|(key, _)| key.clone())
+                        .unwrap_or(input),
+                    None => input,
+                };
+                if key.is_empty() || key.contains(':') || key.starts_with(['-', '#']) {
+                    self.status = format!("Invalid property name: {}", key);
+                    return;
+                }
+                let selected = self.props_state.list_state.selected();
+                let duplicate = self
+                    .props_state
+                    .properties
+                    .iter()
+                    .enumerate()
+                    .any(|(i, p)| p.key == key && (self.props_state.adding || Some(i) != selected));
+                if duplicate {
+                    self.status = format!("Property already exists: {}", key);
+                    return;
+                }
+                if self.props_state.adding {
+                    let value = self
+                        .props_state
+                        .vault_keys
+                        .iter()
+                        .find(|(k, _)| *k == key)
+                        .map(|(_, v)| v.clone())
+                        .unwrap_or(PropertyValue::Text(String::new()));
+                    let is_checkbox = matches!(value, PropertyValue::Checkbox(_));
+                    self.props_state.properties.push(Property { key, value });
+                    self.props_state
+                        .list_state
+                        .select(Some(self.props_state.properties.len() - 1));
+                    self.props_state.adding = false;
+                    self.props_state.modified = true;
+                    if !is_checkbox {
+                        self.start_property_edit(PropsField::Value);
+                        return;
+                    }
+                } else if let Some(i) = selected {
+                    self.props_state.properties[i].rename(key);
+                    self.props_state.modified = true;
+                }
+            }
+            PropsField::Value => {
+                let Some(i) = self.props_state.list_state.selected() else {
+                    return;
+                };
+                match self.props_state.properties[i].value.with_input(&input) {
+                    Ok(value) => {
+                        self.props_state.properties[i].value = value;
+                        self.props_state.modified = true;
+                    }
+                    Err(e) => {
+                        self.status = e;
+                        return;
+                    }
+                }
+            }
+            PropsField::None => {}
+        }
+        self.props_state.editing = PropsField::None;
+        self.props_state.input.clear();
+        self.status = "Properties".to_string();
+    }
+
+    // Write the edited properties back as the note's frontmatter
+    fn write_properties(&mut self) {
+        if !self.props_state.modified {
+            return;
+        }
+        let end = frontmatter::block_range(self.textarea.lines())
+            .map(|(_, close)| close + 1)
+            .unwrap_or(0);
+        let yaml = frontmatter::rewrite(self.textarea.lines(), &self.props_state.properties);
+        self.replace_lines(0, end, &yaml);
+        self.props_state.modified = false;
+    }
+
+    fn close_properties(&mut self) {
+        let modified = self.props_state.modified;
+        self.write_properties();
+        self.props_state.editing = PropsField::None;
+        self.mode = Mode::Normal;
+        self.status = if modified {
+            "Properties updated".to_string()
+        } else {
+            "Normal".to_string()
+        };
+    }
+
+    // `:table #tag status due` or `:table "Projects" status`
+    fn open_table(&mut self, args: &str) -> Result<(), EditorError> {
+        let mut words = args.split([' ', ',']).filter(|w| !w.is_empty());
+        let source = match words.next() {
+            None | Some("*") => QuerySource::All,
+            Some(w) if w.starts_with('#') => {
+                QuerySource::Tag(w.trim_start_matches('#').to_string())
+            }
+            Some(w) => QuerySource::Folder(w.trim_matches(['"', '\'', '/']).to_string()),
+        };
+        let records = self.load_query_records(&source)?;
+        let mut keys: Vec<String> = words.map(|w| w.to_string()).collect();
+        if keys.is_empty() {
+            // Every frontmatter key used by the listed notes
+            let mut all = std::collections::BTreeSet::new();
+            for record in &records {
+                if let Some(map) = record.metadata.as_object() {
+                    all.extend(map.keys().cloned());
+                }
+            }
+            keys = all.into_iter().collect();
+        }
+
+        let mut columns = vec!["name".to_string()];
+        columns.extend(keys);
+        self.table_state.title = match &source {
+            QuerySource::All => "All notes".to_string(),
+            QuerySource::Tag(tag) => format!("#{}", tag),
+            QuerySource::Folder(folder) => format!("{}/", folder),
+        };
+        self.table_state.filters = vec![String::new(); columns.len()];
+        self.table_state.columns = columns;
+        self.table_state.records = records;
+        self.table_state.column = 0;
+        self.table_state.sort = None;
+        self.table_state.editing = TableInput::None;
+        self.table_state.input.clear();
+        self.refresh_table_rows();
+        self.mode = Mode::Table;
+        self.view = View::Editor;
+        self.status = format!("{} notes", self.table_state.records.len());
+        Ok(())
+    }
+
+    fn table_cell(&self, record: &NoteRecord, column: usize) -> String {
+        match column {
+            0 => record.name.clone(),
+            _ => record.field(&self.table_state.columns[column]),
+        }
+    }
+
+    fn refresh_table_rows(&mut self) {
+        let selected_record = self
+            .table_state
+            .list_state
+            .selected()
+            .and_then(|i| self.table_state.rows.get(i))
+            .copied();
+        let mut rows: Vec<usize> = (0..self.table_state.records.len())
+            .filter(|&i| {
+                let record = &self.table_state.records[i];
+                self.table_state
+                    .filters
+                    .iter()
+                    .enumerate()
+                    .all(|(column, filter)| {
+                        filter.is_empty()
+                            || self
+                                .table_cell(record, column)
+                                .to_lowercase()
+                                .contains(&filter.to_lowercase())
+                    })
+            })
+            .collect();
+        if let Some((column, descending)) = self.table_state.sort {
+            rows.sort_by(|&a, &b| {
+                let a = self.table_cell(&self.table_state.records[a], column);
+                let b = self.table_cell(&self.table_state.records[b], column);
+                let ordering = match (a.parse::<f64>(), b.parse::<f64>()) {
+                    (Ok(a), Ok(b)) => a.partial_cmp(&b).unwrap_or(std::cmp::Ordering::Equal),
+                    _ => a.to_lowercase().cmp(&b.to_lowercase()),
+                };
+                if descending {
+                    ordering.reverse()
+                } else {
+                    ordering
+                }
+            });
+        }
+        let selected = selected_record
+            .and_then(|r| rows.iter().position(|&i| i == r))
+            .or(if rows.is_empty() { None } else { Some(0) });
+        self.table_state.rows = rows;
+        self.table_state.list_state.select(selected);
+    }
+
+    fn run_scanner(&self, path: &str) -> Result<(), EditorError> {
+        let output = Command::new("markdown-scanner")
+            .arg(path)
+            .arg(&self.base_dir)
+            .output()?;
+        if !output.status.success() {
+            let error_msg = String::from_utf8_lossy(&output.stderr).into_owned();
+            return Err(EditorError::Scanner(error_msg));
+        }
+        if path.ends_with(".md")
+            && let Ok(file_id) = App::get_file_id(&self.db, path)
+        {
+            let content = fs::read_to_string(path)?;
+            let lines: Vec<String> = content.lines().map(|l| l.to_string()).collect();
+            self.index_tasks(file_id, &lines)?;
+        }
+        Ok(())
+    }
+
+    // Write the edited cell into the frontmatter of its note
+    fn commit_table_cell(&mut self) -> Result<(), EditorError> {
+        let column = self.table_state.column;
+        let Some(&index) = self
+            .table_state
+            .list_state
+            .selected()
+            .and_then(|i| self.table_state.rows.get(i))
+        else {
+            return Ok(());
+        };
+        let key = self.table_state.columns[column].clone();
+        let path = self.table_state.records[index].path.clone();
+        // A key missing from this note takes the type it has in the other rows
+        let default = self
+            .table_state
+            .records
+            .iter()
+            .find_map(|r| r.metadata.get(&key))
+            .map(PropertyValue::empty_like)
+            .unwrap_or(PropertyValue::Text(String::new()));
+        if self.has_unsaved_buffer(Path::new(&path)) {
+            self.status = Self::unsaved_buffer_status(Path::new(&path));
+            return Ok(());
+        }
+
+        let is_current = path == self.file_path;
+        let lines: Vec<String> = if is_current {
+            self.textarea.lines().to_vec()
+        } else {
+            match fs::read_to_string(&path) {
+                Ok(content) => content.lines().map(|l| l.to_string()).collect(),
+                Err(e) => {
+                    self.status = format!("Cannot read {}: {}", gettitle!(&path), e);
+                    return Ok(());
+                }
+            }
+        };
+        let (new_lines, value) =
+            match frontmatter::set_property(&lines, &key, &self.table_state.input, default) {
+                Ok(result) => result,
+                Err(e) => {
+                    self.status = e;
+                    return Ok(());
+                }
+            };
+
+        if is_current {
+            // Keep it in the open buffer so it is saved (and undone) with the rest
+            let old_end = frontmatter::block_range(&lines)
+                .map(|(_, close)| close + 1)
+                .unwrap_or(0);
+            let new_end = new_lines.len() - (lines.len() - old_end);
+            self.replace_lines(0, old_end, &new_lines[..new_end]);
+            self.status = format!("Set {} in the buffer (not saved)", key);
+        } else {
+            if let Err(e) = fs::write(&path, new_lines.join("\n")) {
+                self.status = format!("Cannot write {}: {}", gettitle!(&path), e);
+                return Ok(());
+            }
+            self.run_scanner(&path)?;
+            self.status = format!("Set {} in {}", key, gettitle!(&path));
+        }
+
+        let record = &mut self.table_state.records[index];
+        if !record.metadata.is_object() {
+            record.metadata = serde_json::Value::Object(serde_json::Map::new());
+        }
+        record.metadata[key.as_str()] = value.to_json();
+        self.table_state.editing = TableInput::None;
+        self.table_state.input.clear();
+        self.refresh_table_rows();
+        Ok(())
+    }
+
+    fn open_table_note(&mut self) -> Result<(), EditorError> {
+        let Some(&index) = self
+            .table_state
+            .list_state
+            .selected()
+            .and_then(|i| self.table_state.rows.get(i))
+        else {
+            return Ok(());
+        };
+        let record = self.table_state.records[index].clone();
+        self.jump_to_file(record.path, record.id)
+    }
+
+    fn build_root(&self) -> Vec<TreeNode> {
+        let mut root = Vec::new();
+        if let Ok(iter) = fs::read_dir(&self.base_dir) {
+            for entry in iter {
+                if let Ok(entry) = entry {
+                    let name = entry.file_name().to_string_lossy().to_string();
+                    if name.starts_with(".") {
+                        continue;
+                    }
+                    let p = entry.path();
+                    let rel = name;
+                    if p.is_dir() {
+                        root.push(TreeNode::Dir {
+                            path: rel,
+                            expanded: false,
+                            children: Vec::new(),
+                        });
+                    } else if p.extension().and_then(|s| s.to_str()) == Some("md") {
+                        root.push(TreeNode::File(rel));
+                    }
+                }
+            }
+        }
+        let mut sorted = root;
+        Self::sort_entries(
+            &mut sorted,
+            self.sort_by.clone(),
+            self.sort_asc,
+            &self.base_dir,
+        );
+        sorted
+    }
+
+    fn sort_nodes(nodes: &mut Vec<TreeNode>, sort_by: SortBy, sort_asc: bool, base_dir: &str) {
+        Self::sort_entries(nodes, sort_by.clone(), sort_asc, base_dir);
+        for node in nodes.iter_mut() {
+            if let TreeNode::Dir { children, .. } = node {
+                Self::sort_nodes(children, sort_by.clone(), sort_asc, base_dir);
+            }
+        }
+    }
+
+    fn build_tree_node(
+        full_path: &Path,
+        rel_path: &str,
+        sort_by: SortBy,
+        sort_asc: bool,
+        base_dir: &str,
+    ) -> Vec<TreeNode> {
+        let mut entries = Vec::new();
+        if let Ok(iter) = fs::read_dir(full_path) {
+            for entry in iter {
+                if let Ok(entry) = entry {
+                    let name = entry.file_name().to_string_lossy().to_string();
+                    if name.starts_with(".") {
+                        continue;
+                    }
+                    let p = entry.path();
+                    let rel = if rel_path.is_empty() {
+                        name
+                    } else {
+                        format!("{}/{}", rel_path, name)
+                    };
+                    if p.is_dir() {
+                        entries.push(TreeNode::Dir {
+                            path: rel,
+                            expanded: false,
+                            children: Vec::new(),
+                        });
+                    } else if p.extension().and_then(|s| s.to_str()) == Some("md") {
+                        entries.push(TreeNode::File(rel));
+                    }
+                }
+            }
+        }
+        let mut sorted = entries;
+        Self::sort_entries(&mut sorted, sort_by, sort_asc, base_dir);
+        sorted
+    }
+
+    fn sort_entries(entries: &mut Vec<TreeNode>, sort_by: SortBy, sort_asc: bool, base_dir: &str) {
+        entries.sort_by(|a, b| {
+            let (name_a, is_dir_a) = match a {
+                TreeNode::File(p) => (p.as_str(), false),
+                TreeNode::Dir { path, .. } => (path.as_str(), true),
+            };
+            let (name_b, is_dir_b) = match b {
+                TreeNode::File(p) => (p.as_str(), false),
+                TreeNode::Dir { path, .. } => (path.as_str(), true),
+            };
+            if is_dir_a != is_dir_b {
+                is_dir_b.cmp(&is_dir_a) // dirs first
+            } else {
+                if sort_by == SortBy::Name {
+                    if sort_asc {
+                        name_a.cmp(name_b)
+                    } else {
+                        name_b.cmp(name_a)
+                    }
+                } else {
+                    let path_a = Path::new(base_dir).join(name_a);
+                    let path_b = Path::new(base_dir).join(name_b);
+                    let time_a = fs::metadata(&path_a)
+                        .and_then(|m| m.modified())
+                        .map_err(|e| {
+                            eprintln!("Error getting metadata for {}: {}", path_a.display(), e)
+                        })
+                        .unwrap_or(SystemTime::UNIX_EPOCH);
+                    let time_b = fs::metadata(&path_b)
+                        .and_then(|m| m.modified())
+                        .map_err(|e| {
+                            eprintln!("Error getting metadata for {}: {}", path_b.display(), e)
+                        })
+                        .unwrap_or(SystemTime::UNIX_EPOCH);
+                    if sort_asc {
+                        time_a.cmp(&time_b)
+                    } else {
+                        time_b.cmp(&time_a)
+                    }
+                }
+            }
+        });
+    }
+
+    fn toggle_sort_modified(&mut self) {
+        if self.sort_by == SortBy::Modified {
+            self.sort_asc = !self.sort_asc;
+        } else {
+            self.sort_by = SortBy::Modified; // Keep enum variant as Created for compatibility
+            self.sort_asc = false;
+        }
+        self.status = format!(
+            "Sorted by modification time ({})",
+            if self.sort_asc {
+                "ascending"
+            } else {
+                "descending"
+            }
+        );
+    }
+
+    fn update_tree_sort(&mut self) {
+        Self::sort_nodes(
+            &mut self.file_tree,
+            self.sort_by.clone(),
+            self.sort_asc,
+            &self.base_dir,
+        );
+        self.update_visible();
+    }
+
+    fn toggle_sort_name(&mut self) {
+        if self.sort_by == SortBy::Name {
+            self.sort_asc = !self.sort_asc;
+        } else {
+            self.sort_by = SortBy::Name;
+            self.sort_asc = true;
+        }
+    }
+
+    fn update_visible(&mut self) {
+        let mut visible = Vec::new();
+        Self::add_nodes_to_visible(&self.file_tree, 0, &mut visible);
+        self.visible_items = visible;
+    }
+
+    fn add_nodes_to_visible(nodes: &[TreeNode], depth: usize, visible: &mut Vec<TreeItem>) {
+        for node in nodes {
+            match node {
+                TreeNode::File(p) => {
+                    let display = format!(
+                        "{}{} {}",
+                        "  ".repeat(depth),
+                        '',
+                        Path::new(p)
+                            .file_name()
+                            .and_then(|s| s.to_str())
+                            .unwrap_or("")
+                    );
+                    visible.push(TreeItem {
+                        display,
+                        path: p.clone(),
+                        is_dir: false,
+                        depth,
+                    });
+                }
+                TreeNode::Dir {
+                    path,
+                    expanded,
+                    children,
+                } => {
+                    let display = format!(
+                        "{}{} {}/",
+                        "  ".repeat(depth),
+                        '',
+                        Path::new(path)
+                            .file_name()
+                            .and_then(|s| s.to_str())
+                            .unwrap_or("")
+                    );
+                    visible.push(TreeItem {
+                        display,
+                        path: path.clone(),
+                        is_dir: true,
+                        depth,
+                    });
+                    if *expanded {
+                        Self::add_nodes_to_visible(children, depth + 1, visible);
+                    }
+                }
+            }
+        }
+    }
+
+    fn find_node_mut<'a>(
+        nodes: &'a mut Vec<TreeNode>,
+        path_segments: &[&str],
+    ) -> Option<&'a mut TreeNode> {
+        if path_segments.is_empty() {
+            return None;
+        }
+        let mut current: &mut Vec<TreeNode> = nodes;
+        for (i, &name) in path_segments.iter().enumerate() {
+            let mut found_idx = None;
+            for (idx, node) in current.iter_mut().enumerate() {
+                let node_name = match node {
+                    TreeNode::File(p) => Path::new(p.as_str())
+                        .file_name()
+                        .and_then(|s| s.to_str())
+                        .unwrap_or(""),
+                    TreeNode::Dir { path, .. } => Path::new(path.as_str())
+                        .file_name()
+                        .and_then(|s| s.to_str())
+                        .unwrap_or(""),
+                };
+                if node_name == name {
+                    found_idx = Some(idx);
+                    break;
+                }
+            }
+            if let Some(idx) = found_idx {
+                if i == path_segments.len() - 1 {
+                    return Some(&mut current[idx]);
+                } else {
+                    if let TreeNode::Dir { children, .. } = &mut current[idx] {
+                        current = children;
+                    } else {
+                        return None;
+                    }
+                }
+            } else {
+                return None;
+            }
+        }
+        None
+    }
+
+    fn toggle_expand_dir(&mut self, index: usize) -> Result<(), EditorError> {
+        if index >= self.visible_items.len() {
+            return Ok(());
+        }
+        let item = self.visible_items[index].clone();
+        if item.is_dir {
+            let segments: Vec<&str> = item.path.split('/').collect();
+            if let Some(node) = Self::find_node_mut(&mut self.file_tree, &segments) {
+                if let TreeNode::Dir {
+                    ref mut expanded,
+                    ref mut children,
+                    ref path,
+                } = *node
+                {
+                    *expanded = !*expanded;
+                    if *expanded && children.is_empty() {
+                        let full = Path::new(&self.base_dir).join(path);
+                        *children = Self::build_tree_node(
+                            &full,
+                            path.as_str(),
+                            self.sort_by.clone(),
+                            self.sort_asc,
+                            &self.base_dir,
+                        );
+                    }
+                    self.update_visible();
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn expand_dir(&mut self, index: usize) -> Result<(), EditorError> {
+        if index >= self.visible_items.len() {
+            return Ok(());
+        }
+        let item = self.visible_items[index].clone();
+        if item.is_dir {
+            let segments: Vec<&str> = item.path.split('/').collect();
+            if let Some(node) = Self::find_node_mut(&mut self.file_tree, &segments) {
+                if let TreeNode::Dir {
+                    ref mut expanded,
+                    ref mut children,
+                    ref path,
+                } = *node
+                {
+                    if !*expanded {
+                        *expanded = true;
+                        if children.is_empty() {
+                            let full = Path::new(&self.base_dir).join(path);
+                            *children = Self::build_tree_node(
+                                &full,
+                                path.as_str(),
+                                self.sort_by.clone(),
+                                self.sort_asc,
+                                &self.base_dir,
+                            );
+                        }
+                        self.update_visible();
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn collapse_dir(&mut self, index: usize) -> Result<(), EditorError> {
+        if index >= self.visible_items.len() {
+            return Ok(());
+        }
+        let item = self.visible_items[index].clone();
+        if item.is_dir {
+            let segments: Vec<&str> = item.path.split('/').collect();
+            if let Some(node) = Self::find_node_mut(&mut self.file_tree, &segments) {
+                if let TreeNode::Dir {
+                    ref mut expanded, ..
+                } = *node
+                {
+                    if *expanded {
+                        *expanded = false;
+                        self.update_visible();
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn delete_file(&mut self, path: &str) -> Result<(), EditorError> {
+        let full_path = Path::new(&self.base_dir)
+            .join(path)
+            .to_string_lossy()
+            .to_string();
+        fs::remove_file(&full_path)?;
+        let output = Command::new("markdown-scanner")
+            .arg("--delete")
+            .arg(&full_path)
+            .arg(&self.base_dir)
+            .output()?;
+        if !output.status.success() {
+            let error_msg = String::from_utf8_lossy(&output.stderr).into_owned();
+            return Err(EditorError::Scanner(error_msg));
+        }
+        self.remove_node(path);
+        Ok(())
+    }
+
+    fn delete_selected_file(&mut self) -> Result<(), EditorError> {
+        if let Some(selected) = self.tree_state.selected() {
+            let item = self.visible_items[selected].clone();
+            if !item.is_dir {
+                self.delete_file(&item.path)?;
+                self.update_visible();
+            } else {
+                self.status = "Cannot delete directories".to_string();
+            }
+        }
+        Ok(())
+    }
+
+    fn delete_selected_files(&mut self) -> Result<(), EditorError> {
+        let current = self.tree_state.selected().unwrap_or(0);
+        let anchor = self.tree_visual_anchor.unwrap_or(current);
+        let min = anchor.min(current);
+        let max = anchor.max(current);
+        let mut to_delete = Vec::new();
+        for i in min..=max {
+            let item = self.visible_items[i].clone();
+            if !item.is_dir {
+                to_delete.push(item.path);
+            }
+        }
+        for path in to_delete {
+            self.delete_file(&path)?;
+        }
+        self.update_visible();
+        self.tree_state.select(Some(min));
+        Ok(())
+    }
+
+    fn remove_node(&mut self, path: &str) {
+        let segments: Vec<&str> = path.split('/').collect();
+        if segments.len() == 1 {
+            self.file_tree.retain(|n| match n {
+                TreeNode::File(p) => p.as_str() != path,
+                TreeNode::Dir { path: d, .. } => d.as_str() != path,
+            });
+        } else {
+            let parent_segments = &segments[0..segments.len() - 1];
+            if let Some(parent) = Self::find_node_mut(&mut self.file_tree, parent_segments) {
+                if let TreeNode::Dir {
+                    ref mut children, ..
+                } = *parent
+                {
+                    children.retain(|n| match n {
+                        TreeNode::File(p) => {
+                            p.rsplit('/').next().unwrap() != *segments.last().unwrap()
+                        }
+                        TreeNode::Dir { path: d, .. } => {
+                            d.rsplit('/').next().unwrap() != *segments.last().unwrap()
+                        }
+                    });
+                }
+            }
+        }
+    }
+
+    fn yank_selected(&mut self) {
+        let current = self.tree_state.selected().unwrap_or(0);
+        let anchor = self.tree_visual_anchor.unwrap_or(current);
+        let min = anchor.min(current);
+        let max = anchor.max(current);
+        self.yanked_paths.clear();
+        for i in min..=max {
+            let item = &self.visible_items[i];
+            if !item.is_dir {
+                self.yanked_paths.push(item.path.clone());
+            }
+        }
+        self.status = format!("Yanked {} paths", self.yanked_paths.len());
+    }
+
+    fn cut_selected(&mut self) {
+        self.yank_selected();
+        self.buffer_mode = Some(BufferMode::Cut);
+        self.status = format!("Cut {} paths to buffer", self.yanked_paths.len());
+    }
+
+    fn copy_selected(&mut self) {
+        self.yank_selected();
+        self.buffer_mode = Some(BufferMode::Copy);
+        self.status = format!("Copied {} paths to buffer", self.yanked_paths.len());
+    }
+
+    fn paste_buffer(&mut self) -> Result<(), EditorError> {
+        if self.yanked_paths.is_empty() {
+            self.status = "No paths in buffer".to_string();
+            return Ok(());
+        }
+        if let Some(selected) = self.tree_state.selected() {
+            let item = self.visible_items[selected].clone();
+            let target_dir = if item.is_dir {
+                item.path
+            } else {
+                let path = Path::new(&item.path)
+                    .parent()
+                    .unwrap_or(Path::new(""))
+                    .to_string_lossy()
+                    .to_string();
+                if path == "" {
+                    self.base_dir.clone()
+                } else {
+                    path
+                }
+            };
+            match self.buffer_mode {
+                Some(BufferMode::Cut) => {
+                    self.move_paths(self.yanked_paths.clone(), target_dir)?;
+                    self.yanked_paths.clear();
+                    self.buffer_mode = None;
+                    self.status = "Pasted (moved) from buffer".to_string();
+                }
+                Some(BufferMode::Copy) => {
+                    self.copy_paths(self.yanked_paths.clone(), target_dir)?;
+                    // Do not clear for copy, allow multiple pastes
+                    self.status = "Pasted (cloned) from buffer".to_string();
+                }
+                None => {
+                    self.status = "No buffer mode set".to_string();
+                }
+            }
+            self.file_tree = self.build_root();
+            self.update_visible();
+        }
+        Ok(())
+    }
+
+    fn create_new_file(&mut self, name: String) -> Result<(), EditorError> {
+        if let Some(selected) = self.tree_state.selected() {
+            let item = self.visible_items[selected].clone();
+            let target_dir = if item.is_dir {
+                item.path
+            } else {
+                Path::new(&item.path)
+                    .parent()
+                    .unwrap_or(Path::new(""))
+                    .to_string_lossy()
+                    .to_string()
+            };
+            self.create_note(&target_dir, &name, "")?;
+            self.status = "Created new file".to_string();
+        }
+        Ok(())
+    }
+
+    // Write `name`.md into `dir` (relative to base_dir), index it and refresh the tree
+    fn create_note(&mut self, dir: &str, name: &str, content: &str) -> Result<String, EditorError> {
+        let new_path = if dir.is_empty() {
+            format!("{}.md", name)
+        } else {
+            format!("{}/{}.md", dir, name)
+        };
+        let full_path = Path::new(&self.base_dir)
+            .join(&new_path)
+            .to_string_lossy()
+            .to_string();
+        fs::write(&full_path, content)?;
+        self.run_scanner(&full_path)?;
+        self.file_tree = self.build_root();
+        self.update_visible();
+        Ok(full_path)
+    }
+
+    // `:extract Title` in Visual mode: move the selection into a new note next to
+    // this one and leave `[[Title]]` in its place. `:extract!` applies the template.
+    fn extract_selection(&mut self, title: &str, use_template: bool) -> Result<(), EditorError> {
+        let title = title.trim();
+        if title.is_empty() {
+            self.status = "Usage: :extract New Title".to_string();
+            return Ok(());
+        }
+        // The title names a file next to this note and the link to it
+        if markdown::note_name(title) != title {
+            self.status = format!("\"{}\" is not a valid note name", title);
+            return Ok(());
+        }
+        let Some(((start_row, start_col), (end_row, end_col))) = self.command_selection.take()
+        else {
+            self.status = "Select the text to extract in Visual mode first".to_string();
+            return Ok(());
+        };
+        let dir = Path::new(&self.file_path)
+            .parent()
+            .map(|p| p.to_string_lossy().to_string())
+            .unwrap_or_default();
+        let target = Path::new(&self.base_dir)
+            .join(&dir)
+            .join(format!("{}.md", title));
+        if target.exists() || self.buffer_index_of(&target).is_some() {
+            self.status = format!("{}.md already exists", title);
+            return Ok(());
+        }
+
+        let lines = self.textarea.lines();
+        let byte = |row: usize, col: usize| {
+            lines[row]
+                .char_indices()
+                .nth(col)
+                .map(|(b, _)| b)
+                .unwrap_or(lines[row].len())
+        };
+        let selected = if start_row == end_row {
+            lines[start_row][byte(start_row, start_col)..byte(end_row, end_col)].to_string()
+        } else {
+            let mut selected = vec![lines[start_row][byte(start_row, start_col)..].to_string()];
+            selected.extend(lines[start_row + 1..end_row].iter().cloned());
+            selected.push(lines[end_row][..byte(end_row, end_col)].to_string());
+            selected.join("\n")
+        };
+        let content = if use_template {
+            match self.fill_template("Templates/Yaml-Template.md", title) {
+                Ok(template) => format!("{}\n{}", template, selected),
+                Err(e) => {
+                    self.status = format!("Cannot read the template: {}", e);
+                    return Ok(());
+                }
+            }
+        } else {
+            selected
+        };
+        if let Err(e) = self.create_note(&dir, title, &content) {
+            self.status = format!("Cannot write {}.md: {}", title, e);
+            return Ok(());
+        }
+
+        // Deleting the selection and inserting the link undo together
+        self.textarea.cancel_selection();
+        self.textarea
+            .move_cursor(CursorMove::Jump(start_row as u16, start_col as u16));
+        self.textarea.start_selection();
+        self.textarea
+            .move_cursor(CursorMove::Jump(end_row as u16, end_col as u16));
+        let steps = usize::from((start_row, start_col) != (end_row, end_col)) + 1;
+        self.textarea.insert_str(format!("[[{}]]", title));
+        if steps > 1 {
+            self.push_group(false, steps);
+        }
+        self.status = format!("Extracted to {}.md", title);
+        Ok(())
+    }
+
+    // `:splitnote H2`: one note per heading of that level, with links left in this one
+    fn split_note(&mut self, level_arg: &str) -> Result<(), EditorError> {
+        let digits = level_arg.trim().trim_start_matches(['H', 'h']);
+        let level = if digits.chars().all(|c| c == '#') && !digits.is_empty() {
+            digits.len()
+        } else {
+            digits.parse::<usize>().unwrap_or(2)
+        };
+        if !(1..=6).contains(&level) {
+            self.status = "Usage: :splitnote H2 (levels 1 to 6)".to_string();
+            return Ok(());
+        }
+        let (index, sections) = markdown::split_sections(self.textarea.lines(), level);
+        if sections.is_empty() {
+            self.status = format!("No level {} headings to split", level);
+            return Ok(());
+        }
+        let dir = Path::new(&self.file_path)
+            .parent()
+            .map(|p| p.to_string_lossy().to_string())
+            .unwrap_or_default();
+        let taken = sections.iter().enumerate().find(|(i, (name, _))| {
+            let path = Path::new(&self.base_dir)
+                .join(&dir)
+                .join(format!("{}.md", name));
+            name.is_empty()
+                || sections[..*i].iter().any(|(other, _)| other == name)
+                || path.exists()
+                || self.buffer_index_of(&path).is_some()
+        });
+        if let Some((_, (name, _))) = taken {
+            self.status = format!("Cannot split: note \"{}\" exists or is empty", name);
+            return Ok(());
+        }
+
+        for (name, body) in &sections {
+            self.create_note(&dir, name, &body.join("\n"))?;
+        }
+        let line_count = self.textarea.lines().len();
+        self.replace_lines(0, line_count, &index);
+        self.textarea.move_cursor(CursorMove::Top);
+        self.status = format!(
+            "Split into {} notes (save to keep the index)",
+            sections.len()
+        );
+        Ok(())
+    }
+
+    // FileTreeVisual `m`: merge the selected notes, in order, into `name`.md under
+    // a heading per note, and point links to them at those headings
+    fn merge_selected(&mut self, name: &str) -> Result<(), EditorError> {
+        let name = markdown::note_name(name);
+        let current = self.tree_state.selected().unwrap_or(0);
+        let anchor = self.tree_visual_anchor.take().unwrap_or(current);
+        let paths: Vec<String> = (anchor.min(current)..=anchor.max(current))
+            .filter_map(|i| self.visible_items.get(i))
+            .filter(|item| !item.is_dir && item.path.ends_with(".md"))
+            .map(|item| item.path.clone())
+            .collect();
+        if name.is_empty() || paths.len() < 2 {
+            self.status = "Select two or more notes and give the merged note a name".to_string();
+            return Ok(());
+        }
+        let dir = Path::new(&paths[0])
+            .parent()
+            .map(|p| p.to_string_lossy().to_string())
+            .unwrap_or_default();
+        let target = Path::new(&self.base_dir)
+            .join(&dir)
+            .join(format!("{}.md", name));
+        if target.exists() || self.buffer_index_of(&target).is_some() {
+            self.status = format!("{}.md already exists", name);
+            return Ok(());
+        }
+
+        let titles: Vec<String> = paths.iter().map(|p| gettitle!(p)).collect();
+        let mut merged = Vec::new();
+        let mut properties = Vec::new();
+        let mut sources = Vec::new();
+        for (path, title) in paths.iter().zip(&titles) {
+            let full_path = Path::new(&self.base_dir).join(path);
+            let content = match fs::read_to_string(&full_path) {
+                Ok(content) => content,
+                Err(e) => {
+                    self.status = format!("Cannot read {}: {}", title, e);
+                    return Ok(());
+                }
+            };
+            let lines: Vec<String> = content.lines().map(|s| s.to_string()).collect();
+            if !merged.is_empty() {
+                merged.push(String::new());
+            }
+            merged.push(format!("# {}", title));
+            // Frontmatter goes to the merged note's; what does not fit there
+            // stays under the heading as a YAML block
+            let leftover = frontmatter::merge(&mut properties, frontmatter::parse(&lines));
+            if !leftover.is_empty() {
+                let mut block = frontmatter::to_yaml(&leftover);
+                let last = block.len() - 1;
+                block[0] = "```yaml".to_string();
+                block[last] = "```".to_string();
+                merged.extend(block);
+            }
+            merged.extend(markdown::demote_headings(&lines));
+
+            // Notes linking here, found before the scanner forgets this one
+            if let Some((file_id, _)) = self.resolve_wikilink(&format!("{}.md", title))? {
+                let mut stmt = self.db.prepare(
+                    "SELECT DISTINCT f.path FROM backlinks b
+                     JOIN files f ON b.file_id = f.id
+                     WHERE b.backlink_id = ?",
+                )?;
+                for source in stmt.query_map([file_id], |row| row.get::<_, String>(0))? {
+                    let source = source?;
+                    if !sources.contains(&source) {
+                        sources.push(source);
+                    }
+                }
+            }
+        }
+        let retarget = |lines: Vec<String>| -> Vec<String> {
+            lines
+                .into_iter()
+                .map(|line| {
+                    titles.iter().fold(line, |line, title| {
+                        markdown::retarget_wikilinks(&line, title, &name, title)
+                    })
+                })
+                .collect()
+        };
+        if !properties.is_empty() {
+            merged.splice(0..0, frontmatter::to_yaml(&properties));
+        }
+        let merged = retarget(merged);
+
+        // Nothing is written while one of the notes has unsaved edits in a buffer
+        let mut touched = paths.iter().map(|p| Path::new(&self.base_dir).join(p));
+        let unsaved = touched
+            .find(|p| {
+                self.has_unsaved_buffer(p)
+                    || (p == Path::new(&self.file_path) && self.is_modified())
+            })
+            .or_else(|| {
+                sources
+                    .iter()
+                    .map(|s| Path::new(&self.base_dir).join(s))
+                    .find(|p| self.has_unsaved_buffer(p))
+            });
+        if let Some(path) = unsaved {
+            self.status = Self::unsaved_buffer_status(&path);
+            return Ok(());
+        }
+
+        let merged_path = match self.create_note(&dir, &name, &merged.join("\n")) {
+            Ok(path) => path,
+            Err(e) => {
+                self.status = format!("Cannot write {}.md: {}", name, e);
+                return Ok(());
+            }
+        };
+        let open_was_merged = paths
+            .iter()
+            .any(|p| Path::new(&self.base_dir).join(p) == Path::new(&self.file_path));
+        // From here on a note that cannot be changed is skipped and reported
+        let mut failed = Vec::new();
+        for path in &paths {
+            if self.delete_file(path).is_err() {
+                failed.push(gettitle!(path));
+            }
+        }
+
+        let mut rewritten = 0;
+        for source in sources {
+            let source_full = Path::new(&self.base_dir).join(&source);
+            if !source_full.exists() || source_full == Path::new(&merged_path) {
+                continue;
+            }
+            if source_full == Path::new(&self.file_path) {
+                // The open note is changed in the buffer so unsaved edits survive
+                let lines = retarget(self.textarea.lines().to_vec());
+                if lines.as_slice() != self.textarea.lines() {
+                    let line_count = self.textarea.lines().len();
+                    self.replace_lines(0, line_count, &lines);
+                    rewritten += 1;
+                }
+                continue;
+            }
+            let Ok(content) = fs::read_to_string(&source_full) else {
+                failed.push(gettitle!(&source));
+                continue;
+            };
+            let lines = retarget(content.lines().map(|s| s.to_string()).collect());
+            let new_content = lines.join("\n");
+            if new_content != content {
+                if fs::write(&source_full, new_content).is_err() {
+                    failed.push(gettitle!(&source));
+                    continue;
+                }
+                self.run_scanner(&source_full.to_string_lossy())?;
+                rewritten += 1;
+            }
+        }
+
+        self.file_tree = self.build_root();
+        self.update_visible();
+        if open_was_merged {
+            self.open_wikilink_file(format!("{}.md", name))?;
+        }
+        let deleted: Vec<_> = paths
+            .iter()
+            .map(|p| Path::new(&self.base_dir).join(p))
+            .filter(|p| !p.exists())
+            .collect();
+        self.buffers
+            .retain(|b| !deleted.iter().any(|p| p == Path::new(&b.path)));
+        self.status = format!(
+            "Merged {} notes into {}.md, updated links in {} notes",
+            paths.len(),
+            name,
+            rewritten
+        );
+        if !failed.is_empty() {
+            self.status
+                .push_str(&format!(" (could not change {})", failed.join(", ")));
+        }
+        Ok(())
+    }
+
+    fn rename_selected(&mut self, new_name: String) -> Result<(), EditorError> {
+        if let Some(selected) = self.tree_state.selected() {
+            let item = self.visible_items[selected].clone();
+            if item.is_dir {
+                self.status = "Cannot rename directories".to_string();
+                return Ok(());
+            }
+            let old_path = item.path;
+            let old_full = Path::new(&self.base_dir)
+                .join(&old_path)
+                .to_string_lossy()
+                .to_string();
+            let parent = Path::new(&old_path)
+                .parent()
+                .and_then(|p| p.to_str())
+                .unwrap_or("")
+                .to_string();
+            let new_path = if parent.is_empty() {
+                new_name.clone()
+            } else {
+                format!("{}/{}", parent, new_name)
+            };
+            let new_full = Path::new(&self.base_dir)
+                .join(&new_path)
+                .to_string_lossy()
+                .to_string();
+            fs::rename(&old_full, &new_full)?;
+            let output_delete = Command::new("markdown-scanner")
+                .arg("--delete")
+                .arg(&old_full)
+                .arg(&self.base_dir)
+                .output()?;
+            if !output_delete.status.success() {
+                let error_msg = String::from_utf8_lossy(&output_delete.stderr).into_owned();
+                return Err(EditorError::Scanner(error_msg));
+            }
+            let output_scan = Command::new("markdown-scanner")
+                .arg(&new_full)
+                .arg(&self.base_dir)
+                .output()?;
+            if !output_scan.status.success() {
+                let error_msg = String::from_utf8_lossy(&output_scan.stderr).into_owned();
+                return Err(EditorError::Scanner(error_msg));
+            }
+            self.remove_node(&old_path);
+            self.file_tree = self.build_root();
+            self.update_visible();
+        }
+        Ok(())
+    }
+
+    fn move_paths(&mut self, paths: Vec<String>, target_dir: String) -> Result<(), EditorError> {
+        for old_path in paths {
+            let old_full = Path::new(&self.base_dir)
+                .join(&old_path)
+                .to_string_lossy()
+                .to_string();
+            let filename = Path::new(&old_path)
+                .file_name()
+                .and_then(|s| s.to_str())
+                .unwrap_or("")
+                .to_string();
+            let new_path = format!("{}/{}", target_dir, filename);
+            let new_full = Path::new(&self.base_dir)
+                .join(&new_path)
+                .to_string_lossy()
+                .to_string();
+            fs::rename(&old_full, &new_full)?;
+            let output_delete = Command::new("markdown-scanner")
+                .arg("--delete")
+                .arg(&old_full)
+                .arg(&self.base_dir)
+                .output()?;
+            if !output_delete.status.success() {
+                let error_msg = String::from_utf8_lossy(&output_delete.stderr).into_owned();
+                return Err(EditorError::Scanner(error_msg));
+            }
+            let output_scan = Command::new("markdown-scanner")
+                .arg(&new_full)
+                .arg(&self.base_dir)
+                .output()?;
+            if !output_scan.status.success() {
+                let error_msg = String::from_utf8_lossy(&output_scan.stderr).into_owned();
+                return Err(EditorError::Scanner(error_msg));
+            }
+            self.remove_node(&old_path);
+        }
+        Ok(())
+    }
+
+    fn copy_paths(&mut self, paths: Vec<String>, target_dir: String) -> Result<(), EditorError> {
+        for old_path in paths {
+            let old_full = Path::new(&self.base_dir)
+                .join(&old_path)
+                .to_string_lossy()
+                .to_string();
+            let filename = Path::new(&old_path)
+                .file_name()
+                .and_then(|s| s.to_str())
+                .unwrap_or("")
+                .to_string();
+            let new_path = format!("{}/{}", target_dir, filename);
+            let new_full = Path::new(&self.base_dir)
+                .join(&new_path)
+                .to_string_lossy()
+                .to_string();
+            fs::copy(&old_full, &new_full)?;
+            let output_scan = Command::new("markdown-scanner")
+                .arg(&new_full)
+                .arg(&self.base_dir)
+                .output()?;
+            if !output_scan.status.success() {
+                let error_msg = String::from_utf8_lossy(&output_scan.stderr).into_owned();
+                return Err(EditorError::Scanner(error_msg));
+            }
+        }
+        Ok(())
+    }
+
+    pub fn handle_input(
+        &mut self,
+        event: ratatui::crossterm::event::KeyEvent,
+    ) -> Result<(), EditorError> {
+        // Keys the register notation has no name for are left out, not lost silently
+        let unrecordable = self.macro_depth == 0
+            && self.recording.is_some()
+            && registers::key_to_text(&event).is_none();
+        if self.macro_depth == 0
+            && !unrecordable
+            && let Some((_, keys)) = &mut self.recording
+        {
+            keys.push(event);
+        }
+        // Keys from Normal mode until it is back there with nothing pending make
+        // up one command; it becomes the last change if it edited the buffer
+        let tracking = !self.repeating;
+        if tracking {
+            if self.mode == Mode::Normal
+                && self.key_sequence.is_empty()
+                && self.pending_register.is_none()
+            {
+                self.change_keys.clear();
+                self.change_made = false;
+            }
+            self.change_keys.push(event);
+        }
+        // Only `j` `k` and Enter act on a picked query result
+        if !(self.mode == Mode::Normal
+            && self.key_sequence.is_empty()
+            && matches!(
+                event.code,
+                ratatui::crossterm::event::KeyCode::Char('j' | 'k')
+                    | ratatui::crossterm::event::KeyCode::Up
+                    | ratatui::crossterm::event::KeyCode::Down
+                    | ratatui::crossterm::event::KeyCode::Enter
+            ))
+        {
+            self.query_row = None;
+        }
+        let mode = self.mode;
+        self.handle_key(event)?;
+        if unrecordable {
+            self.status = format!("{:?} cannot be recorded in a macro", event.code);
+        }
+        self.sync_folds();
+        self.leave_closed_folds();
+        if tracking {
+            if matches!(self.mode, Mode::Insert | Mode::BlockInsert) && self.mode != mode {
+                self.change_made = true;
+            }
+            if self.change_made && self.mode == Mode::Normal && self.key_sequence.is_empty() {
+                self.last_change = std::mem::take(&mut self.change_keys);
+                self.change_made = false;
+            }
+        }
+        if self.mode == Mode::Normal && self.key_sequence.is_empty() {
+            self.timeline
+                .record(self.textarea.lines(), Local::now().timestamp());
+        }
+        if let Some((name, count)) = self.macro_request.take() {
+            self.play_macro(name, count)?;
+        }
+        if let Some(count) = self.repeat_request.take() {
+            self.repeat_change(count)?;
+        }
+        Ok(())
+    }
+
+    // `.`: replay the keys of the last change at the cursor, a count replacing
+    // the one it was made with
+    fn repeat_change(&mut self, count: Option<usize>) -> Result<(), EditorError> {
+        if self.last_change.is_empty() {
+            self.status = "No change to repeat".to_string();
+            return Ok(());
+        }
+        let keys = match count {
+            Some(count) => with_count(&self.last_change, count),
+            None => self.last_change.clone(),
+        };
+        // Later `.`s keep the new count, as in vim
+        self.last_change = keys.clone();
+        self.repeating = true;
+        let mut result = Ok(());
+        for key in keys {
+            result = self.handle_input(key);
+            if result.is_err() {
+                break;
+            }
+        }
+        self.repeating = false;
+        result
+    }
+
+    // Replay the keys kept in a register `count` times
+    fn play_macro(&mut self, name: char, count: usize) -> Result<(), EditorError> {
+        let Some(register) = self.registers.get(name) else {
+            self.status = format!("Register \"{} is empty", name);
+            return Ok(());
+        };
+        if self.macro_depth >= MAX_MACRO_DEPTH {
+            self.status = "Macro nested too deep".to_string();
+            return Ok(());
+        }
+        let keys = registers::text_to_keys(&register.text.join("\n"));
+        self.last_macro = Some(name);
+        self.macro_depth += 1;
+        let mut result = Ok(());
+        // Like vim, a motion that fails stops the macro and the ones running it
+        self.motion_failed = false;
+        'repeat: for _ in 0..count {
+            for key in &keys {
+                result = self.handle_input(*key);
+                if result.is_err() || self.motion_failed {
+                    break 'repeat;
+                }
+            }
+        }
+        self.macro_depth -= 1;
+        if self.macro_depth == 0 {
+            self.motion_failed = false;
+        }
+        result
+    }
+
+    fn stop_recording(&mut self) {
+        if let Some((name, mut keys)) = self.recording.take() {
+            keys.pop(); // The `q` that stopped it
+            self.registers.set(name, registers::keys_to_text(&keys));
+            self.save_session();
+            self.status = format!("Recorded @{}", name.to_ascii_lowercase());
+        }
+    }
+
+    fn handle_key(
+        &mut self,
+        event: ratatui::crossterm::event::KeyEvent,
+    ) -> Result<(), EditorError> {
+        match self.mode {
+            Mode::Normal => {
+                // Handle key sequence first if it has started
+                if !self.key_sequence.is_empty() {
+                    if let ratatui::crossterm::event::KeyCode::Char(c) = event.code {
+                        self.key_sequence.push(c);
+                        if self.handle_vim_sequence() {
+                            return Ok(());
+                        }
+                        let sequence = self.key_sequence.clone(); // Clone to avoid borrow issues
+                        match sequence.as_str() {
                             "\\ob" => {
                                 self.start_search(SearchType::Backlinks)?;
                                 self.key_sequence.clear();
@@ -1742,6 +4785,71 @@ impl App {
                             "\\nt" => {
                                 self.process_template_command("Templates/Yaml-Template.md")?;
                             }
+                            "\\rq" => {
+                                self.render_queries = !self.render_queries;
+                                self.query_results.clear();
+                                self.status = if self.render_queries {
+                                    "Rendering query blocks".to_string()
+                                } else {
+                                    "Query blocks as text".to_string()
+                                };
+                                self.key_sequence.clear();
+                            }
+                            "\\x" => {
+                                self.edit_task_lines(false);
+                                self.key_sequence.clear();
+                            }
+                            "\\s" => {
+                                self.edit_task_lines(true);
+                                self.key_sequence.clear();
+                            }
+                            "\\|r" | "\\|R" | "\\|c" | "\\|C" | "\\|s" | "\\|S" | "\\|f" => {
+                                self.table_command(sequence.chars().last().unwrap_or('f'));
+                                self.key_sequence.clear();
+                            }
+                            "\\h<" | "\\h>" | "\\hk" | "\\hj" | "\\hy" | "\\hd" => {
+                                self.section_command(sequence.chars().last().unwrap_or(' '));
+                                self.key_sequence.clear();
+                            }
+                            "za" | "zc" | "zo" | "zM" | "zR" => {
+                                self.fold_command(sequence.chars().last().unwrap_or('a'));
+                                self.key_sequence.clear();
+                            }
+                            "]]" | "[[" => {
+                                self.jump_heading(sequence == "]]");
+                                self.key_sequence.clear();
+                            }
+                            "gv" => {
+                                self.reselect_visual();
+                                self.key_sequence.clear();
+                            }
+                            s if s.starts_with(CTRL_W) && s.chars().count() == 2 => {
+                                self.key_sequence.clear();
+                                self.window_command(s.chars().nth(1).unwrap_or(' '))?;
+                            }
+                            s if s.starts_with('m') && s.chars().count() == 2 => {
+                                self.key_sequence.clear();
+                                self.set_mark(s.chars().nth(1).unwrap_or(' '))?;
+                            }
+                            s if (s.starts_with('\'') || s.starts_with('`'))
+                                && s.chars().count() == 2 =>
+                            {
+                                self.key_sequence.clear();
+                                self.jump_to_mark(
+                                    s.chars().nth(1).unwrap_or(' '),
+                                    s.starts_with('\''),
+                                )?;
+                            }
+                            "\\ol" => {
+                                self.outline_visible = !self.outline_visible;
+                                if self.outline_visible {
+                                    self.mode = Mode::Outline;
+                                    self.status = "Outline".to_string();
+                                } else {
+                                    self.status = "Outline closed".to_string();
+                                }
+                                self.key_sequence.clear();
+                            }
                             "\\if" => {
                                 if self.current_image.is_some() {
                                     self.image_full_screen = !self.image_full_screen;
@@ -1765,7 +4873,15 @@ impl App {
                                 || "\\ooT".starts_with(s)
                                 || "\\t".starts_with(s)
                                 || "\\nt".starts_with(s)
-                                || "\\if".starts_with(s)) =>
+                                || "\\if".starts_with(s)
+                                || "\\rq".starts_with(s)
+                                || "\\x".starts_with(s)
+                                || "\\s".starts_with(s)
+                                || "\\|".starts_with(s)
+                                || "\\ol".starts_with(s)
+                                || "\\h".starts_with(s)
+                                || "]]".starts_with(s)
+                                || "[[".starts_with(s)) =>
                             {
                                 self.key_sequence.clear();
                                 self.status = format!("Invalid sequence 1: {}", s);
@@ -1794,14 +4910,14 @@ impl App {
                         ratatui::crossterm::event::KeyCode::Char('r'),
                         ratatui::crossterm::event::KeyModifiers::CONTROL,
                     ) => {
-                        if self.textarea.redo() {
+                        if self.redo() {
                             self.status = "Redone".to_string();
                         } else {
                             self.status = "Nothing to redo".to_string();
                         }
                     }
                     (ratatui::crossterm::event::KeyCode::Char('u'), _) => {
-                        if self.textarea.undo() {
+                        if self.undo() {
                             self.status = "Undone".to_string();
                         } else {
                             self.status = "Nothing to undo".to_string();
@@ -1835,13 +4951,33 @@ impl App {
                         self.mode = Mode::Visual;
                         self.status = "Visual".to_string();
                     }
+                    (ratatui::crossterm::event::KeyCode::Char('V'), _) => {
+                        self.visual_anchor = Some(self.textarea.cursor());
+                        self.mode = Mode::VisualLine;
+                        self.status = "Visual Line".to_string();
+                    }
                     (ratatui::crossterm::event::KeyCode::Char('x'), _) => {
+                        let (row, col) = self.textarea.cursor();
+                        if let Some(c) = self.textarea.lines()[row].chars().nth(col) {
+                            self.store_register(vec![c.to_string()], false, true);
+                        } else {
+                            // Nothing deleted on an empty line: `"ax` must not carry over
+                            self.pending_register = None;
+                        }
                         self.textarea.delete_next_char();
+                        self.change_made = true;
                     }
                     (ratatui::crossterm::event::KeyCode::Char('p'), _) => {
-                        self.textarea.move_cursor(CursorMove::End);
-                        self.textarea.insert_char('\n');
-                        self.textarea.insert_str(&self.yanked.join("\n"));
+                        self.paste(false);
+                    }
+                    (ratatui::crossterm::event::KeyCode::Char('P'), _) => {
+                        self.paste(true);
+                    }
+                    (ratatui::crossterm::event::KeyCode::Tab, _) => {
+                        self.move_table_cell(true);
+                    }
+                    (ratatui::crossterm::event::KeyCode::BackTab, _) => {
+                        self.move_table_cell(false);
                     }
                     (ratatui::crossterm::event::KeyCode::Char(':'), _) => {
                         self.prev_mode = Some(Mode::Normal);
@@ -1850,10 +4986,10 @@ impl App {
                         self.status = "Command".to_string();
                     }
                     (ratatui::crossterm::event::KeyCode::Char('j'), _) => {
-                        self.textarea.move_cursor(CursorMove::Down);
+                        self.move_line(true);
                     }
                     (ratatui::crossterm::event::KeyCode::Char('k'), _) => {
-                        self.textarea.move_cursor(CursorMove::Up);
+                        self.move_line(false);
                     }
                     (ratatui::crossterm::event::KeyCode::Char('h'), _) => {
                         self.textarea.move_cursor(CursorMove::Back);
@@ -1862,11 +4998,11 @@ impl App {
                         self.textarea.move_cursor(CursorMove::Forward);
                     }
                     (ratatui::crossterm::event::KeyCode::Up, _) => {
-                        self.textarea.move_cursor(CursorMove::Up);
+                        self.move_line(false);
                         // self.scroll_offset = self.scroll_offset.saturating_sub(1);
                     }
                     (ratatui::crossterm::event::KeyCode::Down, _) => {
-                        self.textarea.move_cursor(CursorMove::Down);
+                        self.move_line(true);
                     }
                     (
                         ratatui::crossterm::event::KeyCode::Left,
@@ -1904,10 +5040,33 @@ impl App {
                     (ratatui::crossterm::event::KeyCode::End, _) => {
                         self.textarea.move_cursor(CursorMove::End);
                     }
-                    (ratatui::crossterm::event::KeyCode::Char('G'), _)
-                        if self.key_sequence.is_empty() =>
-                    {
-                        self.textarea.move_cursor(CursorMove::Bottom);
+                    (ratatui::crossterm::event::KeyCode::Char('G'), _)
+                        if self.key_sequence.is_empty() =>
+                    {
+                        self.textarea.move_cursor(CursorMove::Bottom);
+                    }
+                    (ratatui::crossterm::event::KeyCode::Char('K'), _)
+                        if self.key_sequence.is_empty() =>
+                    {
+                        if self.preview_state.active {
+                            self.close_note_preview();
+                            self.status = "Normal".to_string();
+                        } else {
+                            self.open_note_preview()?;
+                        }
+                    }
+                    (
+                        ratatui::crossterm::event::KeyCode::Char('e'),
+                        ratatui::crossterm::event::KeyModifiers::CONTROL,
+                    ) if self.preview_state.active => {
+                        self.preview_state.scroll = (self.preview_state.scroll + 1)
+                            .min(self.preview_state.lines.len().saturating_sub(1));
+                    }
+                    (
+                        ratatui::crossterm::event::KeyCode::Char('y'),
+                        ratatui::crossterm::event::KeyModifiers::CONTROL,
+                    ) if self.preview_state.active => {
+                        self.preview_state.scroll = self.preview_state.scroll.saturating_sub(1);
                     }
                     (
                         ratatui::crossterm::event::KeyCode::Char('q'),
@@ -1915,6 +5074,13 @@ impl App {
                     ) => {
                         self.should_quit = true;
                     }
+                    (
+                        ratatui::crossterm::event::KeyCode::Char('w'),
+                        ratatui::crossterm::event::KeyModifiers::CONTROL,
+                    ) => {
+                        // Window commands wait for their second key
+                        self.key_sequence.push(CTRL_W);
+                    }
                     (
                         ratatui::crossterm::event::KeyCode::Char('s'),
                         ratatui::crossterm::event::KeyModifiers::CONTROL,
@@ -1927,19 +5093,40 @@ impl App {
                         self.status = format!("Saved file: {}", gettitle!(&self.file_path));
                     }
                     (ratatui::crossterm::event::KeyCode::Char(c), _) => {
-                        //TODO IF I DELETE THAT THAN gg dd and yy in not working.
+                        // Starts a key sequence; motions like `w` run right away
                         self.key_sequence.push(c);
-                        let sequence = self.key_sequence.clone(); // Clone to avoid borrow issues
-                        match sequence.as_str() {
-                            _ => {}
-                        }
+                        self.handle_vim_sequence();
                     }
                     (ratatui::crossterm::event::KeyCode::Enter, _) => {
                         if self.view == View::Editor {
                             let (current_row, current_col) = self.textarea.cursor();
                             let line = self.textarea.lines()[current_row].clone();
-                            if self.extract_wikilink(&line, current_col).is_some() {
+                            if let Some((row, index)) = self.query_row.take() {
+                                if let Some((path, id)) = self
+                                    .query_results_at(row)
+                                    .and_then(|(_, targets)| targets.get(index).cloned())
+                                {
+                                    self.jump_to_file(path, id)?;
+                                }
+                            } else if self.extract_wikilink(&line, current_col).is_some() {
                                 self.follow_backlink(usize::MAX)?;
+                            } else if let Some((_, end, text)) =
+                                query::find_blocks(self.textarea.lines()).into_iter().find(
+                                    |(start, end, _)| current_row >= *start && current_row <= *end,
+                                )
+                            {
+                                // Results drawn below the block are picked in place
+                                if self
+                                    .query_results_at(end)
+                                    .is_some_and(|(_, targets)| !targets.is_empty())
+                                {
+                                    self.textarea.move_cursor(CursorMove::Jump(end as u16, 0));
+                                    self.query_row = Some((end, 0));
+                                    self.status =
+                                        "Enter opens the note, j/k pick another".to_string();
+                                } else {
+                                    self.open_query_report(&text)?;
+                                }
                             } else if let Some(tag) = self.extract_tag(&line, current_col) {
                                 //First load
                                 self.load_tag_files(&tag)?;
@@ -1965,8 +5152,12 @@ impl App {
                             self.image_full_screen = false;
                             self.status = "Image popup".to_string();
                             self.last_image_area = None; // Force protocol regen
-                        } else if !self.key_sequence.is_empty() {
+                        } else if self.preview_state.active {
+                            self.close_note_preview();
+                            self.status = "Normal".to_string();
+                        } else if !self.key_sequence.is_empty() || self.pending_register.is_some() {
                             self.key_sequence.clear();
+                            self.pending_register = None;
                             self.status = "Sequence cancelled".to_string();
                         }
                     }
@@ -1984,6 +5175,8 @@ impl App {
                 let input = Input::from(event);
                 match event.code {
                     ratatui::crossterm::event::KeyCode::Esc => {
+                        // Table mode: columns are aligned once typing in the cell is done
+                        self.realign_table();
                         self.mode = Mode::Normal;
                         self.status = "Normal".to_string();
                     }
@@ -2048,6 +5241,44 @@ impl App {
                             }
                         }
                     }
+                    ratatui::crossterm::event::KeyCode::Enter => {
+                        let (row, col) = self.textarea.cursor();
+                        let line = self.textarea.lines()[row].clone();
+                        match markdown::list_continuation(&line) {
+                            // Enter before the marker just splits the line
+                            ListContinuation::Continue(prefix, marker) if col >= marker => {
+                                self.textarea.insert_newline();
+                                self.textarea.insert_str(&prefix);
+                                self.push_group(false, 2);
+                            }
+                            ListContinuation::End if col >= line.chars().count() => {
+                                self.replace_lines(row, row + 1, &[String::new()]);
+                                self.textarea.move_cursor(CursorMove::Head);
+                            }
+                            _ => {
+                                self.textarea.input(input);
+                            }
+                        }
+                    }
+                    ratatui::crossterm::event::KeyCode::Tab
+                    | ratatui::crossterm::event::KeyCode::BackTab => {
+                        let (row, col) = self.textarea.cursor();
+                        let line = self.textarea.lines()[row].clone();
+                        let outdent = event.code == ratatui::crossterm::event::KeyCode::BackTab;
+                        if self.move_table_cell(!outdent) {
+                            // Moved between table cells
+                        } else if markdown::is_list_item(&line) {
+                            let (new_line, delta) = markdown::indent_list_item(&line, outdent);
+                            if delta != 0 {
+                                self.replace_lines(row, row + 1, &[new_line]);
+                                let col = (col as isize + delta).max(0) as usize;
+                                self.textarea
+                                    .move_cursor(CursorMove::Jump(row as u16, col as u16));
+                            }
+                        } else if !outdent {
+                            self.textarea.input(input);
+                        }
+                    }
                     _ => {
                         self.textarea.input(input);
                         if self.completion_state.active {
@@ -2063,21 +5294,179 @@ impl App {
                     self.status = "Normal".to_string();
                     self.command.clear();
                     self.prev_mode = None;
+                    self.command_range = None;
+                    self.command_selection = None;
                 }
                 ratatui::crossterm::event::KeyCode::Enter => {
+                    // `'<,'>` from Visual mode; without it the command is not limited to the selection
+                    match self.command.strip_prefix("'<,'>") {
+                        Some(rest) => self.command = rest.trim_start().to_string(),
+                        None => {
+                            self.command_range = None;
+                            self.command_selection = None;
+                        }
+                    }
                     if self.command == "w" {
                         self.save_file()?;
+                    } else if self.command == "close"
+                        || (self.command == "q" || self.command == "q!") && self.windows.len() > 1
+                    {
+                        self.close_window()?;
+                    } else if self.command == "only" {
+                        self.only_window();
+                    } else if let Some((command, arg)) =
+                        ["sp", "split", "vsp", "vsplit"].iter().find_map(|name| {
+                            let rest = self.command.strip_prefix(name)?;
+                            (rest.is_empty() || rest.starts_with(' ')).then_some((*name, rest))
+                        })
+                    {
+                        let direction = if command.starts_with('v') {
+                            Direction::Horizontal
+                        } else {
+                            Direction::Vertical
+                        };
+                        let arg = arg.trim().to_string();
+                        self.split_window(direction, &arg)?;
                     } else if self.command == "q" {
+                        let current = self.buffer_index();
+                        let modified = self.is_modified()
+                            || self
+                                .buffers
+                                .iter()
+                                .enumerate()
+                                .any(|(i, b)| i != current && b.is_modified());
+                        if modified {
+                            self.status =
+                                "Unsaved changes in open buffers (add ! to override)".to_string();
+                        } else {
+                            self.should_quit = true;
+                        }
+                    } else if self.command == "q!" {
                         self.should_quit = true;
+                    } else if self.command == "ls" || self.command == "buffers" {
+                        self.open_buffers_report();
+                    } else if self.command == "bn" || self.command == "bnext" {
+                        self.cycle_buffer(true)?;
+                    } else if self.command == "bp" || self.command == "bprevious" {
+                        self.cycle_buffer(false)?;
+                    } else if self.command == "bd" || self.command == "bd!" {
+                        self.delete_buffer(self.command.ends_with('!'))?;
+                    } else if let Some(n) = self
+                        .command
+                        .strip_prefix("b ")
+                        .and_then(|n| n.trim().parse::<usize>().ok())
+                    {
+                        self.switch_buffer(n.saturating_sub(1))?;
+                    } else if self.command == "e"
+                        || self.command == "e!"
+                        || self.command.starts_with("e ")
+                        || self.command.starts_with("e! ")
+                        || self.command.starts_with("edit ")
+                    {
+                        let force = self.command.starts_with("e!");
+                        let arg = self
+                            .command
+                            .trim_start_matches("edit")
+                            .trim_start_matches('e')
+                            .trim_start_matches('!')
+                            .trim()
+                            .to_string();
+                        self.edit_path(&arg, force)?;
                     } else if self.command == "wq" {
                         self.save_file()?;
-                        self.should_quit = true;
+                        if self.windows.len() > 1 {
+                            self.close_window()?;
+                        } else {
+                            self.should_quit = true;
+                        }
                     } else if self.command.starts_with("rename ") {
                         let new_name = self.command.trim_start_matches("rename ").to_string();
                         self.rename_selected(new_name)?;
                     } else if self.command.starts_with("new ") {
                         let name = self.command.trim_start_matches("new ").to_string();
                         self.create_new_file(name)?;
+                    } else if self.command == "graph" || self.command.starts_with("graph ") {
+                        let depth = self
+                            .command
+                            .trim_start_matches("graph")
+                            .trim()
+                            .parse::<usize>()
+                            .unwrap_or(self.report_state.graph_depth);
+                        self.open_graph(depth)?;
+                    } else if self.command.starts_with("graphexport ") {
+                        let target = self
+                            .command
+                            .trim_start_matches("graphexport ")
+                            .trim()
+                            .to_string();
+                        self.export_graph(&target)?;
+                    } else if self.command == "registers" || self.command == "reg" {
+                        self.open_registers_report();
+                    } else if self.command == "health" {
+                        self.open_health_report()?;
+                    } else if self.command == "csv2table" {
+                        self.convert_table(false);
+                    } else if self.command == "table2csv" {
+                        self.convert_table(true);
+                    } else if self.command == "donedate" {
+                        self.task_done_date = !self.task_done_date;
+                        self.status = if self.task_done_date {
+                            "Completed tasks get a done date".to_string()
+                        } else {
+                            "No done date on completed tasks".to_string()
+                        };
+                    } else if self.command == "undofile" {
+                        self.undofile = !self.undofile;
+                        if self.undofile {
+                            self.write_undofile()?;
+                        }
+                        self.save_session();
+                        self.status = if self.undofile {
+                            "Undo history is saved to disk".to_string()
+                        } else {
+                            "Undo history is kept in memory only".to_string()
+                        };
+                    } else if let Some(arg) = self
+                        .command
+                        .strip_prefix("earlier")
+                        .or_else(|| self.command.strip_prefix("ea"))
+                    {
+                        let arg = arg.to_string();
+                        self.time_travel(&arg, false);
+                    } else if let Some(arg) = self
+                        .command
+                        .strip_prefix("later")
+                        .or_else(|| self.command.strip_prefix("lat"))
+                    {
+                        let arg = arg.to_string();
+                        self.time_travel(&arg, true);
+                    } else if self.command == "tasks" {
+                        self.open_tasks_report()?;
+                    } else if self.command.starts_with("extract ")
+                        || self.command.starts_with("extract! ")
+                    {
+                        let use_template = self.command.starts_with("extract!");
+                        let title = self
+                            .command
+                            .trim_start_matches("extract")
+                            .trim_start_matches('!')
+                            .to_string();
+                        self.extract_selection(&title, use_template)?;
+                    } else if self.command == "splitnote" || self.command.starts_with("splitnote ")
+                    {
+                        let level = self.command.trim_start_matches("splitnote").to_string();
+                        self.split_note(&level)?;
+                    } else if self.command.starts_with("merge ") {
+                        let name = self.command.trim_start_matches("merge ").to_string();
+                        self.merge_selected(&name)?;
+                        if self.prev_mode == Some(Mode::FileTreeVisual) {
+                            self.prev_mode = Some(Mode::FileTree);
+                        }
+                    } else if self.command == "props" {
+                        self.open_properties()?;
+                    } else if self.command == "table" || self.command.starts_with("table ") {
+                        let args = self.command.trim_start_matches("table").trim().to_string();
+                        self.open_table(&args)?;
                     } else if self.command.starts_with("echo ") {
                         let message = self.command.trim_start_matches("echo ").to_string();
                         self.echo(&message)?;
@@ -2091,8 +5480,12 @@ impl App {
                     } else {
                         self.status = format!("Unknown command: {}", self.command);
                     }
-                    let mode = self.prev_mode.unwrap_or(Mode::Normal);
-                    self.mode = mode;
+                    self.command_selection = None;
+                    // Commands like :graph switch to their own mode
+                    if self.mode == Mode::Command {
+                        let mode = self.prev_mode.unwrap_or(Mode::Normal);
+                        self.mode = mode;
+                    }
                     self.command.clear();
                     self.prev_mode = None;
                 }
@@ -2203,9 +5596,97 @@ impl App {
                 }
                 _ => {}
             },
-            Mode::Visual | Mode::VisualBlock => {
+            Mode::Visual | Mode::VisualLine | Mode::VisualBlock => {
                 let mut input = Input::from(event);
+                if let Some(anchor) = self.visual_anchor {
+                    self.last_visual = Some((self.mode, (anchor, self.textarea.cursor())));
+                }
+                if self.key_sequence == "\\" {
+                    self.key_sequence.clear();
+                    match input.key {
+                        Key::Char('x') => self.edit_task_lines(false),
+                        Key::Char('s') => self.edit_task_lines(true),
+                        _ => self.status = "Invalid sequence".to_string(),
+                    }
+                    return Ok(());
+                }
+                if self.key_sequence == "\"" {
+                    self.key_sequence.clear();
+                    match input.key {
+                        Key::Char(name) if registers::is_valid(name) => {
+                            self.pending_register = Some(name);
+                            self.status = format!("\"{}", name);
+                        }
+                        _ => self.status = "Invalid register".to_string(),
+                    }
+                    return Ok(());
+                }
+                if self.key_sequence == "i" || self.key_sequence == "a" {
+                    let inner = self.key_sequence == "i";
+                    self.key_sequence.clear();
+                    if let Key::Char(object) = input.key {
+                        self.select_text_object(inner, object);
+                    }
+                    return Ok(());
+                }
                 match input.key {
+                    Key::Char(key @ ('i' | 'a')) if self.mode == Mode::Visual => {
+                        self.key_sequence = key.to_string();
+                    }
+                    Key::Char('\\') => {
+                        self.key_sequence = "\\".to_string();
+                    }
+                    Key::Char('"') => {
+                        self.key_sequence = "\"".to_string();
+                    }
+                    Key::Char(':') => {
+                        // Commands like :csv2table work on the selected rows
+                        let row = self.textarea.cursor().0;
+                        let anchor = self.visual_anchor.map_or(row, |a| a.0);
+                        let (start, end) = (anchor.min(row), anchor.max(row));
+                        self.command_range = Some((start, end));
+                        // `V` keeps no textarea selection; it covers the whole lines
+                        self.command_selection = if self.mode == Mode::VisualLine {
+                            let end_col = self.textarea.lines()[end].chars().count();
+                            Some(((start, 0), (end, end_col)))
+                        } else {
+                            self.textarea.selection_range()
+                        };
+                        self.textarea.cancel_selection();
+                        self.visual_anchor = None;
+                        self.prev_mode = Some(Mode::Normal);
+                        self.mode = Mode::Command;
+                        self.command = "'<,'>".to_string();
+                        self.status = "Command".to_string();
+                    }
+                    Key::Char('o') => self.swap_visual_ends(),
+                    Key::Char(mode @ ('v' | 'V')) => {
+                        let mode = if mode == 'v' {
+                            Mode::Visual
+                        } else {
+                            Mode::VisualLine
+                        };
+                        if self.mode == mode {
+                            self.textarea.cancel_selection();
+                            self.visual_anchor = None;
+                            self.mode = Mode::Normal;
+                            self.status = "Normal".to_string();
+                        } else {
+                            self.mode = mode;
+                            self.status = visual_status(mode).to_string();
+                        }
+                    }
+                    Key::Char(key @ ('>' | '<' | '~' | 'u' | 'U')) => {
+                        let operator = match key {
+                            '>' => Operator::Indent,
+                            '<' => Operator::Outdent,
+                            '~' => Operator::ToggleCase,
+                            'u' => Operator::Lower,
+                            _ => Operator::Upper,
+                        };
+                        self.visual_operator(operator);
+                    }
+                    Key::Char('J') => self.visual_join(),
                     Key::Esc => {
                         self.textarea.cancel_selection();
                         self.visual_anchor = None;
@@ -2213,86 +5694,35 @@ impl App {
                         self.status = "Normal".to_string();
                     }
                     Key::Char('y') => {
-                        let (min_row, min_col, max_row, max_col) =
-                            if let Some(anchor) = self.visual_anchor {
-                                let cursor = self.textarea.cursor();
-                                (
-                                    anchor.0.min(cursor.0),
-                                    anchor.1.min(cursor.1),
-                                    anchor.0.max(cursor.0),
-                                    anchor.1.max(cursor.1),
-                                )
-                            } else {
-                                return Ok(());
-                            };
-                        if self.mode == Mode::VisualBlock {
-                            self.yanked = (min_row..=max_row)
-                                .map(|row| {
-                                    let line = &self.textarea.lines()[row];
-                                    let start_byte = line
-                                        .char_indices()
-                                        .nth(min_col)
-                                        .map(|(b, _)| b)
-                                        .unwrap_or(line.len());
-                                    let end_byte = line
-                                        .char_indices()
-                                        .nth(max_col + 1)
-                                        .map(|(b, _)| b)
-                                        .unwrap_or(line.len());
-                                    line[start_byte..end_byte].to_string()
-                                })
-                                .collect();
+                        let text = self.visual_text();
+                        let linewise = self.mode == Mode::VisualLine;
+                        self.status = if linewise {
+                            format!("Yanked {} lines", text.len())
                         } else {
-                            if let Some(range) = self.textarea.selection_range() {
-                                let start_row = range.0.0;
-                                let start_col = range.0.1;
-                                let end_row = range.1.0;
-                                let end_col = range.1.1;
-                                if start_row == end_row {
-                                    let line = self.textarea.lines()[start_row].clone();
-                                    let start_byte = line
-                                        .char_indices()
-                                        .nth(start_col)
-                                        .map(|(b, _)| b)
-                                        .unwrap_or(0);
-                                    let end_byte = line
-                                        .char_indices()
-                                        .nth(end_col)
-                                        .map(|(b, _)| b)
-                                        .unwrap_or(line.len());
-                                    self.yanked = vec![line[start_byte..end_byte].to_string()];
-                                } else {
-                                    let mut yanked = Vec::new();
-                                    for row in start_row..=end_row {
-                                        let line = self.textarea.lines()[row].clone();
-                                        if row == start_row {
-                                            let start_byte = line
-                                                .char_indices()
-                                                .nth(start_col)
-                                                .map(|(b, _)| b)
-                                                .unwrap_or(0);
-                                            yanked.push(line[start_byte..].to_string());
-                                        } else if row == end_row {
-                                            let end_byte = line
-                                                .char_indices()
-                                                .nth(end_col)
-                                                .map(|(b, _)| b)
-                                                .unwrap_or(line.len());
-                                            yanked.push(line[..end_byte].to_string());
-                                        } else {
-                                            yanked.push(line);
-                                        }
-                                    }
-                                    self.yanked = yanked;
-                                }
-                            }
-                        }
+                            "Yanked".to_string()
+                        };
+                        self.store_register(text, linewise, false);
+                        self.textarea.cancel_selection();
+                        self.visual_anchor = None;
+                        self.mode = Mode::Normal;
+                    }
+                    Key::Char('x') | Key::Char('d') if self.mode == Mode::VisualLine => {
+                        let text = self.visual_text();
+                        self.store_register(text, true, true);
+                        self.change_made = true;
+                        let (start, end) = self.visual_rows();
                         self.textarea.cancel_selection();
+                        self.replace_lines(start, end + 1, &[]);
+                        let row = start.min(self.textarea.lines().len() - 1);
+                        self.textarea.move_cursor(CursorMove::Jump(row as u16, 0));
                         self.visual_anchor = None;
                         self.mode = Mode::Normal;
-                        self.status = "Yanked (not undoable)".to_string();
+                        self.status = format!("Deleted {} lines", end - start + 1);
                     }
                     Key::Char('x') | Key::Char('d') => {
+                        let text = self.visual_text();
+                        self.store_register(text, false, true);
+                        self.change_made = true;
                         let (min_row, min_col, max_row, max_col) =
                             if let Some(anchor) = self.visual_anchor {
                                 let cursor = self.textarea.cursor();
@@ -2370,18 +5800,7 @@ impl App {
                                     new_lines.drain((start_row + 1)..=end_row);
                                 }
                                 self.textarea = TextArea::new(new_lines);
-                                self.textarea.set_block(
-                                    Block::default()
-                                        .borders(Borders::ALL)
-                                        .title("Midetor")
-                                        .style(Style::default().fg(Color::White)),
-                                );
-                                self.textarea.set_cursor_line_style(Style::default());
-                                self.textarea.set_cursor_style(
-                                    Style::default().bg(Color::White).fg(Color::Black),
-                                );
-                                self.textarea
-                                    .set_selection_style(Style::default().bg(Color::LightBlue));
+                                set_textarea_delafult_style!(self.textarea);
                                 self.textarea.move_cursor(CursorMove::Jump(
                                     start_row as u16,
                                     start_col as u16,
@@ -2592,300 +6011,1644 @@ impl App {
                             }
                         }
                     }
-                    ratatui::crossterm::event::KeyCode::Right => {
-                        if let Some(selected) = self.tree_state.selected() {
-                            let item = &self.visible_items[selected];
-                            if item.is_dir {
-                                self.expand_dir(selected)?;
-                            }
-                        }
+                    ratatui::crossterm::event::KeyCode::Right => {
+                        if let Some(selected) = self.tree_state.selected() {
+                            let item = &self.visible_items[selected];
+                            if item.is_dir {
+                                self.expand_dir(selected)?;
+                            }
+                        }
+                    }
+                    ratatui::crossterm::event::KeyCode::Enter => {
+                        if let Some(selected) = self.tree_state.selected() {
+                            let item = self.visible_items[selected].clone();
+                            if item.is_dir {
+                                self.toggle_expand_dir(selected)?;
+                            } else {
+                                self.open_wikilink_file(item.path)?;
+                                self.mode = Mode::Normal;
+                            }
+                        }
+                    }
+                    ratatui::crossterm::event::KeyCode::Char('v') => {
+                        if let Some(selected) = self.tree_state.selected() {
+                            self.tree_visual_anchor = Some(selected);
+                            self.mode = Mode::FileTreeVisual;
+                            self.status = "Visual".to_string();
+                        }
+                    }
+                    ratatui::crossterm::event::KeyCode::Char('d') => {
+                        self.delete_selected_file()?;
+                    }
+                    ratatui::crossterm::event::KeyCode::Char('r') => {
+                        self.prev_mode = Some(Mode::FileTree);
+                        self.mode = Mode::Command;
+                        self.command = "rename ".to_string();
+                        self.status = "Rename to:".to_string();
+                    }
+                    ratatui::crossterm::event::KeyCode::Char('n') => {
+                        self.prev_mode = Some(Mode::FileTree);
+                        self.mode = Mode::Command;
+                        self.command = "new ".to_string();
+                        self.status = "New file name:".to_string();
+                    }
+                    ratatui::crossterm::event::KeyCode::Char('y') => {
+                        self.copy_selected();
+                    }
+                    ratatui::crossterm::event::KeyCode::Char('x') => {
+                        self.cut_selected();
+                    }
+                    ratatui::crossterm::event::KeyCode::Char('p') => {
+                        self.paste_buffer()?;
+                    }
+                    ratatui::crossterm::event::KeyCode::Char('<') => {
+                        if !self.full_tree && self.tree_width_percent > 10 {
+                            self.tree_width_percent -= 5;
+                        }
+                    }
+                    ratatui::crossterm::event::KeyCode::Char('>') => {
+                        if !self.full_tree && self.tree_width_percent < 50 {
+                            self.tree_width_percent += 5;
+                        }
+                    }
+                    ratatui::crossterm::event::KeyCode::Char('f') => {
+                        self.full_tree = !self.full_tree;
+                        self.status = if self.full_tree {
+                            "Full-screen FileTree"
+                        } else {
+                            "Split FileTree"
+                        }
+                        .to_string();
+                    }
+                    ratatui::crossterm::event::KeyCode::Char(c) => {
+                        self.key_sequence.push(c);
+                    }
+                    _ => {}
+                }
+            }
+            Mode::Report => match event.code {
+                ratatui::crossterm::event::KeyCode::Esc
+                | ratatui::crossterm::event::KeyCode::Char('q') => {
+                    self.cancel_report();
+                }
+                ratatui::crossterm::event::KeyCode::Enter => {
+                    self.select_report_line()?;
+                }
+                ratatui::crossterm::event::KeyCode::Up
+                | ratatui::crossterm::event::KeyCode::Char('k') => {
+                    let selected = self.report_state.list_state.selected().unwrap_or(0);
+                    if selected > 0 {
+                        self.report_state.list_state.select(Some(selected - 1));
+                    }
+                }
+                ratatui::crossterm::event::KeyCode::Down
+                | ratatui::crossterm::event::KeyCode::Char('j') => {
+                    let selected = self.report_state.list_state.selected().unwrap_or(0);
+                    if selected + 1 < self.report_state.lines.len() {
+                        self.report_state.list_state.select(Some(selected + 1));
+                    }
+                }
+                ratatui::crossterm::event::KeyCode::Char('x')
+                | ratatui::crossterm::event::KeyCode::Char(' ')
+                    if self.report_state.report_type == ReportType::Tasks =>
+                {
+                    self.toggle_report_task()?;
+                }
+                ratatui::crossterm::event::KeyCode::Char('r')
+                    if self.report_state.report_type == ReportType::Tasks =>
+                {
+                    self.refresh_tasks()?;
+                }
+                ratatui::crossterm::event::KeyCode::Char('+')
+                    if self.report_state.report_type == ReportType::Graph =>
+                {
+                    self.open_graph(self.report_state.graph_depth + 1)?;
+                }
+                ratatui::crossterm::event::KeyCode::Char('-')
+                    if self.report_state.report_type == ReportType::Graph =>
+                {
+                    self.open_graph(self.report_state.graph_depth.saturating_sub(1).max(1))?;
+                }
+                _ => {}
+            },
+            Mode::Properties if self.props_state.editing != PropsField::None => match event.code {
+                ratatui::crossterm::event::KeyCode::Esc => {
+                    self.props_state.editing = PropsField::None;
+                    self.props_state.adding = false;
+                    self.props_state.input.clear();
+                    self.status = "Properties".to_string();
+                }
+                ratatui::crossterm::event::KeyCode::Enter => {
+                    self.commit_property_input();
+                }
+                ratatui::crossterm::event::KeyCode::Backspace => {
+                    self.props_state.input.pop();
+                    self.props_state.key_state.select(None);
+                }
+                ratatui::crossterm::event::KeyCode::Up
+                    if self.props_state.editing == PropsField::Key =>
+                {
+                    let selected = self.props_state.key_state.selected();
+                    self.props_state
+                        .key_state
+                        .select(selected.and_then(|i| i.checked_sub(1)));
+                }
+                ratatui::crossterm::event::KeyCode::Down
+                    if self.props_state.editing == PropsField::Key =>
+                {
+                    let count = self.property_key_suggestions().len();
+                    let next = self.props_state.key_state.selected().map_or(0, |i| i + 1);
+                    if next < count {
+                        self.props_state.key_state.select(Some(next));
+                    }
+                }
+                ratatui::crossterm::event::KeyCode::Tab
+                    if self.props_state.editing == PropsField::Key =>
+                {
+                    let index = self.props_state.key_state.selected().unwrap_or(0);
+                    if let Some((key, _)) = self.property_key_suggestions().get(index) {
+                        self.props_state.input = key.clone();
+                    }
+                    self.props_state.key_state.select(None);
+                }
+                ratatui::crossterm::event::KeyCode::Char(c) => {
+                    self.props_state.input.push(c);
+                    self.props_state.key_state.select(None);
+                }
+                _ => {}
+            },
+            Mode::Properties => match event.code {
+                ratatui::crossterm::event::KeyCode::Esc
+                | ratatui::crossterm::event::KeyCode::Char('q') => {
+                    self.close_properties();
+                }
+                ratatui::crossterm::event::KeyCode::Char('w') => {
+                    self.write_properties();
+                    self.status = "Properties written to buffer".to_string();
+                }
+                ratatui::crossterm::event::KeyCode::Up
+                | ratatui::crossterm::event::KeyCode::Char('k') => {
+                    let selected = self.props_state.list_state.selected().unwrap_or(0);
+                    if selected > 0 {
+                        self.props_state.list_state.select(Some(selected - 1));
+                    }
+                }
+                ratatui::crossterm::event::KeyCode::Down
+                | ratatui::crossterm::event::KeyCode::Char('j') => {
+                    let selected = self.props_state.list_state.selected().unwrap_or(0);
+                    if selected + 1 < self.props_state.properties.len() {
+                        self.props_state.list_state.select(Some(selected + 1));
                     }
-                    ratatui::crossterm::event::KeyCode::Enter => {
-                        if let Some(selected) = self.tree_state.selected() {
-                            let item = self.visible_items[selected].clone();
-                            if item.is_dir {
-                                self.toggle_expand_dir(selected)?;
-                            } else {
-                                self.open_wikilink_file(item.path)?;
-                                self.mode = Mode::Normal;
-                            }
-                        }
+                }
+                ratatui::crossterm::event::KeyCode::Enter
+                | ratatui::crossterm::event::KeyCode::Char('e') => {
+                    self.start_property_edit(PropsField::Value);
+                }
+                ratatui::crossterm::event::KeyCode::Char('r')
+                    if self.props_state.list_state.selected().is_some() =>
+                {
+                    self.start_property_edit(PropsField::Key);
+                }
+                ratatui::crossterm::event::KeyCode::Char('a') => {
+                    self.props_state.adding = true;
+                    self.start_property_edit(PropsField::Key);
+                }
+                ratatui::crossterm::event::KeyCode::Char('d') => {
+                    if let Some(i) = self.props_state.list_state.selected() {
+                        self.props_state.properties.remove(i);
+                        self.props_state.modified = true;
+                        let len = self.props_state.properties.len();
+                        self.props_state.list_state.select(if len == 0 {
+                            None
+                        } else {
+                            Some(i.min(len - 1))
+                        });
                     }
-                    ratatui::crossterm::event::KeyCode::Char('v') => {
-                        if let Some(selected) = self.tree_state.selected() {
-                            self.tree_visual_anchor = Some(selected);
-                            self.mode = Mode::FileTreeVisual;
-                            self.status = "Visual".to_string();
+                }
+                ratatui::crossterm::event::KeyCode::Char(' ')
+                | ratatui::crossterm::event::KeyCode::Char('x') => {
+                    let selected = self.props_state.list_state.selected();
+                    if let Some(Property {
+                        value: PropertyValue::Checkbox(checked),
+                        ..
+                    }) = selected.and_then(|i| self.props_state.properties.get_mut(i))
+                    {
+                        *checked = !*checked;
+                        self.props_state.modified = true;
+                    }
+                }
+                ratatui::crossterm::event::KeyCode::Char('t') => {
+                    if let Some(i) = self.props_state.list_state.selected() {
+                        let property = &mut self.props_state.properties[i];
+                        if matches!(property.value, PropertyValue::Raw(_)) {
+                            self.status = "Nested YAML, edit it in the buffer".to_string();
+                            return Ok(());
                         }
+                        property.value = property.value.next_type();
+                        self.status =
+                            format!("{} is now {}", property.key, property.value.type_name());
+                        self.props_state.modified = true;
                     }
-                    ratatui::crossterm::event::KeyCode::Char('d') => {
-                        self.delete_selected_file()?;
+                }
+                _ => {}
+            },
+            Mode::Outline => match event.code {
+                ratatui::crossterm::event::KeyCode::Esc => {
+                    self.mode = Mode::Normal;
+                    self.status = "Normal".to_string();
+                }
+                ratatui::crossterm::event::KeyCode::Char('q') => {
+                    self.outline_visible = false;
+                    self.mode = Mode::Normal;
+                    self.status = "Outline closed".to_string();
+                }
+                ratatui::crossterm::event::KeyCode::Up
+                | ratatui::crossterm::event::KeyCode::Char('k') => {
+                    let selected = self.outline_state.selected().unwrap_or(0);
+                    self.outline_state.select(Some(selected.saturating_sub(1)));
+                }
+                ratatui::crossterm::event::KeyCode::Down
+                | ratatui::crossterm::event::KeyCode::Char('j') => {
+                    let count = markdown::headings(self.textarea.lines()).len();
+                    let next = self.outline_state.selected().map_or(0, |i| i + 1);
+                    if next < count {
+                        self.outline_state.select(Some(next));
                     }
-                    ratatui::crossterm::event::KeyCode::Char('r') => {
-                        self.prev_mode = Some(Mode::FileTree);
-                        self.mode = Mode::Command;
-                        self.command = "rename ".to_string();
-                        self.status = "Rename to:".to_string();
+                }
+                ratatui::crossterm::event::KeyCode::Enter => {
+                    let headings = markdown::headings(self.textarea.lines());
+                    if let Some(heading) =
+                        self.outline_state.selected().and_then(|i| headings.get(i))
+                    {
+                        self.textarea
+                            .move_cursor(CursorMove::Jump(heading.line as u16, 0));
                     }
-                    ratatui::crossterm::event::KeyCode::Char('n') => {
-                        self.prev_mode = Some(Mode::FileTree);
-                        self.mode = Mode::Command;
-                        self.command = "new ".to_string();
-                        self.status = "New file name:".to_string();
+                    self.mode = Mode::Normal;
+                    self.status = "Normal".to_string();
+                }
+                _ => {}
+            },
+            Mode::Table if self.table_state.editing != TableInput::None => match event.code {
+                ratatui::crossterm::event::KeyCode::Esc => {
+                    self.table_state.editing = TableInput::None;
+                    self.table_state.input.clear();
+                }
+                ratatui::crossterm::event::KeyCode::Enter => {
+                    if self.table_state.editing == TableInput::Filter {
+                        let column = self.table_state.column;
+                        self.table_state.filters[column] =
+                            self.table_state.input.trim().to_string();
+                        self.table_state.editing = TableInput::None;
+                        self.table_state.input.clear();
+                        self.refresh_table_rows();
+                        self.status = format!(
+                            "{} of {} notes",
+                            self.table_state.rows.len(),
+                            self.table_state.records.len()
+                        );
+                    } else {
+                        self.commit_table_cell()?;
                     }
-                    ratatui::crossterm::event::KeyCode::Char('y') => {
-                        self.copy_selected();
+                }
+                ratatui::crossterm::event::KeyCode::Backspace => {
+                    self.table_state.input.pop();
+                }
+                ratatui::crossterm::event::KeyCode::Char(c) => {
+                    self.table_state.input.push(c);
+                }
+                _ => {}
+            },
+            Mode::Table => match event.code {
+                ratatui::crossterm::event::KeyCode::Esc
+                | ratatui::crossterm::event::KeyCode::Char('q') => {
+                    self.mode = Mode::Normal;
+                    self.status = "Normal".to_string();
+                }
+                ratatui::crossterm::event::KeyCode::Up
+                | ratatui::crossterm::event::KeyCode::Char('k') => {
+                    let selected = self.table_state.list_state.selected().unwrap_or(0);
+                    if selected > 0 {
+                        self.table_state.list_state.select(Some(selected - 1));
                     }
-                    ratatui::crossterm::event::KeyCode::Char('x') => {
-                        self.cut_selected();
+                }
+                ratatui::crossterm::event::KeyCode::Down
+                | ratatui::crossterm::event::KeyCode::Char('j') => {
+                    let selected = self.table_state.list_state.selected().unwrap_or(0);
+                    if selected + 1 < self.table_state.rows.len() {
+                        self.table_state.list_state.select(Some(selected + 1));
                     }
-                    ratatui::crossterm::event::KeyCode::Char('p') => {
-                        self.paste_buffer()?;
+                }
+                ratatui::crossterm::event::KeyCode::Left
+                | ratatui::crossterm::event::KeyCode::Char('h') => {
+                    self.table_state.column = self.table_state.column.saturating_sub(1);
+                }
+                ratatui::crossterm::event::KeyCode::Right
+                | ratatui::crossterm::event::KeyCode::Char('l')
+                    if self.table_state.column + 1 < self.table_state.columns.len() =>
+                {
+                    self.table_state.column += 1;
+                }
+                ratatui::crossterm::event::KeyCode::Char('s') => {
+                    // Ascending, descending, unsorted
+                    let column = self.table_state.column;
+                    self.table_state.sort = match self.table_state.sort {
+                        Some((c, false)) if c == column => Some((column, true)),
+                        Some((c, true)) if c == column => None,
+                        _ => Some((column, false)),
+                    };
+                    self.refresh_table_rows();
+                }
+                ratatui::crossterm::event::KeyCode::Char('/') => {
+                    self.table_state.input =
+                        self.table_state.filters[self.table_state.column].clone();
+                    self.table_state.editing = TableInput::Filter;
+                }
+                ratatui::crossterm::event::KeyCode::Char('c') => {
+                    self.table_state.filters.iter_mut().for_each(|f| f.clear());
+                    self.refresh_table_rows();
+                    self.status = "Filters cleared".to_string();
+                }
+                ratatui::crossterm::event::KeyCode::Enter
+                | ratatui::crossterm::event::KeyCode::Char('e') => {
+                    if self.table_state.column == 0 {
+                        self.status = "The name column is not editable".to_string();
+                    } else if let Some(&index) = self
+                        .table_state
+                        .list_state
+                        .selected()
+                        .and_then(|i| self.table_state.rows.get(i))
+                    {
+                        let record = &self.table_state.records[index];
+                        self.table_state.input = self.table_cell(record, self.table_state.column);
+                        self.table_state.editing = TableInput::Cell;
                     }
-                    ratatui::crossterm::event::KeyCode::Char('<') => {
-                        if !self.full_tree && self.tree_width_percent > 10 {
-                            self.tree_width_percent -= 5;
-                        }
+                }
+                ratatui::crossterm::event::KeyCode::Char('o') => {
+                    self.open_table_note()?;
+                }
+                _ => {}
+            },
+            Mode::FileTreeVisual => match event.code {
+                ratatui::crossterm::event::KeyCode::Esc => {
+                    self.tree_visual_anchor = None;
+                    self.mode = Mode::FileTree;
+                    self.status = "File Tree".to_string();
+                }
+                ratatui::crossterm::event::KeyCode::Up => {
+                    let selected = self.tree_state.selected().unwrap_or(0);
+                    if selected > 0 {
+                        self.tree_state.select(Some(selected - 1));
                     }
-                    ratatui::crossterm::event::KeyCode::Char('>') => {
-                        if !self.full_tree && self.tree_width_percent < 50 {
-                            self.tree_width_percent += 5;
-                        }
+                }
+                ratatui::crossterm::event::KeyCode::Down => {
+                    let selected = self.tree_state.selected().unwrap_or(0);
+                    if selected < self.visible_items.len() - 1 {
+                        self.tree_state.select(Some(selected + 1));
                     }
-                    ratatui::crossterm::event::KeyCode::Char('f') => {
-                        self.full_tree = !self.full_tree;
-                        self.status = if self.full_tree {
-                            "Full-screen FileTree"
+                }
+                ratatui::crossterm::event::KeyCode::Char('d') => {
+                    self.delete_selected_files()?;
+                    self.tree_visual_anchor = None;
+                    self.mode = Mode::FileTree;
+                }
+                ratatui::crossterm::event::KeyCode::Char('y') => {
+                    self.copy_selected();
+                    self.tree_visual_anchor = None;
+                    self.mode = Mode::FileTree;
+                }
+                ratatui::crossterm::event::KeyCode::Char('x') => {
+                    self.cut_selected();
+                    self.tree_visual_anchor = None;
+                    self.mode = Mode::FileTree;
+                }
+                ratatui::crossterm::event::KeyCode::Char('m') => {
+                    self.prev_mode = Some(Mode::FileTreeVisual);
+                    self.mode = Mode::Command;
+                    self.command = "merge ".to_string();
+                    self.status = "Merge into:".to_string();
+                }
+                ratatui::crossterm::event::KeyCode::Char('r') => {
+                    let current = self.tree_state.selected().unwrap_or(0);
+                    let anchor = self.tree_visual_anchor.unwrap_or(current);
+                    if anchor == current {
+                        self.prev_mode = Some(Mode::FileTreeVisual);
+                        self.mode = Mode::Command;
+                        self.command = "rename ".to_string();
+                        self.status = "Rename to:".to_string();
+                    } else {
+                        self.status = "Rename only for single file".to_string();
+                    }
+                }
+                _ => {}
+            },
+        }
+        Ok(())
+    }
+
+    pub fn render(
+        &mut self,
+        terminal: &mut Terminal<CrosstermBackend<io::Stdout>>,
+    ) -> Result<(), EditorError> {
+        terminal.draw(|f| {
+            if let Err(e) = self.draw(f) {
+                self.status = format!("Render error: {}", e);
+            }
+        })?;
+        Ok(())
+    }
+
+    fn draw(&mut self, f: &mut Frame) -> Result<(), EditorError> {
+        let chunks = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints([
+                Constraint::Min(1),    // Editor, info, or list area
+                Constraint::Length(1), // Status line
+                Constraint::Length(1), // Command line or key sequence
+            ])
+            .split(f.area());
+
+        match self.mode {
+            Mode::Search => {
+                // Render search input field or results list
+                let title = match self.search_state.search_type {
+                    SearchType::Backlinks => format!("Backlinks: {}", self.search_state.query),
+                    SearchType::Tags => format!("Tags: {}", self.search_state.query),
+                    SearchType::Files => format!("Files: {}", self.search_state.query),
+                    SearchType::None => "Search".to_string(),
+                };
+                if self.search_state.results.is_empty() && self.search_state.query.is_empty() {
+                    // Render input field for search
+                    let input = Paragraph::new(self.search_state.query.clone())
+                        .block(
+                            Block::default()
+                                .borders(Borders::ALL)
+                                .title(title)
+                                .style(Style::default().fg(Color::White)),
+                        )
+                        .style(Style::default().fg(Color::Yellow));
+                    f.render_widget(input, chunks[0]);
+                } else {
+                    // Render search results
+                    let items: Vec<ListItem> = self
+                        .search_state
+                        .results
+                        .iter()
+                        .map(|(text, _)| ListItem::new(text.clone()))
+                        .collect();
+                    let list = List::new(items)
+                        .block(
+                            Block::default()
+                                .borders(Borders::ALL)
+                                .title(title)
+                                .style(Style::default().fg(Color::White)),
+                        )
+                        .highlight_style(Style::default().bg(Color::White).fg(Color::Black));
+                    f.render_stateful_widget(list, chunks[0], &mut self.search_state.list_state);
+                }
+            }
+            Mode::TagFiles => {
+                let items: Vec<ListItem> = self
+                    .tag_files
+                    .iter()
+                    .map(|(file_name, _)| ListItem::new(file_name.clone()))
+                    .collect();
+                let list = List::new(items)
+                    .block(
+                        Block::default()
+                            .borders(Borders::ALL)
+                            .title("Files for Tag")
+                            .style(Style::default().fg(Color::White)),
+                    )
+                    .highlight_style(Style::default().bg(Color::White).fg(Color::Black));
+                f.render_stateful_widget(list, chunks[0], &mut self.tag_files_state);
+            }
+            Mode::Report => {
+                let items: Vec<ListItem> = self
+                    .report_state
+                    .lines
+                    .iter()
+                    .map(|line| {
+                        let style = if line.target.is_some() {
+                            Style::default().fg(Color::White)
+                        } else {
+                            Style::default().fg(Color::LightBlue)
+                        };
+                        ListItem::new(line.text.clone()).style(style)
+                    })
+                    .collect();
+                let list = List::new(items)
+                    .block(
+                        Block::default()
+                            .borders(Borders::ALL)
+                            .title(self.report_state.title.clone())
+                            .style(Style::default().fg(Color::White)),
+                    )
+                    .highlight_style(Style::default().bg(Color::White).fg(Color::Black));
+                f.render_stateful_widget(list, chunks[0], &mut self.report_state.list_state);
+            }
+            Mode::FileTree | Mode::FileTreeVisual => {
+                let tree_constraint = if self.full_tree {
+                    Constraint::Percentage(100)
+                } else {
+                    Constraint::Percentage(self.tree_width_percent)
+                };
+                let editor_constraint = if self.full_tree {
+                    Constraint::Length(0)
+                } else {
+                    Constraint::Percentage(100 - self.tree_width_percent)
+                };
+                let main_chunks = Layout::default()
+                    .direction(Direction::Horizontal)
+                    .constraints([tree_constraint, editor_constraint])
+                    .split(chunks[0]);
+
+                let visual_min = self
+                    .tree_visual_anchor
+                    .map(|a| a.min(self.tree_state.selected().unwrap_or(0)))
+                    .unwrap_or(usize::MAX);
+                let visual_max = self
+                    .tree_visual_anchor
+                    .map(|a| a.max(self.tree_state.selected().unwrap_or(0)))
+                    .unwrap_or(0);
+
+                let items: Vec<ListItem> = self
+                    .visible_items
+                    .iter()
+                    .enumerate()
+                    .map(|(i, item)| {
+                        let base_style = if item.is_dir {
+                            Style::default().fg(Color::LightBlue)
                         } else {
-                            "Split FileTree"
+                            Style::default().fg(Color::White)
+                        };
+                        let mut li = ListItem::new(item.display.clone()).style(base_style);
+                        if self.mode == Mode::FileTreeVisual && i >= visual_min && i <= visual_max {
+                            li = li.style(Style::default().bg(Color::LightBlue));
+                        } else if Some(i) == self.tree_state.selected() {
+                            li = li.style(Style::default().bg(Color::White).fg(Color::Black));
                         }
-                        .to_string();
-                    }
-                    ratatui::crossterm::event::KeyCode::Char(c) => {
-                        self.key_sequence.push(c);
-                    }
-                    _ => {}
+                        li
+                    })
+                    .collect();
+
+                let list = List::new(items).block(
+                    Block::default()
+                        .borders(Borders::ALL)
+                        .title("File Tree")
+                        .style(Style::default().fg(Color::White)),
+                );
+                f.render_stateful_widget(list, main_chunks[0], &mut self.tree_state);
+
+                if !self.full_tree {
+                    self.render_editor(f, main_chunks[1])?;
                 }
             }
-            Mode::FileTreeVisual => match event.code {
-                ratatui::crossterm::event::KeyCode::Esc => {
-                    self.tree_visual_anchor = None;
-                    self.mode = Mode::FileTree;
-                    self.status = "File Tree".to_string();
+            Mode::Table => {
+                self.render_table(f, chunks[0]);
+            }
+            Mode::Properties => {
+                let main_chunks = Layout::default()
+                    .direction(Direction::Horizontal)
+                    .constraints([Constraint::Percentage(60), Constraint::Percentage(40)])
+                    .split(chunks[0]);
+                self.render_editor(f, main_chunks[0])?;
+                self.render_properties(f, main_chunks[1]);
+            }
+            Mode::Normal
+            | Mode::Insert
+            | Mode::Complete
+            | Mode::Command
+            | Mode::Visual
+            | Mode::VisualLine
+            | Mode::VisualBlock
+            | Mode::BlockInsert
+            | Mode::Outline => match self.view {
+                View::Editor if self.outline_visible => {
+                    let main_chunks = Layout::default()
+                        .direction(Direction::Horizontal)
+                        .constraints([Constraint::Percentage(75), Constraint::Percentage(25)])
+                        .split(chunks[0]);
+                    self.render_editor(f, main_chunks[0])?;
+                    self.render_outline(f, main_chunks[1]);
                 }
-                ratatui::crossterm::event::KeyCode::Up => {
-                    let selected = self.tree_state.selected().unwrap_or(0);
-                    if selected > 0 {
-                        self.tree_state.select(Some(selected - 1));
-                    }
+                View::Editor => {
+                    self.render_editor(f, chunks[0])?;
                 }
-                ratatui::crossterm::event::KeyCode::Down => {
-                    let selected = self.tree_state.selected().unwrap_or(0);
-                    if selected < self.visible_items.len() - 1 {
-                        self.tree_state.select(Some(selected + 1));
-                    }
+                View::Info => {
+                    let info = Paragraph::new(self.status.clone())
+                        .block(Block::default().borders(Borders::ALL).title("Info"))
+                        .style(Style::default().fg(Color::White));
+                    f.render_widget(info, chunks[0]);
                 }
-                ratatui::crossterm::event::KeyCode::Char('d') => {
-                    self.delete_selected_files()?;
-                    self.tree_visual_anchor = None;
-                    self.mode = Mode::FileTree;
+            },
+        }
+
+        let recording = match &self.recording {
+            Some((name, _)) => format!("recording @{} ", name.to_ascii_lowercase()),
+            None => String::new(),
+        };
+        let status = Paragraph::new(format!("{}-- {} --", recording, self.status))
+            .style(Style::default().fg(Color::Yellow));
+        f.render_widget(status, chunks[1]);
+
+        let command = Paragraph::new(match self.mode {
+            Mode::Command => format!(":{}", self.command),
+            Mode::Search => format!("/{}", self.search_state.query),
+            Mode::Normal | Mode::FileTree if !self.key_sequence.is_empty() => {
+                self.key_sequence.replace(CTRL_W, "^W")
+            }
+            _ => String::new(),
+        })
+        .style(Style::default().fg(Color::White));
+        f.render_widget(command, chunks[2]);
+        Ok(())
+    }
+
+    fn render_table(&mut self, f: &mut Frame, area: Rect) {
+        const MAX_CELL_WIDTH: usize = 30;
+        let state = &self.table_state;
+        let cells: Vec<Vec<String>> = state
+            .rows
+            .iter()
+            .map(|&i| {
+                (0..state.columns.len())
+                    .map(|c| self.table_cell(&state.records[i], c))
+                    .collect()
+            })
+            .collect();
+        let headers: Vec<String> = state
+            .columns
+            .iter()
+            .enumerate()
+            .map(|(c, name)| {
+                let mut header = name.clone();
+                match state.sort {
+                    Some((column, false)) if column == c => header.push_str(" ▲"),
+                    Some((column, true)) if column == c => header.push_str(" ▼"),
+                    _ => {}
                 }
-                ratatui::crossterm::event::KeyCode::Char('y') => {
-                    self.copy_selected();
-                    self.tree_visual_anchor = None;
-                    self.mode = Mode::FileTree;
+                if !state.filters[c].is_empty() {
+                    header.push_str(" ⧩");
                 }
-                ratatui::crossterm::event::KeyCode::Char('x') => {
-                    self.cut_selected();
-                    self.tree_visual_anchor = None;
-                    self.mode = Mode::FileTree;
+                header
+            })
+            .collect();
+        let widths: Vec<usize> = headers
+            .iter()
+            .enumerate()
+            .map(|(c, header)| {
+                cells
+                    .iter()
+                    .map(|row| row[c].chars().count())
+                    .chain(std::iter::once(header.chars().count()))
+                    .max()
+                    .unwrap_or(0)
+                    .min(MAX_CELL_WIDTH)
+            })
+            .collect();
+        let fit = |text: &str, width: usize| {
+            if text.chars().count() > width {
+                let cut: String = text.chars().take(width.saturating_sub(1)).collect();
+                format!("{}…", cut)
+            } else {
+                format!("{:<width$}", text, width = width)
+            }
+        };
+        let selected_column = state.column;
+        let row_line = |row: &[String], base: Style| {
+            let mut spans = Vec::new();
+            for (c, cell) in row.iter().enumerate() {
+                if c > 0 {
+                    spans.push(Span::styled(" │ ", Style::default().fg(Color::DarkGray)));
                 }
-                ratatui::crossterm::event::KeyCode::Char('r') => {
-                    let current = self.tree_state.selected().unwrap_or(0);
-                    let anchor = self.tree_visual_anchor.unwrap_or(current);
-                    if anchor == current {
-                        self.prev_mode = Some(Mode::FileTreeVisual);
-                        self.mode = Mode::Command;
-                        self.command = "rename ".to_string();
-                        self.status = "Rename to:".to_string();
-                    } else {
-                        self.status = "Rename only for single file".to_string();
-                    }
+                let style = if c == selected_column {
+                    base.fg(Color::Yellow)
+                } else {
+                    base
+                };
+                spans.push(Span::styled(fit(cell, widths[c]), style));
+            }
+            Line::from(spans)
+        };
+
+        let block = Block::default()
+            .borders(Borders::ALL)
+            .title(format!(
+                "Table: {} ({}/{})",
+                state.title,
+                state.rows.len(),
+                state.records.len()
+            ))
+            .style(Style::default().fg(Color::White));
+        let inner = block.inner(area);
+        f.render_widget(block, area);
+        let parts = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints([Constraint::Length(1), Constraint::Min(1)])
+            .split(inner);
+        let header = Paragraph::new(row_line(&headers, Style::default().fg(Color::LightBlue)));
+        f.render_widget(header, parts[0]);
+        let items: Vec<ListItem> = cells
+            .iter()
+            .map(|row| ListItem::new(row_line(row, Style::default())))
+            .collect();
+        let list = List::new(items).highlight_style(Style::default().bg(Color::DarkGray));
+        f.render_stateful_widget(list, parts[1], &mut self.table_state.list_state);
+
+        if self.table_state.editing != TableInput::None {
+            let title = match self.table_state.editing {
+                TableInput::Filter => {
+                    format!("Filter {}", self.table_state.columns[selected_column])
+                }
+                _ => format!("Set {}", self.table_state.columns[selected_column]),
+            };
+            let input_area = Rect {
+                x: area.x + 1,
+                y: area.y + area.height.saturating_sub(4),
+                width: area.width.saturating_sub(2),
+                height: 3.min(area.height),
+            };
+            let input = Paragraph::new(format!("{}█", self.table_state.input))
+                .block(
+                    Block::default()
+                        .borders(Borders::ALL)
+                        .title(title)
+                        .style(Style::default().fg(Color::White)),
+                )
+                .style(Style::default().fg(Color::Yellow));
+            f.render_widget(Clear, input_area);
+            f.render_widget(input, input_area);
+        }
+    }
+
+    fn render_outline(&mut self, f: &mut Frame, area: Rect) {
+        let headings = markdown::headings(self.textarea.lines());
+        let current = markdown::heading_at(&headings, self.textarea.cursor().0);
+        if self.mode != Mode::Outline {
+            self.outline_state.select(current);
+        }
+        let min_level = headings.iter().map(|h| h.level).min().unwrap_or(1);
+        let items: Vec<ListItem> = headings
+            .iter()
+            .enumerate()
+            .map(|(i, heading)| {
+                let style = if Some(i) == current {
+                    Style::default().fg(Color::Yellow)
+                } else {
+                    Style::default().fg(Color::White)
+                };
+                ListItem::new(format!(
+                    "{}{}",
+                    "  ".repeat(heading.level - min_level),
+                    heading.title
+                ))
+                .style(style)
+            })
+            .collect();
+        let highlight = if self.mode == Mode::Outline {
+            Style::default().bg(Color::White).fg(Color::Black)
+        } else {
+            Style::default()
+        };
+        let list = List::new(items)
+            .block(
+                Block::default()
+                    .borders(Borders::ALL)
+                    .title("Outline")
+                    .style(Style::default().fg(Color::White)),
+            )
+            .highlight_style(highlight);
+        f.render_stateful_widget(list, area, &mut self.outline_state);
+    }
+
+    // `]]` / `[[`: cursor to the next or previous heading
+    fn jump_heading(&mut self, forward: bool) {
+        let row = self.textarea.cursor().0;
+        let headings = markdown::headings(self.textarea.lines());
+        let target = if forward {
+            headings.iter().find(|h| h.line > row)
+        } else {
+            headings.iter().rev().find(|h| h.line < row)
+        };
+        match target {
+            Some(heading) => {
+                self.textarea
+                    .move_cursor(CursorMove::Jump(heading.line as u16, 0));
+                self.status = heading.title.clone();
+            }
+            None => self.status = "No more headings".to_string(),
+        }
+    }
+
+    // Run the key sequence if it is an operator/motion command. False when it is
+    // not part of that grammar, so the `\` chords and others get a look at it.
+    fn handle_vim_sequence(&mut self) -> bool {
+        // `q{reg}` records a macro, `q` stops, `[count]@{reg}` plays one and `@@` the last
+        let digits = self
+            .key_sequence
+            .find(|c: char| !c.is_ascii_digit())
+            .unwrap_or(self.key_sequence.len());
+        let (count, rest) = self.key_sequence.split_at(digits);
+        if !count.starts_with('0') && rest == "." {
+            self.repeat_request = Some(count.parse().ok());
+            self.key_sequence.clear();
+            return true;
+        }
+        if !count.starts_with('0')
+            && (rest.starts_with('@') || rest.starts_with('q') && count.is_empty())
+        {
+            let count = count.parse().unwrap_or(1);
+            let rest = rest.to_string();
+            if rest == "q" && self.recording.is_some() {
+                self.key_sequence.clear();
+                self.stop_recording();
+                return true;
+            }
+            let Some(name) = rest.chars().nth(1) else {
+                return true;
+            };
+            self.key_sequence.clear();
+            match (rest.starts_with('q'), name) {
+                (true, name) if name.is_ascii_alphanumeric() => {
+                    self.recording = Some((name, Vec::new()));
+                    self.status = format!("Recording @{}", name.to_ascii_lowercase());
+                }
+                (false, '@') => match self.last_macro {
+                    Some(name) => self.macro_request = Some((name, count)),
+                    None => self.status = "No previous macro".to_string(),
+                },
+                (false, name) if name.is_ascii_alphanumeric() || name == '"' => {
+                    self.macro_request = Some((name, count))
+                }
+                _ => self.status = format!("Invalid register: {}", name),
+            }
+            return true;
+        }
+        // `"x` picks the register for the next yank, delete or paste
+        if let Some(name) = self.key_sequence.strip_prefix('"') {
+            let Some(name) = name.chars().next() else {
+                return true;
+            };
+            self.key_sequence.clear();
+            if registers::is_valid(name) {
+                self.pending_register = Some(name);
+                self.status = format!("\"{}", name);
+            } else {
+                self.status = format!("Invalid register: {}", name);
+            }
+            return true;
+        }
+        match vim::parse(&self.key_sequence) {
+            vim::Parse::Pending => true,
+            vim::Parse::Invalid => false,
+            vim::Parse::Command(command) => {
+                self.key_sequence.clear();
+                self.run_vim_command(command);
+                self.pending_register = None;
+                true
+            }
+        }
+    }
+
+    fn run_vim_command(&mut self, command: vim::Command) {
+        let lines = self.textarea.lines().to_vec();
+        let cursor = self.textarea.cursor();
+        let motion = match command.motion {
+            Motion::RepeatFind { reverse } => match self.last_find {
+                Some(find) => Motion::Find(vim::Find {
+                    forward: find.forward != reverse,
+                    repeat: true,
+                    ..find
+                }),
+                None => {
+                    self.status = "No previous f/t to repeat".to_string();
+                    self.motion_failed = true;
+                    return;
                 }
-                _ => {}
             },
+            Motion::Find(find) => {
+                self.last_find = Some(find);
+                command.motion
+            }
+            motion => motion,
+        };
+        if let Motion::Object { inner, object } = motion {
+            match (command.operator, self.text_object(inner, object)) {
+                (Some(operator), Some((start, end, linewise))) => {
+                    self.apply_operator(operator, start, end, linewise)
+                }
+                _ => {
+                    self.status = "No text object here".to_string();
+                    self.motion_failed = true;
+                }
+            }
+            return;
+        }
+        let target = match (command.operator, motion) {
+            (Some(Operator::Change), Motion::WordStart { big })
+                if !vim::is_blank_at(&lines, cursor) =>
+            {
+                Some(vim::change_word_target(&lines, cursor, big, command.count))
+            }
+            _ => vim::target(&lines, cursor, motion, command.count),
+        };
+        let Some(target) = target else {
+            self.status = "No match".to_string();
+            self.motion_failed = true;
+            return;
+        };
+        match command.operator {
+            // `j` on the last line, `w` at the end of the note and the like
+            None if target.pos == cursor
+                && !matches!(
+                    motion,
+                    Motion::LineStart
+                        | Motion::FirstNonBlank
+                        | Motion::LineEnd
+                        | Motion::FirstLine
+                        | Motion::LastLine
+                        | Motion::Line
+                ) =>
+            {
+                self.motion_failed = true;
+            }
+            None => {
+                self.textarea
+                    .move_cursor(CursorMove::Jump(target.pos.0 as u16, target.pos.1 as u16));
+                if target.pos.0 != cursor.0 {
+                    self.skip_folds(target.pos.0 > cursor.0);
+                }
+            }
+            Some(operator) => {
+                let (start, end, linewise) = vim::operator_range(&lines, cursor, motion, target);
+                self.apply_operator(operator, start, end, linewise);
+            }
+        }
+    }
+
+    // Range of `i{object}`/`a{object}` at the cursor; `l` is the wikilink or
+    // `[text](url)` link under it
+    fn text_object(&self, inner: bool, object: char) -> Option<vim::Range> {
+        let (row, col) = self.textarea.cursor();
+        if object != 'l' {
+            return vim::text_object(self.textarea.lines(), (row, col), inner, object);
+        }
+        let line = &self.textarea.lines()[row];
+        let md_link_re = regex::Regex::new(r"\[([^\]]*)\]\([^)]*\)").unwrap();
+        let md_link = || {
+            md_link_re.captures_iter(line).find_map(|cap| {
+                let whole = cap.get(0)?;
+                let text = cap.get(1)?;
+                let span = (whole.start(), text.start(), text.end(), whole.end());
+                let chars = |byte: usize| line[..byte].chars().count();
+                (chars(whole.start()) <= col && col < chars(whole.end())).then_some(span)
+            })
+        };
+        let (open, content_start, content_end, close_end) =
+            self.extract_span(line, col, "[[", "]]").or_else(md_link)?;
+        let chars = |byte: usize| line[..byte].chars().count();
+        if inner {
+            Some((
+                (row, chars(content_start)),
+                (row, chars(content_end)),
+                false,
+            ))
+        } else {
+            Some(((row, chars(open)), (row, chars(close_end)), false))
+        }
+    }
+
+    // Visual `i{object}`/`a{object}`: select the object
+    fn select_text_object(&mut self, inner: bool, object: char) {
+        let Some((start, end, linewise)) = self.text_object(inner, object) else {
+            self.status = "No text object here".to_string();
+            return;
+        };
+        let end = if linewise {
+            (end.0, self.textarea.lines()[end.0].chars().count())
+        } else {
+            end
+        };
+        self.textarea.cancel_selection();
+        self.textarea
+            .move_cursor(CursorMove::Jump(start.0 as u16, start.1 as u16));
+        self.textarea.start_selection();
+        self.textarea
+            .move_cursor(CursorMove::Jump(end.0 as u16, end.1 as u16));
+        self.visual_anchor = Some(start);
+    }
+
+    // Save yanked or deleted text to the register picked with `"x`, or the default ones
+    fn store_register(&mut self, text: Vec<String>, linewise: bool, delete: bool) {
+        let name = self.pending_register.take();
+        let register = Register::new(text, linewise);
+        if delete {
+            self.registers.delete(name, register);
+        } else {
+            self.registers.yank(name, register);
+        }
+    }
+
+    // `p`/`P`: linewise text goes below/above the line, charwise after/at the cursor
+    fn paste(&mut self, before: bool) {
+        let name = self.pending_register.take().unwrap_or('"');
+        let Some(register) = self.registers.get(name) else {
+            self.status = format!("Register \"{} is empty", name);
+            return;
+        };
+        self.change_made = true;
+        let (row, col) = self.textarea.cursor();
+        if register.linewise {
+            let at = if before { row } else { row + 1 };
+            self.replace_lines(at, at, &register.text);
+            let indent = register.text[0]
+                .chars()
+                .take_while(|c| c.is_whitespace())
+                .count();
+            self.textarea
+                .move_cursor(CursorMove::Jump(at as u16, indent as u16));
+        } else {
+            if !before && col < self.textarea.lines()[row].chars().count() {
+                self.textarea.move_cursor(CursorMove::Forward);
+            }
+            self.textarea.insert_str(register.text.join("\n"));
+            self.textarea.move_cursor(CursorMove::Back);
+        }
+    }
+
+    // Text under the Visual or Visual Block selection, one entry per line
+    fn visual_text(&self) -> Vec<String> {
+        let Some(anchor) = self.visual_anchor else {
+            return Vec::new();
+        };
+        let lines = self.textarea.lines();
+        let cursor = self.textarea.cursor();
+        if self.mode == Mode::VisualLine {
+            let (start, end) = self.visual_rows();
+            return lines[start..=end].to_vec();
+        }
+        if self.mode == Mode::VisualBlock {
+            let (min_col, max_col) = (anchor.1.min(cursor.1), anchor.1.max(cursor.1));
+            (anchor.0.min(cursor.0)..=anchor.0.max(cursor.0))
+                .map(|row| {
+                    lines[row]
+                        .chars()
+                        .skip(min_col)
+                        .take(max_col - min_col + 1)
+                        .collect()
+                })
+                .collect()
+        } else {
+            self.textarea
+                .selection_range()
+                .map(|(start, end)| vim::text_in(lines, start, end))
+                .unwrap_or_default()
+        }
+    }
+
+    // First and last row of the Visual selection
+    fn visual_rows(&self) -> (usize, usize) {
+        let row = self.textarea.cursor().0;
+        let anchor = self.visual_anchor.map_or(row, |a| a.0);
+        (anchor.min(row), anchor.max(row))
+    }
+
+    fn leave_visual(&mut self) {
+        self.textarea.cancel_selection();
+        self.visual_anchor = None;
+        self.mode = Mode::Normal;
+    }
+
+    // Select from `anchor` to `cursor`, keeping the textarea selection in step
+    fn select_visual(&mut self, mode: Mode, anchor: (usize, usize), cursor: (usize, usize)) {
+        self.textarea.cancel_selection();
+        self.textarea
+            .move_cursor(CursorMove::Jump(anchor.0 as u16, anchor.1 as u16));
+        self.textarea.start_selection();
+        self.textarea
+            .move_cursor(CursorMove::Jump(cursor.0 as u16, cursor.1 as u16));
+        self.visual_anchor = Some(anchor);
+        self.mode = mode;
+    }
+
+    // `gv`: select the last Visual selection again
+    fn reselect_visual(&mut self) {
+        let Some((mode, (anchor, cursor))) = self.last_visual else {
+            self.status = "No previous selection".to_string();
+            return;
+        };
+        let last_row = self.textarea.lines().len() - 1;
+        let clamp = |(row, col): (usize, usize)| (row.min(last_row), col);
+        self.select_visual(mode, clamp(anchor), clamp(cursor));
+        self.status = visual_status(mode).to_string();
+    }
+
+    // `o` in Visual: move the cursor to the other end of the selection
+    fn swap_visual_ends(&mut self) {
+        if let Some(anchor) = self.visual_anchor {
+            let cursor = self.textarea.cursor();
+            self.select_visual(self.mode, cursor, anchor);
         }
-        Ok(())
     }
 
-    pub fn render(
-        &mut self,
-        terminal: &mut Terminal<CrosstermBackend<io::Stdout>>,
-    ) -> Result<(), EditorError> {
-        terminal.draw(|f| {
-            if let Err(e) = self.draw(f) {
-                self.status = format!("Render error: {}", e);
+    // `>` `<` `~` `u` `U` on the Visual selection
+    fn visual_operator(&mut self, operator: Operator) {
+        let (start, end) = self.visual_rows();
+        let selection = self.textarea.selection_range();
+        let (anchor, cursor) = (self.visual_anchor, self.textarea.cursor());
+        let mode = self.mode;
+        self.leave_visual();
+        if mode == Mode::VisualLine || matches!(operator, Operator::Indent | Operator::Outdent) {
+            self.apply_operator(operator, (start, 0), (end, 0), true);
+        } else if mode == Mode::VisualBlock {
+            let anchor = anchor.unwrap_or(cursor);
+            let (min_col, max_col) = (anchor.1.min(cursor.1), anchor.1.max(cursor.1));
+            let lines = self.textarea.lines().to_vec();
+            let new_lines: Vec<String> = (start..=end)
+                .flat_map(|row| {
+                    vim::map_range(&lines, (row, min_col), (row, max_col + 1), false, |c| {
+                        vim::change_case(operator, c)
+                    })
+                })
+                .collect();
+            if new_lines.as_slice() != &lines[start..=end] {
+                self.replace_lines(start, end + 1, &new_lines);
             }
-        })?;
-        Ok(())
+            self.textarea
+                .move_cursor(CursorMove::Jump(start as u16, min_col as u16));
+            self.change_made = true;
+        } else if let Some((from, to)) = selection {
+            self.apply_operator(operator, from, to, false);
+        }
+        if !matches!(operator, Operator::Indent | Operator::Outdent) {
+            self.status = "Normal".to_string();
+        }
     }
 
-    fn draw(&mut self, f: &mut Frame) -> Result<(), EditorError> {
-        let chunks = Layout::default()
-            .direction(Direction::Vertical)
-            .constraints([
-                Constraint::Min(1),    // Editor, info, or list area
-                Constraint::Length(1), // Status line
-                Constraint::Length(1), // Command line or key sequence
-            ])
-            .split(f.area());
+    // `J` in Visual: join the selected lines, or the line with the next one
+    fn visual_join(&mut self) {
+        let (start, end) = self.visual_rows();
+        let end = end.max(start + 1).min(self.textarea.lines().len() - 1);
+        self.leave_visual();
+        if end > start {
+            let joined = vim::join_lines(&self.textarea.lines()[start..=end]);
+            let col = self.textarea.lines()[start].trim_end().chars().count();
+            self.replace_lines(start, end + 1, &[joined]);
+            self.textarea
+                .move_cursor(CursorMove::Jump(start as u16, col as u16));
+            self.change_made = true;
+        }
+        self.status = format!("Joined {} lines", end - start + 1);
+    }
 
-        match self.mode {
-            Mode::Search => {
-                // Render search input field or results list
-                let title = match self.search_state.search_type {
-                    SearchType::Backlinks => format!("Backlinks: {}", self.search_state.query),
-                    SearchType::Tags => format!("Tags: {}", self.search_state.query),
-                    SearchType::Files => format!("Files: {}", self.search_state.query),
-                    SearchType::None => "Search".to_string(),
-                };
-                if self.search_state.results.is_empty() && self.search_state.query.is_empty() {
-                    // Render input field for search
-                    let input = Paragraph::new(self.search_state.query.clone())
-                        .block(
-                            Block::default()
-                                .borders(Borders::ALL)
-                                .title(title)
-                                .style(Style::default().fg(Color::White)),
-                        )
-                        .style(Style::default().fg(Color::Yellow));
-                    f.render_widget(input, chunks[0]);
+    fn open_registers_report(&mut self) {
+        let lines: Vec<ReportLine> = self
+            .registers
+            .list()
+            .into_iter()
+            .map(|(name, register)| ReportLine {
+                text: format!(
+                    "\"{}  {}  {}",
+                    name,
+                    if register.linewise { "l" } else { "c" },
+                    register.preview()
+                ),
+                target: None,
+                row: 0,
+            })
+            .collect();
+        let count = lines.len();
+        self.show_report(ReportType::Registers, "Registers".to_string(), lines);
+        self.status = format!("{} registers", count);
+    }
+
+    // Run an operator on rows start.0..=end.0 when linewise, else on the chars
+    // from start up to end. Each change is one undo step.
+    fn apply_operator(
+        &mut self,
+        operator: Operator,
+        start: (usize, usize),
+        end: (usize, usize),
+        linewise: bool,
+    ) {
+        let lines = self.textarea.lines().to_vec();
+        let first_non_blank =
+            |line: &str| line.chars().take_while(|c| c.is_whitespace()).count() as u16;
+        let row_count = end.0 - start.0 + 1;
+        self.change_made |= operator != Operator::Yank;
+        match operator {
+            Operator::Yank | Operator::Delete | Operator::Change => {
+                let text = if linewise {
+                    lines[start.0..=end.0].to_vec()
                 } else {
-                    // Render search results
-                    let items: Vec<ListItem> = self
-                        .search_state
-                        .results
-                        .iter()
-                        .map(|(text, _)| ListItem::new(text.clone()))
+                    vim::text_in(&lines, start, end)
+                };
+                self.store_register(text, linewise, operator != Operator::Yank);
+                if operator == Operator::Yank {
+                    self.textarea
+                        .move_cursor(CursorMove::Jump(start.0 as u16, start.1 as u16));
+                    self.status = if linewise {
+                        format!("Yanked {} lines", row_count)
+                    } else {
+                        "Yanked".to_string()
+                    };
+                    return;
+                }
+                if linewise && operator == Operator::Delete {
+                    self.replace_lines(start.0, end.0 + 1, &[]);
+                    let row = start.0.min(self.textarea.lines().len() - 1);
+                    let col = first_non_blank(&self.textarea.lines()[row]);
+                    self.textarea.move_cursor(CursorMove::Jump(row as u16, col));
+                    self.status = format!("Deleted {} lines", row_count);
+                    return;
+                }
+                if linewise {
+                    // `cc` keeps the indent of the first line
+                    let indent: String = lines[start.0]
+                        .chars()
+                        .take_while(|c| c.is_whitespace())
                         .collect();
-                    let list = List::new(items)
-                        .block(
-                            Block::default()
-                                .borders(Borders::ALL)
-                                .title(title)
-                                .style(Style::default().fg(Color::White)),
-                        )
-                        .highlight_style(Style::default().bg(Color::White).fg(Color::Black));
-                    f.render_stateful_widget(list, chunks[0], &mut self.search_state.list_state);
+                    self.replace_lines(start.0, end.0 + 1, std::slice::from_ref(&indent));
+                    self.textarea.move_cursor(CursorMove::Jump(
+                        start.0 as u16,
+                        indent.chars().count() as u16,
+                    ));
+                } else {
+                    self.textarea.cancel_selection();
+                    self.textarea
+                        .move_cursor(CursorMove::Jump(start.0 as u16, start.1 as u16));
+                    if start != end {
+                        self.textarea.start_selection();
+                        self.textarea
+                            .move_cursor(CursorMove::Jump(end.0 as u16, end.1 as u16));
+                        self.textarea.cut();
+                    }
+                }
+                if operator == Operator::Change {
+                    self.mode = Mode::Insert;
+                    self.status = "Insert".to_string();
+                } else {
+                    self.status = "Deleted".to_string();
                 }
             }
-            Mode::TagFiles => {
-                let items: Vec<ListItem> = self
-                    .tag_files
+            Operator::Indent | Operator::Outdent => {
+                let new_lines: Vec<String> = lines[start.0..=end.0]
                     .iter()
-                    .map(|(file_name, _)| ListItem::new(file_name.clone()))
+                    .map(|line| {
+                        if operator == Operator::Indent && line.is_empty() {
+                            line.clone()
+                        } else {
+                            markdown::indent_list_item(line, operator == Operator::Outdent).0
+                        }
+                    })
                     .collect();
-                let list = List::new(items)
-                    .block(
-                        Block::default()
-                            .borders(Borders::ALL)
-                            .title("Files for Tag")
-                            .style(Style::default().fg(Color::White)),
-                    )
-                    .highlight_style(Style::default().bg(Color::White).fg(Color::Black));
-                f.render_stateful_widget(list, chunks[0], &mut self.tag_files_state);
+                self.replace_lines(start.0, end.0 + 1, &new_lines);
+                let col = first_non_blank(&self.textarea.lines()[start.0]);
+                self.textarea
+                    .move_cursor(CursorMove::Jump(start.0 as u16, col));
+                self.status = format!("Shifted {} lines", row_count);
             }
-            Mode::FileTree | Mode::FileTreeVisual => {
-                let tree_constraint = if self.full_tree {
-                    Constraint::Percentage(100)
+            Operator::Lower | Operator::Upper | Operator::ToggleCase => {
+                let new_lines = vim::map_range(&lines, start, end, linewise, |c| {
+                    vim::change_case(operator, c)
+                });
+                if new_lines.as_slice() != &lines[start.0..=end.0] {
+                    self.replace_lines(start.0, end.0 + 1, &new_lines);
+                }
+                self.textarea
+                    .move_cursor(CursorMove::Jump(start.0 as u16, start.1 as u16));
+            }
+        }
+    }
+
+    // `\h` chords on the section under the cursor: `<`/`>` promote/demote it with
+    // its subheadings, `k`/`j` move it past its siblings, `y`/`d` yank/delete it
+    fn section_command(&mut self, command: char) {
+        let lines = self.textarea.lines().to_vec();
+        let row = self.textarea.cursor().0;
+        let headings = markdown::headings(&lines);
+        let Some(index) = markdown::heading_at(&headings, row) else {
+            self.status = "Not in a section".to_string();
+            return;
+        };
+        let level = headings[index].level;
+        let start = headings[index].line;
+        let end = markdown::section_end(&headings, index, lines.len());
+
+        match command {
+            '<' | '>' => {
+                let inner: Vec<&markdown::Heading> = headings
+                    .iter()
+                    .filter(|h| h.line >= start && h.line < end)
+                    .collect();
+                let out_of_range = if command == '<' {
+                    inner.iter().any(|h| h.level == 1)
                 } else {
-                    Constraint::Percentage(self.tree_width_percent)
+                    inner.iter().any(|h| h.level == 6)
                 };
-                let editor_constraint = if self.full_tree {
-                    Constraint::Length(0)
+                if out_of_range {
+                    self.status = "Heading level must stay between 1 and 6".to_string();
+                    return;
+                }
+                let mut section = lines[start..end].to_vec();
+                for heading in inner {
+                    let line = &mut section[heading.line - start];
+                    if command == '<' {
+                        line.remove(0);
+                    } else {
+                        line.insert(0, '#');
+                    }
+                }
+                self.replace_lines(start, end, &section);
+                self.status = if command == '<' {
+                    "Promoted section".to_string()
                 } else {
-                    Constraint::Percentage(100 - self.tree_width_percent)
+                    "Demoted section".to_string()
                 };
-                let main_chunks = Layout::default()
-                    .direction(Direction::Horizontal)
-                    .constraints([tree_constraint, editor_constraint])
-                    .split(chunks[0]);
+            }
+            'k' => {
+                // Previous heading of the same level under the same parent
+                let Some(previous) = headings[..index]
+                    .iter()
+                    .rev()
+                    .find(|h| h.level <= level)
+                    .filter(|h| h.level == level)
+                else {
+                    self.status = "No section above".to_string();
+                    return;
+                };
+                let previous_start = previous.line;
+                let mut moved = lines[start..end].to_vec();
+                moved.extend_from_slice(&lines[previous_start..start]);
+                self.replace_lines(previous_start, end, &moved);
+                self.textarea
+                    .move_cursor(CursorMove::Jump((previous_start + row - start) as u16, 0));
+                self.status = "Moved section up".to_string();
+            }
+            'j' => {
+                let Some(next) = headings
+                    .iter()
+                    .position(|h| h.line == end)
+                    .filter(|&i| headings[i].level == level)
+                else {
+                    self.status = "No section below".to_string();
+                    return;
+                };
+                let next_end = markdown::section_end(&headings, next, lines.len());
+                let mut moved = lines[end..next_end].to_vec();
+                moved.extend_from_slice(&lines[start..end]);
+                self.replace_lines(start, next_end, &moved);
+                self.textarea
+                    .move_cursor(CursorMove::Jump((row + next_end - end) as u16, 0));
+                self.status = "Moved section down".to_string();
+            }
+            'y' | 'd' => {
+                self.store_register(lines[start..end].to_vec(), true, command == 'd');
+                if command == 'd' {
+                    self.replace_lines(start, end, &[]);
+                    self.textarea.move_cursor(CursorMove::Jump(start as u16, 0));
+                    self.status = format!("Deleted section ({} lines)", end - start);
+                } else {
+                    self.status = format!("Yanked section ({} lines)", end - start);
+                }
+            }
+            _ => {}
+        }
+    }
 
-                let visual_min = self
-                    .tree_visual_anchor
-                    .map(|a| a.min(self.tree_state.selected().unwrap_or(0)))
-                    .unwrap_or(usize::MAX);
-                let visual_max = self
-                    .tree_visual_anchor
-                    .map(|a| a.max(self.tree_state.selected().unwrap_or(0)))
-                    .unwrap_or(0);
+    // Keep closed folds on their rows after lines were added or removed above them
+    fn sync_folds(&mut self) {
+        let line_count = self.textarea.lines().len();
+        if line_count != self.fold_line_count {
+            let delta = line_count as isize - self.fold_line_count as isize;
+            let row = self.textarea.cursor().0;
+            for start in &mut self.closed_folds {
+                if (delta > 0 && *start >= row) || (delta < 0 && *start > row) {
+                    *start = start.saturating_add_signed(delta);
+                }
+            }
+            self.fold_line_count = line_count;
+        }
+        if self.closed_folds.is_empty() {
+            return;
+        }
+        let starts: Vec<usize> = markdown::fold_regions(self.textarea.lines())
+            .iter()
+            .map(|&(start, _)| start)
+            .collect();
+        self.closed_folds.retain(|start| starts.contains(start));
+        self.closed_folds.dedup();
+    }
 
-                let items: Vec<ListItem> = self
-                    .visible_items
+    // Outermost closed fold hiding `row`, as (first row, last row)
+    fn closed_fold_at(&self, row: usize) -> Option<(usize, usize)> {
+        markdown::fold_regions(self.textarea.lines())
+            .into_iter()
+            .find(|&(start, end)| self.closed_folds.contains(&start) && row > start && row <= end)
+    }
+
+    fn hidden_rows(
+        line_count: usize,
+        regions: &[(usize, usize)],
+        closed_folds: &[usize],
+    ) -> Vec<bool> {
+        let mut hidden = vec![false; line_count];
+        for &(start, end) in regions {
+            if closed_folds.contains(&start) {
+                for row in start + 1..=end.min(hidden.len().saturating_sub(1)) {
+                    hidden[row] = true;
+                }
+            }
+        }
+        hidden
+    }
+
+    // Keep the cursor off rows hidden by closed folds, whatever moved it: it
+    // goes to the first row of the fold, except in Insert mode where the fold
+    // it is typing in opens
+    fn leave_closed_folds(&mut self) {
+        if self.closed_folds.is_empty() {
+            return;
+        }
+        let row = self.textarea.cursor().0;
+        if matches!(self.mode, Mode::Insert | Mode::BlockInsert) {
+            let regions = markdown::fold_regions(self.textarea.lines());
+            self.closed_folds.retain(|start| {
+                !regions
                     .iter()
-                    .enumerate()
-                    .map(|(i, item)| {
-                        let base_style = if item.is_dir {
-                            Style::default().fg(Color::LightBlue)
-                        } else {
-                            Style::default().fg(Color::White)
-                        };
-                        let mut li = ListItem::new(item.display.clone()).style(base_style);
-                        if self.mode == Mode::FileTreeVisual && i >= visual_min && i <= visual_max {
-                            li = li.style(Style::default().bg(Color::LightBlue));
-                        } else if Some(i) == self.tree_state.selected() {
-                            li = li.style(Style::default().bg(Color::White).fg(Color::Black));
-                        }
-                        li
-                    })
-                    .collect();
+                    .any(|&(s, end)| s == *start && row > s && row <= end)
+            });
+        } else if let Some((start, _)) = self.closed_fold_at(row) {
+            let col = self.textarea.cursor().1;
+            self.textarea
+                .move_cursor(CursorMove::Jump(start as u16, col as u16));
+        }
+    }
 
-                let list = List::new(items).block(
-                    Block::default()
-                        .borders(Borders::ALL)
-                        .title("File Tree")
-                        .style(Style::default().fg(Color::White)),
-                );
-                f.render_stateful_widget(list, main_chunks[0], &mut self.tree_state);
+    // Step over a closed fold the cursor moved into
+    fn skip_folds(&mut self, down: bool) {
+        let row = self.textarea.cursor().0;
+        let Some((start, end)) = self.closed_fold_at(row) else {
+            return;
+        };
+        let target = if down && end + 1 < self.textarea.lines().len() {
+            end + 1
+        } else {
+            start
+        };
+        let col = self.textarea.cursor().1;
+        self.textarea
+            .move_cursor(CursorMove::Jump(target as u16, col as u16));
+    }
 
-                if !self.full_tree {
-                    self.render_editor(f, main_chunks[1])?;
-                }
+    // `za` `zc` `zo` on the innermost fold at the cursor, `zM` `zR` on all of them
+    fn fold_command(&mut self, command: char) {
+        self.sync_folds();
+        let regions = markdown::fold_regions(self.textarea.lines());
+        let row = self.textarea.cursor().0;
+        match command {
+            'M' => {
+                self.closed_folds = regions.iter().map(|&(start, _)| start).collect();
+                self.closed_folds.dedup();
+                self.status = format!("Closed {} folds", self.closed_folds.len());
             }
-            Mode::Normal
-            | Mode::Insert
-            | Mode::Complete
-            | Mode::Command
-            | Mode::Visual
-            | Mode::VisualBlock
-            | Mode::BlockInsert => match self.view {
-                View::Editor => {
-                    self.render_editor(f, chunks[0])?;
-                }
-                View::Info => {
-                    let info = Paragraph::new(self.status.clone())
-                        .block(Block::default().borders(Borders::ALL).title("Info"))
-                        .style(Style::default().fg(Color::White));
-                    f.render_widget(info, chunks[0]);
+            'R' => {
+                self.closed_folds.clear();
+                self.status = "Opened all folds".to_string();
+            }
+            _ => {
+                let innermost = |open_only: bool| {
+                    regions
+                        .iter()
+                        .filter(|&&(start, end)| row >= start && row <= end)
+                        .filter(|&&(start, _)| !open_only || !self.closed_folds.contains(&start))
+                        .min_by_key(|&&(start, end)| end - start)
+                        .map(|&(start, _)| start)
+                };
+                let closed_here = self.closed_folds.contains(&row);
+                match command {
+                    'o' | 'a' if closed_here => {
+                        self.closed_folds.retain(|&start| start != row);
+                        self.status = "Opened fold".to_string();
+                    }
+                    'o' => self.status = "No closed fold here".to_string(),
+                    _ => match innermost(true) {
+                        Some(start) => {
+                            self.closed_folds.push(start);
+                            self.status = "Closed fold".to_string();
+                        }
+                        None => self.status = "No fold here".to_string(),
+                    },
                 }
-            },
+            }
+        }
+        // The cursor stays on a visible row
+        if let Some((start, _)) = self.closed_fold_at(row) {
+            let col = self.textarea.cursor().1;
+            self.textarea
+                .move_cursor(CursorMove::Jump(start as u16, col as u16));
         }
+    }
 
-        let status = Paragraph::new(format!("-- {} --", self.status))
-            .style(Style::default().fg(Color::Yellow));
-        f.render_widget(status, chunks[1]);
+    fn render_properties(&mut self, f: &mut Frame, area: Rect) {
+        let editing = self.props_state.editing;
+        let constraints = if editing == PropsField::None {
+            vec![Constraint::Min(1)]
+        } else {
+            vec![Constraint::Min(1), Constraint::Length(3)]
+        };
+        let panel = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints(constraints)
+            .split(area);
 
-        let command = Paragraph::new(match self.mode {
-            Mode::Command => format!(":{}", self.command),
-            Mode::Search => format!("/{}", self.search_state.query),
-            Mode::Normal | Mode::FileTree if !self.key_sequence.is_empty() => {
-                format!("{}", self.key_sequence)
-            }
-            _ => String::new(),
-        })
-        .style(Style::default().fg(Color::White));
-        f.render_widget(command, chunks[2]);
-        Ok(())
+        if editing == PropsField::Key {
+            // Key completion from the keys used across the vault
+            let items: Vec<ListItem> = self
+                .property_key_suggestions()
+                .into_iter()
+                .map(|(key, value)| {
+                    ListItem::new(Line::from(vec![
+                        Span::raw(key.clone()),
+                        Span::styled(
+                            format!("  {}", value.type_name()),
+                            Style::default().fg(Color::DarkGray),
+                        ),
+                    ]))
+                })
+                .collect();
+            let list = List::new(items)
+                .block(
+                    Block::default()
+                        .borders(Borders::ALL)
+                        .title("Keys in vault")
+                        .style(Style::default().fg(Color::White)),
+                )
+                .highlight_style(Style::default().bg(Color::White).fg(Color::Black));
+            f.render_stateful_widget(list, panel[0], &mut self.props_state.key_state);
+        } else {
+            let key_width = self
+                .props_state
+                .properties
+                .iter()
+                .map(|p| p.key.chars().count())
+                .max()
+                .unwrap_or(0);
+            let items: Vec<ListItem> = self
+                .props_state
+                .properties
+                .iter()
+                .map(|p| {
+                    ListItem::new(Line::from(vec![
+                        Span::styled(
+                            format!("{:<width$} ", p.key, width = key_width),
+                            Style::default().fg(Color::LightBlue),
+                        ),
+                        Span::styled(
+                            format!("{:<8} ", p.value.type_name()),
+                            Style::default().fg(Color::DarkGray),
+                        ),
+                        Span::raw(p.value.display()),
+                    ]))
+                })
+                .collect();
+            let title = if self.props_state.modified {
+                "Properties [+]"
+            } else {
+                "Properties"
+            };
+            let list = List::new(items)
+                .block(
+                    Block::default()
+                        .borders(Borders::ALL)
+                        .title(title)
+                        .style(Style::default().fg(Color::White)),
+                )
+                .highlight_style(Style::default().bg(Color::White).fg(Color::Black));
+            f.render_stateful_widget(list, panel[0], &mut self.props_state.list_state);
+        }
+
+        if editing != PropsField::None {
+            let title = match editing {
+                PropsField::Key => "Name".to_string(),
+                _ => self
+                    .props_state
+                    .list_state
+                    .selected()
+                    .and_then(|i| self.props_state.properties.get(i))
+                    .map(|p| format!("{} ({})", p.key, p.value.type_name()))
+                    .unwrap_or_default(),
+            };
+            let input = Paragraph::new(format!("{}█", self.props_state.input))
+                .block(
+                    Block::default()
+                        .borders(Borders::ALL)
+                        .title(title)
+                        .style(Style::default().fg(Color::White)),
+                )
+                .style(Style::default().fg(Color::Yellow));
+            f.render_widget(input, panel[1]);
+        }
     }
 
     fn render_editor(&mut self, f: &mut Frame, area: Rect) -> Result<(), EditorError> {
@@ -2897,19 +7660,61 @@ impl App {
         if cursor_row != last_cursor_row || cursor_col != last_cursor_col {
             self.load_image_at_cursor()?;
             self.completion_state.trigger_start = (cursor_row, cursor_col);
+            if self.preview_state.active {
+                let line = self
+                    .textarea
+                    .lines()
+                    .get(cursor_row)
+                    .cloned()
+                    .unwrap_or_default();
+                // Close the preview once the cursor leaves its wikilink
+                if self.extract_wikilink(&line, cursor_col).as_deref()
+                    != Some(self.preview_state.link.as_str())
+                {
+                    self.close_note_preview();
+                }
+            }
         }
 
-        // text rendering code
-        let text = self.textarea.lines().join("\n");
-        let syntax = self
-            .syntax_set
-            .find_syntax_by_extension("md")
-            .unwrap_or_else(|| self.syntax_set.find_syntax_by_name("Markdown").unwrap());
-        let mut highlighter = HighlightLines::new(syntax, &self.theme);
-        let mut highlighted_lines = Vec::new();
+        // Draw the other windows first; popups belong to the active one
+        self.editor_area = area;
+        let mut active_area = area;
+        for (id, window_area) in self.split.areas(area) {
+            if id == self.active_window {
+                active_area = window_area;
+                continue;
+            }
+            let Some(index) = self.windows.iter().position(|w| w.id == id) else {
+                continue;
+            };
+            let window = self.windows[index].clone();
+            let (lines, closed_folds) = if window.path == self.file_path {
+                (self.textarea.lines().to_vec(), self.closed_folds.clone())
+            } else {
+                self.buffers
+                    .iter()
+                    .find(|b| b.path == window.path)
+                    .map(|b| (b.textarea.lines().to_vec(), b.closed_folds.clone()))
+                    .unwrap_or_default()
+            };
+            let view = WindowView {
+                lines,
+                cursor: window.cursor,
+                selection: None,
+                closed_folds,
+                scroll: window.scroll,
+                block: Self::window_block(&window.path, false),
+                active: false,
+            };
+            self.windows[index].scroll = self.draw_window(f, window_area, view)?.scroll;
+        }
 
         // Get selection range for Visual/VisualBlock modes
         let selection_range = match self.mode {
+            Mode::VisualLine => self.visual_anchor.map(|anchor| {
+                let row = self.textarea.cursor().0;
+                ((anchor.0.min(row), 0), (anchor.0.max(row), usize::MAX))
+            }),
             Mode::Visual | Mode::VisualBlock => self.visual_anchor.map(|anchor| {
                 let cursor = self.textarea.cursor();
                 let start_row = anchor.0.min(cursor.0);
@@ -2928,6 +7733,169 @@ impl App {
             }),
             _ => None,
         };
+        let block = if self.windows.len() > 1 {
+            Self::window_block(&self.file_path, true)
+        } else {
+            self.textarea.block().cloned().unwrap_or_default()
+        };
+        let view = WindowView {
+            lines: self.textarea.lines().to_vec(),
+            cursor: (cursor_row, cursor_col),
+            selection: selection_range,
+            closed_folds: self.closed_folds.clone(),
+            scroll: (self.scroll_offset, self.horizontal_scroll_offset),
+            block,
+            active: true,
+        };
+        let drawn = self.draw_window(f, active_area, view)?;
+        (self.scroll_offset, self.horizontal_scroll_offset) = drawn.scroll;
+        let visible_lines = drawn.visible_lines;
+        let to_display = |row: usize| {
+            drawn
+                .display_rows
+                .iter()
+                .position(|r| r.is_some_and(|r| r >= row))
+                .unwrap_or(drawn.display_rows.len())
+        };
+        let editor_area = area;
+        let area = active_area;
+
+        if let (Some(picker), Some(dyn_img), Some(image_row)) = (
+            self.image_picker.as_mut(),
+            self.current_image.as_ref(),
+            self.current_image_line,
+        ) {
+            let (image_area, title_str) = if self.image_full_screen {
+                (editor_area, "Image (Full Screen)") // Use the whole editor area
+            } else {
+                (
+                    Self::popup_area(
+                        area,
+                        to_display(image_row),
+                        self.scroll_offset,
+                        visible_lines,
+                    ),
+                    "Image",
+                )
+            };
+
+            if self.image_protocol.is_none() || self.last_image_area != Some(image_area) {
+                self.image_protocol = Some(picker.new_resize_protocol(dyn_img.clone()));
+
+                self.last_image_area = Some(image_area);
+            }
+
+            if let Some(image_protocol) = self.image_protocol.as_mut() {
+                let block = Block::default().borders(Borders::ALL).title(title_str);
+                let image_widget = ratatui_image::StatefulImage::default()
+                    .resize(Resize::Scale(Some(FilterType::Triangle)));
+                if self.image_full_screen {
+                    // Clear text underneath
+
+                    f.render_widget(ratatui::widgets::Clear, image_area);
+                }
+
+                f.render_widget(block, image_area);
+
+                let margin = Margin::new(1, 1);
+
+                f.render_stateful_widget(image_widget, image_area.inner(margin), image_protocol);
+
+                if let Some(Err(e)) = image_protocol.last_encoding_result() {
+                    self.status = format!("Image encoding error: {}", e);
+                }
+            }
+        }
+
+        // Render note preview popup if active
+        if self.preview_state.active {
+            let popup_area = Self::popup_area(
+                area,
+                to_display(self.preview_state.anchor_row),
+                self.scroll_offset,
+                visible_lines,
+            );
+            let inner_height = popup_area.height.saturating_sub(2) as usize;
+            let preview_lines = self.highlight_lines(&self.preview_state.lines)?;
+            let shown: Vec<Line> = preview_lines
+                .into_iter()
+                .skip(self.preview_state.scroll)
+                .take(inner_height)
+                .collect();
+            let title = format!(
+                "{} ({}/{})",
+                self.preview_state.title,
+                (self.preview_state.scroll + 1).min(self.preview_state.lines.len()),
+                self.preview_state.lines.len()
+            );
+            let paragraph = Paragraph::new(shown).block(
+                Block::default()
+                    .borders(Borders::ALL)
+                    .title(title)
+                    .style(Style::default().fg(Color::White).bg(Color::Black)),
+            );
+            f.render_widget(Clear, popup_area);
+            f.render_widget(paragraph, popup_area);
+        }
+
+        // Render completion popup if active
+        if self.completion_state.active && !self.completion_state.suggestions.is_empty() {
+            let items: Vec<ListItem> = self
+                .completion_state
+                .suggestions
+                .iter()
+                .map(|s| ListItem::new(format!("{s}{}", " ".repeat(50))))
+                .collect();
+            let list = List::new(items)
+                .block(
+                    Block::default()
+                        .borders(Borders::ALL)
+                        .title(match self.completion_state.completion_type {
+                            CompletionType::File => "Files",
+                            CompletionType::Tag => "Tags",
+                            CompletionType::Variable => "Variable",
+                            CompletionType::None => "",
+                        })
+                        .style(Style::default().fg(Color::White).bg(Color::Black)),
+                )
+                .highlight_style(Style::default().bg(Color::White).fg(Color::Black));
+            let popup_width = 40;
+            let popup_height = (self.completion_state.suggestions.len().min(5) + 2) as u16;
+            let popup_area = Rect {
+                x: area.x + area.width.saturating_sub(popup_width),
+                y: area.y + 1,
+                width: popup_width,
+                height: popup_height,
+            };
+            f.render_stateful_widget(list, popup_area, &mut self.completion_state.list_state);
+        }
+
+        Ok(())
+    }
+    // Highlight, fold and scroll a window's text into `area`
+    fn draw_window(
+        &mut self,
+        f: &mut Frame,
+        area: Rect,
+        view: WindowView,
+    ) -> Result<DrawnWindow, EditorError> {
+        let WindowView {
+            lines,
+            cursor: (cursor_row, cursor_col),
+            selection,
+            closed_folds,
+            scroll: (mut scroll_offset, mut horizontal_scroll_offset),
+            block,
+            active,
+        } = view;
+        // text rendering code
+        let text = lines.join("\n");
+        let syntax = self
+            .syntax_set
+            .find_syntax_by_extension("md")
+            .unwrap_or_else(|| self.syntax_set.find_syntax_by_name("Markdown").unwrap());
+        let mut highlighter = HighlightLines::new(syntax, &self.theme);
+        let mut highlighted_lines = Vec::new();
 
         for (row, line) in LinesWithEndings::from(&text).enumerate() {
             let ranges = highlighter
@@ -2941,7 +7909,7 @@ impl App {
                 let text_len = text.chars().count();
                 let mut span_style = Style::default().fg(color);
 
-                if let Some(((start_row, start_col), (end_row, end_col))) = selection_range {
+                if let Some(((start_row, start_col), (end_row, end_col))) = selection {
                     if (row > start_row || (row == start_row && col >= start_col))
                         && (row < end_row || (row == end_row && col < end_col))
                     {
@@ -2955,31 +7923,99 @@ impl App {
             highlighted_lines.push(Line::from(spans));
         }
 
+        // Buffer row of every displayed line; None for lines that only exist on screen
+        let mut display_rows: Vec<Option<usize>> = (0..highlighted_lines.len()).map(Some).collect();
+        if self.render_queries {
+            for (end, lines) in self.query_block_lines(&lines).into_iter().rev() {
+                if end < highlighted_lines.len() {
+                    let count = lines.len();
+                    highlighted_lines.splice(end + 1..end + 1, lines);
+                    display_rows.splice(end + 1..end + 1, std::iter::repeat_n(None, count));
+                }
+            }
+        }
+        // Drop rows hidden by closed folds, with any query results under them
+        let fold_regions = markdown::fold_regions(&lines);
+        let hidden = Self::hidden_rows(lines.len(), &fold_regions, &closed_folds);
+        let mut gutter = Vec::with_capacity(display_rows.len());
+        let mut last_hidden = false;
+        let mut index = 0;
+        while index < display_rows.len() {
+            let is_hidden = match display_rows[index] {
+                Some(row) => hidden.get(row).copied().unwrap_or(false),
+                None => last_hidden,
+            };
+            last_hidden = is_hidden;
+            if is_hidden {
+                display_rows.remove(index);
+                highlighted_lines.remove(index);
+                continue;
+            }
+            let fold = display_rows[index]
+                .and_then(|row| fold_regions.iter().find(|&&(start, _)| start == row));
+            gutter.push(match fold {
+                Some(&(start, end)) if closed_folds.contains(&start) => {
+                    highlighted_lines[index].spans.push(Span::styled(
+                        format!(" ⋯ {} lines", end - start),
+                        Style::default().fg(Color::DarkGray),
+                    ));
+                    "▸ "
+                }
+                Some(_) => "▾ ",
+                None => "  ",
+            });
+            index += 1;
+        }
+        let to_display = |row: usize| {
+            display_rows
+                .iter()
+                .position(|r| r.is_some_and(|r| r >= row))
+                .unwrap_or(display_rows.len())
+        };
+        // A result picked below a ```query block takes the cursor
+        let picked = self
+            .query_row
+            .filter(|&(row, _)| active && row == cursor_row)
+            .and_then(|(row, index)| {
+                let (header, _) = self.query_results_at(row)?;
+                let fence = display_rows.iter().position(|r| *r == Some(row))?;
+                Some(fence + 1 + header + index)
+            })
+            .filter(|&index| index < highlighted_lines.len());
+        if let Some(index) = picked {
+            for span in &mut highlighted_lines[index].spans {
+                span.style = span.style.add_modifier(Modifier::REVERSED);
+            }
+        }
+        let cursor_display = picked.unwrap_or_else(|| to_display(cursor_row));
+
         // Calculate scroll offsets
         let area_height = area.height.saturating_sub(2) as usize;
-        let area_width = area.width.saturating_sub(2) as usize;
+        // Two columns of the inner area hold the fold markers
+        let area_width = area.width.saturating_sub(4) as usize;
         let visible_lines = area_height.min(highlighted_lines.len());
 
-        if cursor_row < self.scroll_offset {
-            self.scroll_offset = cursor_row;
-        } else if cursor_row >= self.scroll_offset + visible_lines {
-            self.scroll_offset = cursor_row - (visible_lines - 1);
+        if cursor_display < scroll_offset {
+            scroll_offset = cursor_display;
+        } else if cursor_display >= scroll_offset + visible_lines {
+            scroll_offset = cursor_display - (visible_lines - 1);
         }
-        self.scroll_offset = self
-            .scroll_offset
-            .min(highlighted_lines.len().saturating_sub(visible_lines));
+        scroll_offset = scroll_offset.min(highlighted_lines.len().saturating_sub(visible_lines));
 
-        if cursor_col < self.horizontal_scroll_offset {
-            self.horizontal_scroll_offset = cursor_col;
-        } else if cursor_col >= self.horizontal_scroll_offset + area_width {
-            self.horizontal_scroll_offset = cursor_col - (area_width - 1);
+        if cursor_col < horizontal_scroll_offset {
+            horizontal_scroll_offset = cursor_col;
+        } else if cursor_col >= horizontal_scroll_offset + area_width {
+            horizontal_scroll_offset = cursor_col - (area_width - 1);
         }
 
-        let start_line = self.scroll_offset;
-        let end_line = (self.scroll_offset + visible_lines).min(highlighted_lines.len());
+        let start_line = scroll_offset;
+        let end_line = (scroll_offset + visible_lines).min(highlighted_lines.len());
         let mut visible_text = Vec::new();
-        for line in highlighted_lines[start_line..end_line].iter() {
-            let mut new_spans = Vec::new();
+        for (line, marker) in highlighted_lines[start_line..end_line]
+            .iter()
+            .zip(&gutter[start_line..end_line])
+        {
+            let mut new_spans = vec![Span::styled(*marker, Style::default().fg(Color::DarkGray))];
             let mut col = 0;
             for span in &line.spans {
                 let text = span.content.as_ref();
@@ -2987,9 +8023,9 @@ impl App {
                 let span_start = col;
                 let span_end = col + span_len;
 
-                if span_end > self.horizontal_scroll_offset {
-                    let start_char = if span_start < self.horizontal_scroll_offset {
-                        self.horizontal_scroll_offset - span_start
+                if span_end > horizontal_scroll_offset {
+                    let start_char = if span_start < horizontal_scroll_offset {
+                        horizontal_scroll_offset - span_start
                     } else {
                         0
                     };
@@ -3005,33 +8041,30 @@ impl App {
         }
 
         if !self.image_full_screen {
-            let block = self.textarea.block().cloned().unwrap_or_default();
-
             let paragraph = Paragraph::new(visible_text)
                 .block(block)
                 .style(self.textarea.style());
 
             f.render_widget(paragraph, area);
 
-            if cursor_row >= self.scroll_offset && cursor_row < self.scroll_offset + visible_lines {
-                let screen_row = (cursor_row - self.scroll_offset) as u16;
-                let screen_col = (cursor_col.saturating_sub(self.horizontal_scroll_offset)) as u16;
+            if active
+                && picked.is_none()
+                && cursor_display >= scroll_offset
+                && cursor_display < scroll_offset + visible_lines
+            {
+                let screen_row = (cursor_display - scroll_offset) as u16;
+                let screen_col = (cursor_col.saturating_sub(horizontal_scroll_offset)) as u16;
                 let max_width = area_width as u16;
                 let cursor_x = screen_col.min(max_width);
 
                 let cursor_area = Rect {
-                    x: area.x + 1 + cursor_x,
+                    x: area.x + 3 + cursor_x,
                     y: area.y + 1 + screen_row,
                     width: 1,
                     height: 1,
                 };
 
-                let line = self
-                    .textarea
-                    .lines()
-                    .get(cursor_row)
-                    .cloned()
-                    .unwrap_or_default();
+                let line = lines.get(cursor_row).cloned().unwrap_or_default();
 
                 let ch: char = line.chars().nth(cursor_col).unwrap_or(' ');
                 let cursor_style = Style::default().bg(Color::White).fg(Color::Black);
@@ -3041,113 +8074,101 @@ impl App {
             }
         }
 
-        if let (Some(picker), Some(dyn_img), Some(image_row)) = (
-            self.image_picker.as_mut(),
-            self.current_image.as_ref(),
-            self.current_image_line,
-        ) {
-            let (image_area, title_str) = if self.image_full_screen {
-                (area, "Image (Full Screen)") // Use the whole editor area
-            } else {
-                // Ensure popup stays within text_area bounds
-                let popup_width = (area.width as f32 * 0.4).max(30.0).min(area.width as f32) as u16;
-
-                let popup_height =
-                    (area.height as f32 * 0.6).max(10.0).min(area.height as f32) as u16;
-
-                let max_y = area.y + area.height.saturating_sub(popup_height);
-
-                let max_x = area.x + area.width.saturating_sub(popup_width);
-
-                let mut popup_y;
-
-                if image_row < self.scroll_offset || image_row >= self.scroll_offset + visible_lines
-                {
-                    popup_y = area.y + 1; // Top of text_area
-                } else {
-                    let screen_row = (image_row - self.scroll_offset) as u16;
-
-                    popup_y = area.y + 1 + screen_row;
-                }
-
-                popup_y = popup_y.min(max_y);
-
-                let popup_area = Rect {
-                    x: max_x, // Right-aligned
-
-                    y: popup_y,
-
-                    width: popup_width,
-
-                    height: popup_height,
-                };
-
-                (popup_area, "Image")
-            };
-
-            if self.image_protocol.is_none() || self.last_image_area != Some(image_area) {
-                self.image_protocol = Some(picker.new_resize_protocol(dyn_img.clone()));
+        Ok(DrawnWindow {
+            scroll: (scroll_offset, horizontal_scroll_offset),
+            display_rows,
+            visible_lines,
+        })
+    }
 
-                self.last_image_area = Some(image_area);
-            }
+    fn window_block(path: &str, active: bool) -> Block<'static> {
+        let color = if active {
+            Color::White
+        } else {
+            Color::DarkGray
+        };
+        Block::default()
+            .borders(Borders::ALL)
+            .title(gettitle!(path))
+            .style(Style::default().fg(color))
+    }
 
-            if let Some(image_protocol) = self.image_protocol.as_mut() {
-                let block = Block::default().borders(Borders::ALL).title(title_str);
-                let image_widget = ratatui_image::StatefulImage::default()
-                    .resize(Resize::Scale(Some(FilterType::Triangle)));
-                if self.image_full_screen {
-                    // Clear text underneath
+    // Right-aligned popup next to `anchor_row`, kept within `area`
+    fn popup_area(
+        area: Rect,
+        anchor_row: usize,
+        scroll_offset: usize,
+        visible_lines: usize,
+    ) -> Rect {
+        let popup_width = (area.width as f32 * 0.4).max(30.0).min(area.width as f32) as u16;
+        let popup_height = (area.height as f32 * 0.6).max(10.0).min(area.height as f32) as u16;
+        let max_y = area.y + area.height.saturating_sub(popup_height);
+        let max_x = area.x + area.width.saturating_sub(popup_width);
+
+        let popup_y = if anchor_row < scroll_offset || anchor_row >= scroll_offset + visible_lines {
+            area.y + 1 // Top of text_area
+        } else {
+            area.y + 1 + (anchor_row - scroll_offset) as u16
+        };
 
-                    f.render_widget(ratatui::widgets::Clear, image_area);
-                }
+        Rect {
+            x: max_x,
+            y: popup_y.min(max_y),
+            width: popup_width,
+            height: popup_height,
+        }
+    }
 
-                f.render_widget(block, image_area);
+    fn highlight_lines(&self, lines: &[String]) -> Result<Vec<Line<'static>>, EditorError> {
+        let syntax = self
+            .syntax_set
+            .find_syntax_by_extension("md")
+            .unwrap_or_else(|| self.syntax_set.find_syntax_by_name("Markdown").unwrap());
+        let mut highlighter = HighlightLines::new(syntax, &self.theme);
+        let mut highlighted = Vec::new();
+        for line in lines {
+            let line = format!("{}\n", line);
+            let ranges = highlighter
+                .highlight_line(&line, &self.syntax_set)
+                .map_err(|e| EditorError::SyntaxHighlighting(e.to_string()))?;
+            let spans: Vec<Span<'static>> = ranges
+                .into_iter()
+                .map(|(style, text)| {
+                    let color =
+                        Color::Rgb(style.foreground.r, style.foreground.g, style.foreground.b);
+                    Span::styled(
+                        text.trim_end_matches('\n').to_string(),
+                        Style::default().fg(color),
+                    )
+                })
+                .collect();
+            highlighted.push(Line::from(spans));
+        }
+        Ok(highlighted)
+    }
 
-                let margin = Margin::new(1, 1);
+    fn process_template_command(&mut self, template_path_str: &str) -> Result<(), EditorError> {
+        let title = gettitle!(&self.file_path);
+        let processed_content = self.fill_template(template_path_str, &title)?;
 
-                f.render_stateful_widget(image_widget, image_area.inner(margin), image_protocol);
+        // Get current buffer content.
+        let current_content = self.textarea.lines().join("\n");
 
-                if let Some(Err(e)) = image_protocol.last_encoding_result() {
-                    self.status = format!("Image encoding error: {}", e);
-                }
-            }
-        }
+        // Combine template and current content.
+        let new_content = format!("{}\n{}", processed_content, current_content);
+        let new_lines: Vec<String> = new_content.lines().map(|s| s.to_string()).collect();
 
-        // Render completion popup if active
-        if self.completion_state.active && !self.completion_state.suggestions.is_empty() {
-            let items: Vec<ListItem> = self
-                .completion_state
-                .suggestions
-                .iter()
-                .map(|s| ListItem::new(format!("{s}{}", " ".repeat(50))))
-                .collect();
-            let list = List::new(items)
-                .block(
-                    Block::default()
-                        .borders(Borders::ALL)
-                        .title(match self.completion_state.completion_type {
-                            CompletionType::File => "Files",
-                            CompletionType::Tag => "Tags",
-                            CompletionType::Variable => "Variable",
-                            CompletionType::None => "",
-                        })
-                        .style(Style::default().fg(Color::White).bg(Color::Black)),
-                )
-                .highlight_style(Style::default().bg(Color::White).fg(Color::Black));
-            let popup_width = 40;
-            let popup_height = (self.completion_state.suggestions.len().min(5) + 2) as u16;
-            let popup_area = Rect {
-                x: area.x + area.width.saturating_sub(popup_width),
-                y: area.y + 1,
-                width: popup_width,
-                height: popup_height,
-            };
-            f.render_stateful_widget(list, popup_area, &mut self.completion_state.list_state);
-        }
+        // Update the textarea.
+        let mut new_textarea = TextArea::new(new_lines);
+        set_textarea_delafult_style!(new_textarea);
+        self.textarea = new_textarea;
 
+        self.status = "Template processed and inserted.".to_string();
         Ok(())
     }
-    fn process_template_command(&mut self, template_path_str: &str) -> Result<(), EditorError> {
+
+    // Template text with {{date}}, {{time}} and {{title}} filled in
+    fn fill_template(&self, template_path_str: &str, title: &str) -> Result<String, EditorError> {
         // 1. Define the template path. This should be a configurable path.
         let template_path = Path::new(&self.base_dir).join(template_path_str);
 
@@ -3159,37 +8180,16 @@ impl App {
             ))
         })?;
 
-        // 3. Get current date, time, and title.
+        // 3. Get current date and time.
         let now = Local::now();
         let current_date = now.format("%Y-%m-%d").to_string();
         let current_time = now.format("%H:%M:%S").to_string();
-        let title = gettitle!(&self.file_path);
-        // Path::new(&self.file_path)
-        // .file_stem()
-        // .and_then(|s| s.to_str())
-        // .unwrap_or_default()
-        // .to_string();
 
         // 4. Replace template variables.
-        let processed_content = template_content
+        Ok(template_content
             .replace("{{date}}", &current_date)
             .replace("{{time}}", &current_time)
-            .replace("{{title}}", &title);
-
-        // 5. Get current buffer content.
-        let current_content = self.textarea.lines().join("\n");
-
-        // 6. Combine template and current content.
-        let new_content = format!("{}\n{}", processed_content, current_content);
-        let new_lines: Vec<String> = new_content.lines().map(|s| s.to_string()).collect();
-
-        // 7. Update the textarea.
-        let mut new_textarea = TextArea::new(new_lines);
-        set_textarea_delafult_style!(new_textarea);
-        self.textarea = new_textarea;
-
-        self.status = "Template processed and inserted.".to_string();
-        Ok(())
+            .replace("{{title}}", title))
     }
 
     fn extract_image_paths(&self) -> Vec<(String, usize)> {
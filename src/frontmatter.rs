@@ -0,0 +1,453 @@
+use regex::Regex;
+
+// YAML frontmatter between the leading `---` lines of a note. Only the flat
+// subset used for note properties is understood; anything else (nested maps,
+// multi-line strings, comments) is kept as raw lines and written back unchanged.
+
+#[derive(PartialEq, Clone, Debug)]
+pub enum PropertyValue {
+    Text(String),
+    List(Vec<String>),
+    Date(String),
+    Number(String),
+    Checkbox(bool),
+    Raw(Vec<String>), // Lines of the entry as written, its `key:` line included
+}
+
+#[derive(Clone, Debug)]
+pub struct Property {
+    pub key: String, // Empty for comments and other lines that are not `key: value`
+    pub value: PropertyValue,
+}
+
+impl Property {
+    // Rename, keeping a raw entry's lines as they are apart from the key
+    pub fn rename(&mut self, key: String) {
+        if let PropertyValue::Raw(lines) = &mut self.value
+            && let Some(first) = lines.first_mut()
+            && let Some((_, rest)) = first.split_once(':')
+        {
+            *first = format!("{}:{}", key, rest);
+        }
+        self.key = key;
+    }
+}
+
+fn is_date(value: &str) -> bool {
+    Regex::new(r"^\d{4}-\d{2}-\d{2}([ T]\d{2}:\d{2}(:\d{2})?)?$")
+        .unwrap()
+        .is_match(value)
+}
+
+// `inf` and `nan` parse as f64 but are text in a note
+fn is_number(value: &str) -> bool {
+    value.parse::<f64>().is_ok_and(f64::is_finite)
+}
+
+// A value without its trailing ` # comment`; a `#` inside quotes or a word is kept
+fn strip_comment(value: &str) -> &str {
+    let value = value.trim();
+    let quoted_end = match value.chars().next() {
+        Some(q @ ('"' | '\'')) => value[1..].find(q).map_or(0, |i| i + 2),
+        _ => 0,
+    };
+    match Regex::new(r"\s#").unwrap().find(&value[quoted_end..]) {
+        Some(comment) => value[..quoted_end + comment.start()].trim_end(),
+        None => value,
+    }
+}
+
+fn unquote(value: &str) -> (String, bool) {
+    let value = value.trim();
+    if value.len() >= 2
+        && ((value.starts_with('"') && value.ends_with('"'))
+            || (value.starts_with('\'') && value.ends_with('\'')))
+    {
+        let inner = &value[1..value.len() - 1];
+        let inner = if value.starts_with('"') {
+            inner.replace("\\\"", "\"").replace("\\\\", "\\")
+        } else {
+            inner.replace("''", "'")
+        };
+        (inner, true)
+    } else {
+        (value.to_string(), false)
+    }
+}
+
+fn quote(value: &str) -> String {
+    let plain_unsafe = value.is_empty()
+        || value != value.trim()
+        || value.contains(": ")
+        || value.contains(" #")
+        || value.ends_with(':')
+        || value.starts_with(|c: char| "-?:,[]{}#&*!|>'\"%@`".contains(c))
+        || ["true", "false", "yes", "no", "null", "~"].contains(&value.to_lowercase().as_str())
+        || value.parse::<f64>().is_ok()
+        || is_date(value);
+    if plain_unsafe {
+        format!("\"{}\"", value.replace('\\', "\\\\").replace('"', "\\\""))
+    } else {
+        value.to_string()
+    }
+}
+
+impl PropertyValue {
+    fn infer(raw: &str) -> PropertyValue {
+        let (value, quoted) = unquote(raw);
+        if quoted {
+            return PropertyValue::Text(value);
+        }
+        match value.to_lowercase().as_str() {
+            "true" => return PropertyValue::Checkbox(true),
+            "false" => return PropertyValue::Checkbox(false),
+            _ => {}
+        }
+        if is_number(&value) {
+            PropertyValue::Number(value)
+        } else if is_date(&value) {
+            PropertyValue::Date(value)
+        } else {
+            PropertyValue::Text(value)
+        }
+    }
+
+    // Empty value of the type a key has in `files.metadata`
+    pub fn empty_like(value: &serde_json::Value) -> PropertyValue {
+        match value {
+            serde_json::Value::Array(_) => PropertyValue::List(Vec::new()),
+            serde_json::Value::Bool(_) => PropertyValue::Checkbox(false),
+            serde_json::Value::Number(_) => PropertyValue::Number(String::new()),
+            serde_json::Value::String(s) if is_date(s) => PropertyValue::Date(String::new()),
+            _ => PropertyValue::Text(String::new()),
+        }
+    }
+
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            PropertyValue::Text(_) => "text",
+            PropertyValue::List(_) => "list",
+            PropertyValue::Date(_) => "date",
+            PropertyValue::Number(_) => "number",
+            PropertyValue::Checkbox(_) => "checkbox",
+            PropertyValue::Raw(_) => "yaml",
+        }
+    }
+
+    pub fn display(&self) -> String {
+        match self {
+            PropertyValue::Text(s) | PropertyValue::Date(s) | PropertyValue::Number(s) => s.clone(),
+            PropertyValue::List(items) => items.join(", "),
+            PropertyValue::Checkbox(checked) => if *checked { "[x]" } else { "[ ]" }.to_string(),
+            PropertyValue::Raw(lines) => {
+                lines.iter().map(|l| l.trim()).collect::<Vec<_>>().join(" ")
+            }
+        }
+    }
+
+    // Same shape markdown-scanner stores in `files.metadata`
+    pub fn to_json(&self) -> serde_json::Value {
+        match self {
+            PropertyValue::Text(s) | PropertyValue::Date(s) => serde_json::Value::String(s.clone()),
+            PropertyValue::Number(s) => s
+                .parse::<i64>()
+                .map(serde_json::Value::from)
+                .or_else(|_| s.parse::<f64>().map(serde_json::Value::from))
+                .unwrap_or(serde_json::Value::Null),
+            PropertyValue::Checkbox(checked) => serde_json::Value::Bool(*checked),
+            PropertyValue::List(items) => serde_json::Value::from(items.clone()),
+            PropertyValue::Raw(_) => serde_json::Value::Null,
+        }
+    }
+
+    // Text used to pre-fill the edit field
+    pub fn edit_text(&self) -> String {
+        match self {
+            PropertyValue::Checkbox(checked) => checked.to_string(),
+            other => other.display(),
+        }
+    }
+
+    // Parse edited text as a value of the same type
+    pub fn with_input(&self, input: &str) -> Result<PropertyValue, String> {
+        let input = input.trim();
+        match self {
+            PropertyValue::Text(_) | PropertyValue::Raw(_) => {
+                Ok(PropertyValue::Text(input.to_string()))
+            }
+            PropertyValue::List(_) => Ok(PropertyValue::List(
+                input
+                    .split(',')
+                    .map(|s| s.trim().to_string())
+                    .filter(|s| !s.is_empty())
+                    .collect(),
+            )),
+            PropertyValue::Date(_) if input.is_empty() || is_date(input) => {
+                Ok(PropertyValue::Date(input.to_string()))
+            }
+            PropertyValue::Date(_) => Err(format!("Not a date (YYYY-MM-DD): {}", input)),
+            PropertyValue::Number(_) if is_number(input) => {
+                Ok(PropertyValue::Number(input.to_string()))
+            }
+            PropertyValue::Number(_) => Err(format!("Not a number: {}", input)),
+            PropertyValue::Checkbox(_) => Ok(PropertyValue::Checkbox(matches!(
+                input.to_lowercase().as_str(),
+                "true" | "x" | "yes" | "y" | "1"
+            ))),
+        }
+    }
+
+    // Convert to the next type in text → list → date → number → checkbox
+    pub fn next_type(&self) -> PropertyValue {
+        let text = match self {
+            PropertyValue::Checkbox(_) => String::new(),
+            other => other.display(),
+        };
+        match self {
+            PropertyValue::Text(_) | PropertyValue::Raw(_) => PropertyValue::List(
+                text.split(',')
+                    .map(|s| s.trim().to_string())
+                    .filter(|s| !s.is_empty())
+                    .collect(),
+            ),
+            PropertyValue::List(_) => {
+                PropertyValue::Date(if is_date(&text) { text } else { String::new() })
+            }
+            PropertyValue::Date(_) => PropertyValue::Number(if is_number(&text) {
+                text
+            } else {
+                "0".to_string()
+            }),
+            PropertyValue::Number(_) => PropertyValue::Checkbox(false),
+            PropertyValue::Checkbox(_) => PropertyValue::Text(text),
+        }
+    }
+}
+
+// Rows of the opening and closing `---` when the buffer starts with frontmatter
+pub fn block_range(lines: &[String]) -> Option<(usize, usize)> {
+    if lines.first().map(|l| l.trim_end()) != Some("---") {
+        return None;
+    }
+    lines
+        .iter()
+        .enumerate()
+        .skip(1)
+        .find(|(_, l)| l.trim_end() == "---" || l.trim_end() == "...")
+        .map(|(row, _)| (0, row))
+}
+
+// Properties with the rows each one spans, as (first row, row after the last)
+fn parse_entries(lines: &[String]) -> Vec<(Property, usize, usize)> {
+    let Some((start, end)) = block_range(lines) else {
+        return Vec::new();
+    };
+    let key_re = Regex::new(r"^([^\s#-][^:]*):(?:\s+(.*))?$").unwrap();
+    let item_re = Regex::new(r"^\s*-\s+(.*)$").unwrap();
+    // YAML allows a block list under a key without indenting it
+    let is_child =
+        |line: &String| line.starts_with([' ', '\t']) || line == "-" || line.starts_with("- ");
+
+    let mut entries = Vec::new();
+    let mut row = start + 1;
+    while row < end {
+        let first = row;
+        let line = &lines[row];
+        row += 1;
+        let Some(cap) = key_re.captures(line) else {
+            let property = Property {
+                key: String::new(),
+                value: PropertyValue::Raw(vec![line.clone()]),
+            };
+            entries.push((property, first, row));
+            continue;
+        };
+        let key = cap[1].trim().to_string();
+        let rest = cap.get(2).map_or("", |m| strip_comment(m.as_str()));
+
+        // Indented lines belong to this key: a block list, something nested or
+        // the text of a `|`/`>` block. Blank lines count when more follow.
+        let mut children = Vec::new();
+        let mut next = row;
+        while next < end {
+            if is_child(&lines[next]) {
+                children.extend(lines[row..=next].iter().cloned());
+                row = next + 1;
+            } else if !lines[next].trim().is_empty() {
+                break;
+            }
+            next += 1;
+        }
+
+        let raw = || {
+            let mut all = vec![line.clone()];
+            all.extend(children.iter().cloned());
+            PropertyValue::Raw(all)
+        };
+        let value = if rest.is_empty() {
+            if children.is_empty() {
+                PropertyValue::Text(String::new())
+            } else if children.iter().all(|l| item_re.is_match(l)) {
+                PropertyValue::List(
+                    children
+                        .iter()
+                        .map(|l| unquote(strip_comment(&item_re.captures(l).unwrap()[1])).0)
+                        .collect(),
+                )
+            } else {
+                raw()
+            }
+        } else if !children.is_empty() || rest.starts_with(['|', '>', '{', '&', '*', '!', '#']) {
+            raw()
+        } else if rest.starts_with('[') && rest.ends_with(']') {
+            PropertyValue::List(
+                rest[1..rest.len() - 1]
+                    .split(',')
+                    .map(|s| unquote(s).0)
+                    .filter(|s| !s.is_empty())
+                    .collect(),
+            )
+        } else {
+            PropertyValue::infer(rest)
+        };
+        entries.push((Property { key, value }, first, row));
+    }
+    entries
+}
+
+pub fn parse(lines: &[String]) -> Vec<Property> {
+    parse_entries(lines)
+        .into_iter()
+        .map(|(property, _, _)| property)
+        .collect()
+}
+
+fn entry_lines(property: &Property) -> Vec<String> {
+    let key = &property.key;
+    match &property.value {
+        PropertyValue::Text(s) => vec![format!("{}: {}", key, quote(s))],
+        PropertyValue::Date(s) | PropertyValue::Number(s) if s.is_empty() => {
+            vec![format!("{}:", key)]
+        }
+        PropertyValue::Date(s) | PropertyValue::Number(s) => vec![format!("{}: {}", key, s)],
+        PropertyValue::Checkbox(checked) => vec![format!("{}: {}", key, checked)],
+        PropertyValue::List(items) if items.is_empty() => vec![format!("{}: []", key)],
+        PropertyValue::List(items) => {
+            let mut lines = vec![format!("{}:", key)];
+            lines.extend(items.iter().map(|item| format!("  - {}", quote(item))));
+            lines
+        }
+        PropertyValue::Raw(lines) => lines.clone(),
+    }
+}
+
+pub fn to_yaml(properties: &[Property]) -> Vec<String> {
+    let mut lines = vec!["---".to_string()];
+    lines.extend(properties.iter().flat_map(entry_lines));
+    lines.push("---".to_string());
+    lines
+}
+
+// The frontmatter of `lines` with `properties` in its place. Entries that did
+// not change keep their lines as written, comments and formatting included.
+pub fn rewrite(lines: &[String], properties: &[Property]) -> Vec<String> {
+    if properties.is_empty() {
+        return Vec::new();
+    }
+    let mut entries = parse_entries(lines);
+    let mut yaml = vec!["---".to_string()];
+    for property in properties {
+        let unchanged = entries
+            .iter()
+            .position(|(old, _, _)| old.key == property.key && old.value == property.value);
+        match unchanged {
+            Some(index) => {
+                let (_, start, end) = entries.remove(index);
+                yaml.extend(lines[start..end].iter().cloned());
+            }
+            None => yaml.extend(entry_lines(property)),
+        }
+    }
+    let close = block_range(lines).map_or("---".to_string(), |(_, close)| lines[close].clone());
+    yaml.push(close);
+    yaml
+}
+
+// Values a property contributes to a merged list; None for raw YAML
+fn items(value: &PropertyValue) -> Option<Vec<String>> {
+    match value {
+        PropertyValue::List(items) => Some(items.clone()),
+        PropertyValue::Text(s) | PropertyValue::Date(s) | PropertyValue::Number(s) => {
+            Some(if s.is_empty() {
+                Vec::new()
+            } else {
+                vec![s.clone()]
+            })
+        }
+        PropertyValue::Checkbox(checked) => Some(vec![checked.to_string()]),
+        PropertyValue::Raw(_) => None,
+    }
+}
+
+// Add the properties of another note to `merged`. A key already there with a
+// different value becomes a list of both; the properties that cannot be
+// combined that way (raw YAML under a taken key) are returned.
+pub fn merge(merged: &mut Vec<Property>, properties: Vec<Property>) -> Vec<Property> {
+    let mut leftover = Vec::new();
+    for property in properties {
+        let Some(existing) = merged
+            .iter_mut()
+            .find(|p| !p.key.is_empty() && p.key == property.key)
+        else {
+            merged.push(property);
+            continue;
+        };
+        if existing.value == property.value {
+            continue;
+        }
+        match (items(&existing.value), items(&property.value)) {
+            (Some(mut combined), Some(more)) => {
+                for item in more {
+                    if !combined.contains(&item) {
+                        combined.push(item);
+                    }
+                }
+                existing.value = PropertyValue::List(combined);
+            }
+            _ => leftover.push(property),
+        }
+    }
+    leftover
+}
+
+// Set `key` from edited text, keeping the type it already has in the note or
+// `default` for a new key. Only that key's lines change; returns the rewritten
+// lines of the whole note.
+pub fn set_property(
+    lines: &[String],
+    key: &str,
+    input: &str,
+    default: PropertyValue,
+) -> Result<(Vec<String>, PropertyValue), String> {
+    let entry = parse_entries(lines)
+        .into_iter()
+        .find(|(property, _, _)| property.key == key);
+    let (old, start, end) = match (entry, block_range(lines)) {
+        (Some((property, start, end)), _) => (property.value, start, end),
+        (None, Some((_, close))) => (default, close, close),
+        (None, None) => (default, 0, 0),
+    };
+    let value = old.with_input(input)?;
+    let mut new_entry = entry_lines(&Property {
+        key: key.to_string(),
+        value: value.clone(),
+    });
+    if block_range(lines).is_none() {
+        new_entry.insert(0, "---".to_string());
+        new_entry.push("---".to_string());
+    }
+    let mut new_lines = lines[..start].to_vec();
+    new_lines.extend(new_entry);
+    new_lines.extend(lines[end..].iter().cloned());
+    Ok((new_lines, value))
+}
@@ -13,6 +13,15 @@ use std::process::Command as ProcessCommand;
 
 mod app;
 mod error;
+mod frontmatter;
+mod markdown;
+mod query;
+mod registers;
+mod table;
+mod tasks;
+mod undo;
+mod vim;
+mod window;
 
 use app::App;
 use error::EditorError;
@@ -163,6 +172,10 @@ fn main() -> Result<(), EditorError> {
                             ratatui::crossterm::event::KeyCode::Backspace
                         }
                         crossterm::event::KeyCode::Esc => ratatui::crossterm::event::KeyCode::Esc,
+                        crossterm::event::KeyCode::Tab => ratatui::crossterm::event::KeyCode::Tab,
+                        crossterm::event::KeyCode::BackTab => {
+                            ratatui::crossterm::event::KeyCode::BackTab
+                        }
                         crossterm::event::KeyCode::Left => ratatui::crossterm::event::KeyCode::Left,
                         crossterm::event::KeyCode::Right => {
                             ratatui::crossterm::event::KeyCode::Right
@@ -202,6 +215,7 @@ fn main() -> Result<(), EditorError> {
             _ => {}
         }
     }
+    app.save_session();
 
     Ok(())
 }
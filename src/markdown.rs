@@ -0,0 +1,275 @@
+use regex::Regex;
+
+// Line-level Markdown structure used by the editing commands
+
+// Indentation added or removed by Tab / Shift-Tab on a list item
+pub const LIST_INDENT: &str = "    ";
+
+pub enum ListContinuation {
+    None,
+    Continue(String, usize), // Prefix for the new line, width of the marker on this one
+    End,                     // Empty item: Enter removes the marker and ends the list
+}
+
+fn ordered_regex() -> Regex {
+    Regex::new(r"^(\s*)(\d+)([.)])(\s+)(\[.\]\s+)?(.*)$").unwrap()
+}
+
+fn bullet_regex() -> Regex {
+    Regex::new(r"^(\s*)([-*+])(\s+)(\[.\]\s+)?(.*)$").unwrap()
+}
+
+pub fn is_list_item(line: &str) -> bool {
+    ordered_regex().is_match(line) || bullet_regex().is_match(line)
+}
+
+// `---`, `* * *`, `___` and the like
+pub fn is_horizontal_rule(line: &str) -> bool {
+    Regex::new(r"^\s*(?:(?:-\s*){3,}|(?:\*\s*){3,}|(?:_\s*){3,})$")
+        .unwrap()
+        .is_match(line)
+}
+
+// What Enter at the end of `line` should do in Insert mode
+pub fn list_continuation(line: &str) -> ListContinuation {
+    if is_horizontal_rule(line) {
+        return ListContinuation::None;
+    }
+    let continuation = |rest: regex::Match, prefix: String| {
+        if rest.as_str().trim().is_empty() {
+            ListContinuation::End
+        } else {
+            ListContinuation::Continue(prefix, line[..rest.start()].chars().count())
+        }
+    };
+    if let Some(cap) = ordered_regex().captures(line) {
+        let number = cap[2].parse::<u64>().unwrap_or(0) + 1;
+        let task = if cap.get(5).is_some() { "[ ] " } else { "" };
+        return continuation(
+            cap.get(6).unwrap(),
+            format!("{}{}{}{}{}", &cap[1], number, &cap[3], &cap[4], task),
+        );
+    }
+    if let Some(cap) = bullet_regex().captures(line) {
+        let task = if cap.get(4).is_some() { "[ ] " } else { "" };
+        return continuation(
+            cap.get(5).unwrap(),
+            format!("{}{}{}{}", &cap[1], &cap[2], &cap[3], task),
+        );
+    }
+    if let Some(cap) = Regex::new(r"^(\s*(?:>\s?)+)(.*)$").unwrap().captures(line) {
+        return continuation(cap.get(2).unwrap(), cap[1].to_string());
+    }
+    ListContinuation::None
+}
+
+// (new line, change in length at the start) for Tab / Shift-Tab on a list item
+pub fn indent_list_item(line: &str, outdent: bool) -> (String, isize) {
+    if !outdent {
+        return (
+            format!("{}{}", LIST_INDENT, line),
+            LIST_INDENT.len() as isize,
+        );
+    }
+    let removed = if line.starts_with('\t') {
+        1
+    } else {
+        line.chars()
+            .take(LIST_INDENT.len())
+            .take_while(|c| *c == ' ')
+            .count()
+    };
+    (line[removed..].to_string(), -(removed as isize))
+}
+
+#[derive(Clone, Debug)]
+pub struct Heading {
+    pub line: usize,
+    pub level: usize,
+    pub title: String,
+}
+
+// `#` headings outside frontmatter and fenced code blocks
+pub fn headings(lines: &[String]) -> Vec<Heading> {
+    let heading_re = Regex::new(r"^(#{1,6})\s+(.*?)(?:\s+#+)?\s*$").unwrap();
+    let body_start = crate::frontmatter::block_range(lines)
+        .map(|(_, close)| close + 1)
+        .unwrap_or(0);
+    let mut headings = Vec::new();
+    let mut in_code = false;
+    for (line, text) in lines.iter().enumerate().skip(body_start) {
+        if text.trim_start().starts_with("```") {
+            in_code = !in_code;
+            continue;
+        }
+        if in_code {
+            continue;
+        }
+        if let Some(cap) = heading_re.captures(text) {
+            headings.push(Heading {
+                line,
+                level: cap[1].len(),
+                title: cap[2].to_string(),
+            });
+        }
+    }
+    headings
+}
+
+// Index of the heading whose section contains `row`
+pub fn heading_at(headings: &[Heading], row: usize) -> Option<usize> {
+    headings.iter().rposition(|h| h.line <= row)
+}
+
+// End (exclusive) of the section of headings[index], including its subheadings
+pub fn section_end(headings: &[Heading], index: usize, line_count: usize) -> usize {
+    let level = headings[index].level;
+    headings[index + 1..]
+        .iter()
+        .find(|h| h.level <= level)
+        .map(|h| h.line)
+        .unwrap_or(line_count)
+}
+
+// Rows start..=end that `za` can fold: frontmatter, fenced code blocks, heading
+// sections and list items with nested lines
+pub fn fold_regions(lines: &[String]) -> Vec<(usize, usize)> {
+    let mut regions = Vec::new();
+    let body_start = match crate::frontmatter::block_range(lines) {
+        Some((start, close)) => {
+            regions.push((start, close));
+            close + 1
+        }
+        None => 0,
+    };
+
+    let mut fences = Vec::new();
+    let mut fence_start = None;
+    for (row, text) in lines.iter().enumerate().skip(body_start) {
+        if !text.trim_start().starts_with("```") {
+            continue;
+        }
+        match fence_start.take() {
+            Some(start) => fences.push((start, row)),
+            None => fence_start = Some(row),
+        }
+    }
+
+    let headings = headings(lines);
+    for index in 0..headings.len() {
+        let start = headings[index].line;
+        let end = section_end(&headings, index, lines.len());
+        // Blank lines before the next heading stay visible
+        let last = (start + 1..end)
+            .rev()
+            .find(|&row| !lines[row].trim().is_empty())
+            .unwrap_or(start);
+        if last > start {
+            regions.push((start, last));
+        }
+    }
+
+    let (ordered_re, bullet_re) = (ordered_regex(), bullet_regex());
+    let indent = |line: &str| line.len() - line.trim_start().len();
+    for (row, text) in lines.iter().enumerate().skip(body_start) {
+        let in_code = fences
+            .iter()
+            .any(|&(start, end)| row >= start && row <= end);
+        if in_code || !(ordered_re.is_match(text) || bullet_re.is_match(text)) {
+            continue;
+        }
+        let last = lines[row + 1..]
+            .iter()
+            .take_while(|l| !l.trim().is_empty() && indent(l) > indent(text))
+            .count();
+        if last > 0 {
+            regions.push((row, row + last));
+        }
+    }
+    regions.extend(fences);
+    regions.sort();
+    regions
+}
+
+// A heading turned into a note name: characters not allowed in file names or
+// wikilinks are dropped
+pub fn note_name(title: &str) -> String {
+    title
+        .chars()
+        .filter(|c| !"/\\:*?\"<>|#^[]".contains(*c))
+        .collect::<String>()
+        .trim()
+        .to_string()
+}
+
+// Split out the sections of `level` headings as (note name, body). The rest of
+// the note is returned with a `- [[name]]` line in place of each section.
+pub fn split_sections(lines: &[String], level: usize) -> (Vec<String>, Vec<(String, Vec<String>)>) {
+    let headings = headings(lines);
+    let mut index = Vec::new();
+    let mut sections = Vec::new();
+    let mut row = 0;
+    for (i, heading) in headings.iter().enumerate() {
+        if heading.level != level || heading.line < row {
+            continue;
+        }
+        let end = section_end(&headings, i, lines.len());
+        index.extend(lines[row..heading.line].iter().cloned());
+        let name = note_name(&heading.title);
+        let body: Vec<String> = lines[heading.line + 1..end].to_vec();
+        let first = body
+            .iter()
+            .position(|l| !l.trim().is_empty())
+            .unwrap_or(body.len());
+        let last = body
+            .iter()
+            .rposition(|l| !l.trim().is_empty())
+            .map_or(first, |l| l + 1);
+        index.push(format!("- [[{}]]", name));
+        // Keep the blank line that separated this section from the next
+        if lines[..end].last().is_some_and(|l| l.trim().is_empty()) {
+            index.push(String::new());
+        }
+        sections.push((name, body[first..last].to_vec()));
+        row = end;
+    }
+    index.extend(lines[row..].iter().cloned());
+    (index, sections)
+}
+
+// Note body one heading level deeper, without its frontmatter, to go under a new heading
+pub fn demote_headings(lines: &[String]) -> Vec<String> {
+    let body_start = crate::frontmatter::block_range(lines)
+        .map(|(_, close)| close + 1)
+        .unwrap_or(0);
+    let mut demoted = lines.to_vec();
+    for heading in headings(lines) {
+        if heading.level < 6 {
+            demoted[heading.line].insert(0, '#');
+        }
+    }
+    demoted.drain(..body_start);
+    demoted
+}
+
+// Point `[[from]]`, `[[from|alias]]` and `[[from#heading]]` at `[[to#heading]]`,
+// where a link to the whole note gets `default_heading`
+pub fn retarget_wikilinks(line: &str, from: &str, to: &str, default_heading: &str) -> String {
+    let link_re = Regex::new(r"\[\[([^\]|#]*)(#[^\]|]*)?(\|[^\]]*)?\]\]").unwrap();
+    link_re
+        .replace_all(line, |cap: &regex::Captures| {
+            let target = cap[1].trim();
+            let target = target.strip_suffix(".md").unwrap_or(target);
+            let name = target.rsplit('/').next().unwrap_or(target);
+            if !name.eq_ignore_ascii_case(from) {
+                return cap[0].to_string();
+            }
+            let heading = cap
+                .get(2)
+                .map(|h| h.as_str().to_string())
+                .unwrap_or_else(|| format!("#{}", default_heading));
+            let alias = cap.get(3).map_or("", |a| a.as_str());
+            format!("[[{}{}{}]]", to, heading, alias)
+        })
+        .to_string()
+}
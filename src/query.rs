@@ -0,0 +1,366 @@
+use serde_json::Value;
+use std::cmp::Ordering;
+
+// Dataview-like queries written in ```query blocks, e.g.
+// LIST FROM #project WHERE status = "active" SORT modified DESC LIMIT 10
+// TABLE status, due FROM "Projects" WHERE priority >= 2
+
+#[derive(PartialEq, Clone, Debug)]
+pub enum QueryOutput {
+    List,
+    Table,
+}
+
+#[derive(PartialEq, Clone, Debug)]
+pub enum QuerySource {
+    All,
+    Tag(String),
+    Folder(String),
+}
+
+#[derive(Clone, Debug)]
+pub struct Condition {
+    field: String,
+    op: String,
+    value: String,
+}
+
+#[derive(Clone, Debug)]
+pub struct NoteQuery {
+    pub output: QueryOutput,
+    pub fields: Vec<String>,
+    pub source: QuerySource,
+    conditions: Vec<Condition>,
+    sort: Option<(String, bool)>, // (field, descending)
+    limit: Option<usize>,
+}
+
+// A note as seen by a query: columns from `files`, `file_tags` and `files.metadata`
+#[derive(Clone, Debug)]
+pub struct NoteRecord {
+    pub id: i64,
+    pub path: String,
+    pub name: String,
+    pub modified: String,
+    pub tags: Vec<String>,
+    pub metadata: Value,
+}
+
+#[derive(PartialEq, Clone, Debug)]
+enum Token {
+    Word(String),
+    Str(String),
+    Op(String),
+    Comma,
+}
+
+fn tokenize(text: &str) -> Result<Vec<Token>, String> {
+    let mut tokens = Vec::new();
+    let mut chars = text.chars().peekable();
+    while let Some(&c) = chars.peek() {
+        if c.is_whitespace() {
+            chars.next();
+        } else if c == ',' {
+            chars.next();
+            tokens.push(Token::Comma);
+        } else if c == '"' || c == '\'' {
+            chars.next();
+            let mut value = String::new();
+            loop {
+                match chars.next() {
+                    Some(ch) if ch == c => break,
+                    Some(ch) => value.push(ch),
+                    None => return Err("Unterminated string".to_string()),
+                }
+            }
+            tokens.push(Token::Str(value));
+        } else if "=!<>".contains(c) {
+            chars.next();
+            let mut op = c.to_string();
+            if chars.peek() == Some(&'=') {
+                chars.next();
+                op.push('=');
+            }
+            if op == "!" {
+                return Err("Expected !=".to_string());
+            }
+            tokens.push(Token::Op(op));
+        } else {
+            let mut word = String::new();
+            while let Some(&ch) = chars.peek() {
+                if ch.is_whitespace() || ch == ',' || "=!<>\"'".contains(ch) {
+                    break;
+                }
+                word.push(ch);
+                chars.next();
+            }
+            tokens.push(Token::Word(word));
+        }
+    }
+    Ok(tokens)
+}
+
+fn is_keyword(token: Option<&Token>, keyword: &str) -> bool {
+    matches!(token, Some(Token::Word(w)) if w.eq_ignore_ascii_case(keyword))
+}
+
+fn value_of(token: Option<Token>) -> Result<String, String> {
+    match token {
+        Some(Token::Word(w)) | Some(Token::Str(w)) => Ok(w),
+        other => Err(format!("Expected a value, found {:?}", other)),
+    }
+}
+
+pub fn parse(text: &str) -> Result<NoteQuery, String> {
+    let mut tokens = tokenize(text)?.into_iter().peekable();
+
+    let output = match tokens.next() {
+        Some(Token::Word(w)) if w.eq_ignore_ascii_case("list") => QueryOutput::List,
+        Some(Token::Word(w)) if w.eq_ignore_ascii_case("table") => QueryOutput::Table,
+        _ => return Err("Query must start with LIST or TABLE".to_string()),
+    };
+
+    let mut query = NoteQuery {
+        output,
+        fields: Vec::new(),
+        source: QuerySource::All,
+        conditions: Vec::new(),
+        sort: None,
+        limit: None,
+    };
+
+    if query.output == QueryOutput::Table {
+        while let Some(token) = tokens.peek() {
+            if ["from", "where", "sort", "limit"]
+                .iter()
+                .any(|k| is_keyword(Some(token), k))
+            {
+                break;
+            }
+            match tokens.next() {
+                Some(Token::Word(w)) | Some(Token::Str(w)) => query.fields.push(w),
+                Some(Token::Comma) => {}
+                other => return Err(format!("Unexpected {:?} in TABLE fields", other)),
+            }
+        }
+    }
+
+    while let Some(token) = tokens.next() {
+        let Token::Word(keyword) = token else {
+            return Err(format!("Unexpected {:?}", token));
+        };
+        match keyword.to_lowercase().as_str() {
+            "from" => {
+                query.source = match tokens.next() {
+                    Some(Token::Word(w)) if w.starts_with('#') => {
+                        QuerySource::Tag(w.trim_start_matches('#').to_string())
+                    }
+                    Some(Token::Word(w)) | Some(Token::Str(w)) => {
+                        QuerySource::Folder(w.trim_matches('/').to_string())
+                    }
+                    other => return Err(format!("Expected #tag or \"folder\", found {:?}", other)),
+                };
+            }
+            "where" => loop {
+                let field = value_of(tokens.next())?;
+                let op = match tokens.next() {
+                    Some(Token::Op(op)) => op,
+                    Some(Token::Word(w)) if w.eq_ignore_ascii_case("contains") => {
+                        "contains".to_string()
+                    }
+                    other => return Err(format!("Expected an operator, found {:?}", other)),
+                };
+                let value = value_of(tokens.next())?;
+                query.conditions.push(Condition { field, op, value });
+                if is_keyword(tokens.peek(), "and") {
+                    tokens.next();
+                } else {
+                    break;
+                }
+            },
+            "sort" => {
+                let field = value_of(tokens.next())?;
+                let descending = if is_keyword(tokens.peek(), "desc") {
+                    tokens.next();
+                    true
+                } else {
+                    if is_keyword(tokens.peek(), "asc") {
+                        tokens.next();
+                    }
+                    false
+                };
+                query.sort = Some((field, descending));
+            }
+            "limit" => {
+                let limit = value_of(tokens.next())?;
+                query.limit = Some(
+                    limit
+                        .parse()
+                        .map_err(|_| format!("Invalid LIMIT: {}", limit))?,
+                );
+            }
+            other => return Err(format!("Unknown keyword: {}", other)),
+        }
+    }
+
+    Ok(query)
+}
+
+fn json_to_string(value: &Value) -> String {
+    match value {
+        Value::Null => String::new(),
+        Value::String(s) => s.clone(),
+        Value::Array(items) => items
+            .iter()
+            .map(json_to_string)
+            .collect::<Vec<_>>()
+            .join(", "),
+        other => other.to_string(),
+    }
+}
+
+impl NoteRecord {
+    pub fn field(&self, key: &str) -> String {
+        match key {
+            "file.name" | "name" => self.name.clone(),
+            "file.path" | "path" => self.path.clone(),
+            "file.mtime" | "modified" => self.modified.clone(),
+            "file.tags" | "tags" => self.tags.join(", "),
+            _ => self
+                .metadata
+                .get(key)
+                .map(json_to_string)
+                .unwrap_or_default(),
+        }
+    }
+}
+
+// Numbers compare numerically, everything else as text
+fn compare_values(a: &str, b: &str) -> Ordering {
+    match (a.parse::<f64>(), b.parse::<f64>()) {
+        (Ok(a), Ok(b)) => a.partial_cmp(&b).unwrap_or(Ordering::Equal),
+        _ => a.to_lowercase().cmp(&b.to_lowercase()),
+    }
+}
+
+impl Condition {
+    fn matches(&self, record: &NoteRecord) -> bool {
+        let actual = record.field(&self.field);
+        let ordering = compare_values(&actual, &self.value);
+        match self.op.as_str() {
+            "=" | "==" => ordering == Ordering::Equal,
+            "!=" => ordering != Ordering::Equal,
+            "<" => ordering == Ordering::Less,
+            "<=" => ordering != Ordering::Greater,
+            ">" => ordering == Ordering::Greater,
+            ">=" => ordering != Ordering::Less,
+            "contains" => actual.to_lowercase().contains(&self.value.to_lowercase()),
+            _ => false,
+        }
+    }
+}
+
+impl NoteQuery {
+    pub fn run(&self, records: Vec<NoteRecord>) -> Vec<NoteRecord> {
+        let mut matched: Vec<NoteRecord> = records
+            .into_iter()
+            .filter(|r| self.conditions.iter().all(|c| c.matches(r)))
+            .collect();
+        if let Some((field, descending)) = &self.sort {
+            matched.sort_by(|a, b| {
+                let ordering = compare_values(&a.field(field), &b.field(field));
+                if *descending {
+                    ordering.reverse()
+                } else {
+                    ordering
+                }
+            });
+        }
+        if let Some(limit) = self.limit {
+            matched.truncate(limit);
+        }
+        matched
+    }
+}
+
+impl NoteQuery {
+    // (header lines, one line per record)
+    pub fn format_results(&self, records: &[NoteRecord]) -> (Vec<String>, Vec<String>) {
+        if self.output == QueryOutput::List {
+            let rows = records
+                .iter()
+                .map(|r| {
+                    let extra: Vec<String> = self
+                        .fields
+                        .iter()
+                        .map(|f| r.field(f))
+                        .filter(|v| !v.is_empty())
+                        .collect();
+                    if extra.is_empty() {
+                        format!("• {}", r.name)
+                    } else {
+                        format!("• {} ({})", r.name, extra.join(", "))
+                    }
+                })
+                .collect();
+            return (Vec::new(), rows);
+        }
+
+        let mut columns = vec!["name".to_string()];
+        columns.extend(self.fields.iter().cloned());
+        let cells: Vec<Vec<String>> = records
+            .iter()
+            .map(|r| columns.iter().map(|c| r.field(c)).collect())
+            .collect();
+        let widths: Vec<usize> = columns
+            .iter()
+            .enumerate()
+            .map(|(i, c)| {
+                cells
+                    .iter()
+                    .map(|row| row[i].chars().count())
+                    .chain(std::iter::once(c.chars().count()))
+                    .max()
+                    .unwrap_or(0)
+            })
+            .collect();
+        let format_row = |row: &[String]| {
+            row.iter()
+                .zip(&widths)
+                .map(|(cell, width)| format!("{:<width$}", cell, width = *width))
+                .collect::<Vec<_>>()
+                .join(" │ ")
+                .trim_end()
+                .to_string()
+        };
+        let header = vec![
+            format_row(&columns),
+            widths
+                .iter()
+                .map(|w| "─".repeat(*w))
+                .collect::<Vec<_>>()
+                .join("─┼─"),
+        ];
+        let rows = cells.iter().map(|row| format_row(row)).collect();
+        (header, rows)
+    }
+}
+
+// ```query blocks in a buffer as (opening fence row, closing fence row, query text)
+pub fn find_blocks(lines: &[String]) -> Vec<(usize, usize, String)> {
+    let mut blocks = Vec::new();
+    let mut start: Option<usize> = None;
+    for (row, line) in lines.iter().enumerate() {
+        let trimmed = line.trim();
+        match start {
+            None if trimmed == "```query" => start = Some(row),
+            Some(open) if trimmed == "```" => {
+                let text = lines[open + 1..row].join(" ");
+                blocks.push((open, row, text));
+                start = None;
+            }
+            _ => {}
+        }
+    }
+    blocks
+}
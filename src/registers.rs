@@ -0,0 +1,392 @@
+use ratatui::crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use std::collections::HashMap;
+use std::io::Write;
+use std::process::{Command, Stdio};
+
+// Vim registers: `"` unnamed, `0` last yank, `1`-`9` deletes (newest first),
+// `-` small deletes within a line, `a`-`z` named (`A`-`Z` append), `_` black
+// hole, `+`/`*` the system clipboard and primary selection
+
+#[derive(Clone, Debug, Default)]
+pub struct Register {
+    pub text: Vec<String>, // One entry per line
+    pub linewise: bool,
+}
+
+impl Register {
+    pub fn new(text: Vec<String>, linewise: bool) -> Register {
+        Register { text, linewise }
+    }
+
+    // One-line summary for `:registers`
+    pub fn preview(&self) -> String {
+        self.text.join("⏎")
+    }
+}
+
+#[derive(Default)]
+pub struct Registers {
+    registers: HashMap<char, Register>,
+}
+
+pub fn is_valid(name: char) -> bool {
+    name.is_ascii_alphanumeric() || "\"-_+*".contains(name)
+}
+
+impl Registers {
+    // Text copied with `y`; `name` is the register picked with `"x`, if any
+    pub fn yank(&mut self, name: Option<char>, register: Register) {
+        if name == Some('_') {
+            return;
+        }
+        if name.is_none() {
+            self.registers.insert('0', register.clone());
+        }
+        self.store(name, register);
+    }
+
+    // Text removed by `d`, `c` or `x`
+    pub fn delete(&mut self, name: Option<char>, register: Register) {
+        if name == Some('_') {
+            return;
+        }
+        if name.is_none() {
+            if register.linewise || register.text.len() > 1 {
+                for n in (1..9).rev() {
+                    let from = char::from_digit(n, 10).unwrap();
+                    if let Some(older) = self.registers.remove(&from) {
+                        self.registers
+                            .insert(char::from_digit(n + 1, 10).unwrap(), older);
+                    }
+                }
+                self.registers.insert('1', register.clone());
+            } else {
+                self.registers.insert('-', register.clone());
+            }
+        }
+        self.store(name, register);
+    }
+
+    fn store(&mut self, name: Option<char>, register: Register) {
+        match name {
+            Some(upper @ 'A'..='Z') => {
+                let lower = upper.to_ascii_lowercase();
+                let register = match self.registers.remove(&lower) {
+                    Some(mut existing) if existing.linewise || register.linewise => {
+                        existing.text.extend(register.text);
+                        existing.linewise = true;
+                        existing
+                    }
+                    Some(mut existing) => {
+                        let mut lines = register.text.into_iter();
+                        if let (Some(last), Some(first)) = (existing.text.last_mut(), lines.next())
+                        {
+                            last.push_str(&first);
+                        }
+                        existing.text.extend(lines);
+                        existing
+                    }
+                    None => register,
+                };
+                self.registers.insert(lower, register.clone());
+                self.registers.insert('"', register);
+            }
+            Some(clipboard @ ('+' | '*')) => {
+                copy_to_clipboard(clipboard, &register.text.join("\n"));
+                self.registers.insert(clipboard, register.clone());
+                self.registers.insert('"', register);
+            }
+            Some(name) => {
+                self.registers.insert(name, register.clone());
+                self.registers.insert('"', register);
+            }
+            None => {
+                self.registers.insert('"', register);
+            }
+        }
+    }
+
+    // Contents of a register; `+`/`*` read the system clipboard when a tool for it is installed
+    pub fn get(&self, name: char) -> Option<Register> {
+        let name = name.to_ascii_lowercase();
+        if (name == '+' || name == '*')
+            && let Some(text) = paste_from_clipboard(name)
+        {
+            let linewise = text.ends_with('\n');
+            let text = text.strip_suffix('\n').unwrap_or(&text);
+            return Some(Register::new(
+                text.split('\n').map(|s| s.to_string()).collect(),
+                linewise,
+            ));
+        }
+        self.registers.get(&name).cloned()
+    }
+
+    // Set a register outright, as macro recording does; `A`-`Z` append
+    pub fn set(&mut self, name: char, text: String) {
+        let lower = name.to_ascii_lowercase();
+        let text = match self.registers.get(&lower) {
+            Some(existing) if name.is_ascii_uppercase() => existing.text.join("\n") + &text,
+            _ => text,
+        };
+        self.registers
+            .insert(lower, Register::new(vec![text], false));
+    }
+
+    // `a`-`z`, the registers kept in the session file
+    pub fn to_json(&self) -> serde_json::Value {
+        let named = self
+            .registers
+            .iter()
+            .filter(|(name, _)| name.is_ascii_lowercase())
+            .map(|(name, register)| {
+                (
+                    name.to_string(),
+                    serde_json::json!({ "text": register.text, "linewise": register.linewise }),
+                )
+            })
+            .collect();
+        serde_json::Value::Object(named)
+    }
+
+    pub fn load_json(&mut self, json: &serde_json::Value) {
+        let Some(named) = json.as_object() else {
+            return;
+        };
+        for (name, register) in named {
+            let (Some(name), Some(text)) = (name.chars().next(), register["text"].as_array())
+            else {
+                continue;
+            };
+            let text = text
+                .iter()
+                .filter_map(|line| line.as_str().map(|s| s.to_string()))
+                .collect();
+            let linewise = register["linewise"].as_bool().unwrap_or(false);
+            self.registers.insert(name, Register::new(text, linewise));
+        }
+    }
+
+    // Filled registers in `:registers` order
+    pub fn list(&self) -> Vec<(char, &Register)> {
+        "\"0123456789-abcdefghijklmnopqrstuvwxyz+*"
+            .chars()
+            .filter_map(|name| self.registers.get(&name).map(|r| (name, r)))
+            .collect()
+    }
+}
+
+fn has_command(name: &str) -> bool {
+    Command::new("which")
+        .arg(name)
+        .output()
+        .is_ok_and(|o| o.status.success())
+}
+
+// wl-copy on Wayland, xclip on X11, otherwise an OSC 52 escape the terminal
+// forwards to the clipboard
+fn copy_to_clipboard(name: char, text: &str) {
+    let primary = name == '*';
+    let command = if std::env::var_os("WAYLAND_DISPLAY").is_some() && has_command("wl-copy") {
+        let mut command = Command::new("wl-copy");
+        if primary {
+            command.arg("--primary");
+        }
+        Some(command)
+    } else if std::env::var_os("DISPLAY").is_some() && has_command("xclip") {
+        let mut command = Command::new("xclip");
+        command
+            .arg("-selection")
+            .arg(if primary { "primary" } else { "clipboard" });
+        Some(command)
+    } else {
+        None
+    };
+    if let Some(mut command) = command {
+        let child = command
+            .stdin(Stdio::piped())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .spawn();
+        if let Ok(mut child) = child {
+            if let Some(mut stdin) = child.stdin.take() {
+                let _ = stdin.write_all(text.as_bytes());
+            }
+            let _ = child.wait();
+            return;
+        }
+    }
+    let target = if primary { "p" } else { "c" };
+    let mut stdout = std::io::stdout();
+    let _ = write!(stdout, "\x1b]52;{};{}\x07", target, base64(text.as_bytes()));
+    let _ = stdout.flush();
+}
+
+fn paste_from_clipboard(name: char) -> Option<String> {
+    let primary = name == '*';
+    let output = if std::env::var_os("WAYLAND_DISPLAY").is_some() && has_command("wl-paste") {
+        // The trailing newline of a linewise copy has to survive
+        let mut command = Command::new("wl-paste");
+        if primary {
+            command.arg("--primary");
+        }
+        command.output().ok()?
+    } else if std::env::var_os("DISPLAY").is_some() && has_command("xclip") {
+        Command::new("xclip")
+            .arg("-selection")
+            .arg(if primary { "primary" } else { "clipboard" })
+            .arg("-o")
+            .output()
+            .ok()?
+    } else {
+        return None;
+    };
+    output
+        .status
+        .success()
+        .then(|| String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+fn base64(bytes: &[u8]) -> String {
+    const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut encoded = String::new();
+    for chunk in bytes.chunks(3) {
+        let n = chunk
+            .iter()
+            .enumerate()
+            .fold(0u32, |n, (i, &b)| n | (b as u32) << (16 - 8 * i));
+        for i in 0..4 {
+            if i <= chunk.len() {
+                encoded.push(ALPHABET[(n >> (18 - 6 * i) & 63) as usize] as char);
+            } else {
+                encoded.push('=');
+            }
+        }
+    }
+    encoded
+}
+
+// Macros are kept in registers as key notation: `<Esc>`, `<CR>`, `<C-w>`,
+// `<S-Up>`, `<F5>` and `<lt>` for a literal `<`
+const KEY_NAMES: &[(KeyCode, &str)] = &[
+    (KeyCode::Esc, "Esc"),
+    (KeyCode::Enter, "CR"),
+    (KeyCode::Backspace, "BS"),
+    (KeyCode::Tab, "Tab"),
+    (KeyCode::BackTab, "S-Tab"),
+    (KeyCode::Left, "Left"),
+    (KeyCode::Right, "Right"),
+    (KeyCode::Up, "Up"),
+    (KeyCode::Down, "Down"),
+    (KeyCode::Home, "Home"),
+    (KeyCode::End, "End"),
+    (KeyCode::Delete, "Del"),
+    (KeyCode::Insert, "Insert"),
+    (KeyCode::PageUp, "PageUp"),
+    (KeyCode::PageDown, "PageDown"),
+];
+
+const MODIFIER_NAMES: &[(KeyModifiers, &str)] = &[
+    (KeyModifiers::CONTROL, "C-"),
+    (KeyModifiers::ALT, "M-"),
+    (KeyModifiers::SHIFT, "S-"),
+];
+
+// Notation for one key; None for keys a macro cannot hold
+pub fn key_to_text(key: &KeyEvent) -> Option<String> {
+    let name = match key.code {
+        KeyCode::BackTab => return Some("<S-Tab>".to_string()),
+        KeyCode::Char(c)
+            if !key
+                .modifiers
+                .intersects(KeyModifiers::CONTROL | KeyModifiers::ALT) =>
+        {
+            return Some(match c {
+                '<' => "<lt>".to_string(),
+                c => c.to_string(),
+            });
+        }
+        KeyCode::Char('<') => "lt".to_string(),
+        KeyCode::Char('>') => "gt".to_string(),
+        KeyCode::Char(c) => c.to_string(),
+        KeyCode::F(n) => format!("F{}", n),
+        code => KEY_NAMES.iter().find(|(k, _)| *k == code)?.1.to_string(),
+    };
+    // Shift is already in the character of a Char key
+    let prefix: String = MODIFIER_NAMES
+        .iter()
+        .filter(|(modifier, _)| {
+            key.modifiers.contains(*modifier)
+                && !(*modifier == KeyModifiers::SHIFT && matches!(key.code, KeyCode::Char(_)))
+        })
+        .map(|(_, prefix)| *prefix)
+        .collect();
+    Some(format!("<{}{}>", prefix, name))
+}
+
+pub fn keys_to_text(keys: &[KeyEvent]) -> String {
+    keys.iter().filter_map(key_to_text).collect()
+}
+
+// The key named inside `<...>`
+fn parse_key_name(name: &str) -> Option<KeyEvent> {
+    if let Some((code, _)) = KEY_NAMES.iter().find(|(_, n)| *n == name) {
+        let modifiers = if *code == KeyCode::BackTab {
+            KeyModifiers::SHIFT
+        } else {
+            KeyModifiers::NONE
+        };
+        return Some(KeyEvent::new(*code, modifiers));
+    }
+    let mut modifiers = KeyModifiers::NONE;
+    let mut rest = name;
+    while let Some((modifier, after)) = MODIFIER_NAMES.iter().find_map(|(modifier, prefix)| {
+        let after = rest.strip_prefix(prefix)?;
+        (!after.is_empty()).then_some((*modifier, after))
+    }) {
+        modifiers |= modifier;
+        rest = after;
+    }
+    let mut chars = rest.chars();
+    let code = match (chars.next()?, chars.next()) {
+        (c, None) => KeyCode::Char(c),
+        _ if rest == "lt" => KeyCode::Char('<'),
+        _ if rest == "gt" => KeyCode::Char('>'),
+        _ => match rest.strip_prefix('F').and_then(|n| n.parse::<u8>().ok()) {
+            Some(n) => KeyCode::F(n),
+            None => KEY_NAMES.iter().find(|(_, n)| *n == rest)?.0,
+        },
+    };
+    // A bare character needs a modifier to be written in `<...>`
+    if modifiers.is_empty() && matches!(code, KeyCode::Char(_)) && rest != "lt" {
+        return None;
+    }
+    Some(KeyEvent::new(code, modifiers))
+}
+
+pub fn text_to_keys(text: &str) -> Vec<KeyEvent> {
+    let mut keys = Vec::new();
+    let mut rest = text;
+    while let Some(c) = rest.chars().next() {
+        let special = rest
+            .strip_prefix('<')
+            .and_then(|after| after.split_once('>'))
+            .and_then(|(name, after)| Some((parse_key_name(name)?, after)));
+        match special {
+            Some((key, after)) => {
+                keys.push(key);
+                rest = after;
+            }
+            None => {
+                let code = if c == '\n' {
+                    KeyCode::Enter
+                } else {
+                    KeyCode::Char(c)
+                };
+                keys.push(KeyEvent::new(code, KeyModifiers::NONE));
+                rest = &rest[c.len_utf8()..];
+            }
+        }
+    }
+    keys
+}
@@ -0,0 +1,317 @@
+use std::cmp::Ordering;
+
+// Markdown pipe tables: `| a | b |` rows with a `| --- | :-: |` separator
+
+#[derive(PartialEq, Clone, Copy, Debug)]
+pub enum Align {
+    None,
+    Left,
+    Center,
+    Right,
+}
+
+#[derive(Clone, Debug)]
+pub struct Table {
+    pub header: Vec<String>,
+    pub aligns: Vec<Align>,
+    pub rows: Vec<Vec<String>>,
+}
+
+pub fn is_table_line(line: &str) -> bool {
+    line.trim_start().starts_with('|')
+}
+
+// Rows start..end (exclusive) of the table containing `row`
+pub fn table_range(lines: &[String], row: usize) -> Option<(usize, usize)> {
+    if !lines.get(row).is_some_and(|l| is_table_line(l)) {
+        return None;
+    }
+    let mut start = row;
+    while start > 0 && is_table_line(&lines[start - 1]) {
+        start -= 1;
+    }
+    let mut end = row + 1;
+    while end < lines.len() && is_table_line(&lines[end]) {
+        end += 1;
+    }
+    Some((start, end))
+}
+
+// Char ranges between the pipes of a row, one per cell
+pub fn cell_bounds(line: &str) -> Vec<(usize, usize)> {
+    let chars: Vec<char> = line.chars().collect();
+    let mut pipes = Vec::new();
+    let mut i = 0;
+    while i < chars.len() {
+        match chars[i] {
+            '\\' => i += 1,
+            '|' => pipes.push(i),
+            _ => {}
+        }
+        i += 1;
+    }
+    let mut bounds: Vec<(usize, usize)> = pipes.windows(2).map(|w| (w[0] + 1, w[1])).collect();
+    // A row without the closing pipe still has a last cell
+    if let Some(&last) = pipes
+        .last()
+        .filter(|&&last| chars[last + 1..].iter().any(|c| !c.is_whitespace()))
+    {
+        bounds.push((last + 1, chars.len()));
+    }
+    bounds
+}
+
+pub fn parse_row(line: &str) -> Vec<String> {
+    let chars: Vec<char> = line.chars().collect();
+    cell_bounds(line)
+        .into_iter()
+        .map(|(start, end)| {
+            chars[start..end]
+                .iter()
+                .collect::<String>()
+                .trim()
+                .to_string()
+        })
+        .collect()
+}
+
+fn parse_align(cell: &str) -> Option<Align> {
+    let dashes = cell.trim_matches(':');
+    if dashes.is_empty() || !dashes.chars().all(|c| c == '-') {
+        return None;
+    }
+    Some(match (cell.starts_with(':'), cell.ends_with(':')) {
+        (true, true) => Align::Center,
+        (true, false) => Align::Left,
+        (false, true) => Align::Right,
+        (false, false) => Align::None,
+    })
+}
+
+fn compare_cells(a: &str, b: &str) -> Ordering {
+    match (a.parse::<f64>(), b.parse::<f64>()) {
+        (Ok(a), Ok(b)) => a.partial_cmp(&b).unwrap_or(Ordering::Equal),
+        _ => a.to_lowercase().cmp(&b.to_lowercase()),
+    }
+}
+
+fn split_csv_line(line: &str) -> Vec<String> {
+    let delimiter = if !line.contains(',') && line.contains('\t') {
+        '\t'
+    } else {
+        ','
+    };
+    let mut cells = Vec::new();
+    let mut cell = String::new();
+    let mut quoted = false;
+    let mut chars = line.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if quoted && chars.peek() == Some(&'"') => {
+                cell.push('"');
+                chars.next();
+            }
+            '"' => quoted = !quoted,
+            c if c == delimiter && !quoted => cells.push(std::mem::take(&mut cell)),
+            c => cell.push(c),
+        }
+    }
+    cells.push(cell);
+    cells
+        .into_iter()
+        .map(|c| c.trim().replace('|', "\\|"))
+        .collect()
+}
+
+impl Table {
+    pub fn parse(lines: &[String]) -> Option<Table> {
+        let mut rows: Vec<Vec<String>> = lines.iter().map(|l| parse_row(l)).collect();
+        if rows.is_empty() {
+            return None;
+        }
+        let header = rows.remove(0);
+        let aligns = match rows.first() {
+            Some(cells) if !cells.is_empty() && cells.iter().all(|c| parse_align(c).is_some()) => {
+                let aligns = cells.iter().filter_map(|c| parse_align(c)).collect();
+                rows.remove(0);
+                aligns
+            }
+            _ => Vec::new(),
+        };
+        let mut table = Table {
+            header,
+            aligns,
+            rows,
+        };
+        table.normalize();
+        Some(table)
+    }
+
+    pub fn from_csv(lines: &[String]) -> Option<Table> {
+        let mut rows: Vec<Vec<String>> = lines
+            .iter()
+            .filter(|l| !l.trim().is_empty())
+            .map(|l| split_csv_line(l))
+            .collect();
+        if rows.is_empty() {
+            return None;
+        }
+        let header = rows.remove(0);
+        let mut table = Table {
+            header,
+            aligns: Vec::new(),
+            rows,
+        };
+        table.normalize();
+        Some(table)
+    }
+
+    pub fn to_csv(&self) -> Vec<String> {
+        let escape = |cell: &String| {
+            let cell = cell.replace("\\|", "|");
+            if cell.contains([',', '"', '\n']) {
+                format!("\"{}\"", cell.replace('"', "\"\""))
+            } else {
+                cell
+            }
+        };
+        std::iter::once(&self.header)
+            .chain(&self.rows)
+            .map(|row| row.iter().map(escape).collect::<Vec<_>>().join(","))
+            .collect()
+    }
+
+    pub fn columns(&self) -> usize {
+        self.header.len()
+    }
+
+    // Every row gets the same number of cells
+    fn normalize(&mut self) {
+        let columns = std::iter::once(&self.header)
+            .chain(&self.rows)
+            .map(|r| r.len())
+            .max()
+            .unwrap_or(0)
+            .max(1);
+        self.header.resize(columns, String::new());
+        self.aligns.resize(columns, Align::None);
+        for row in &mut self.rows {
+            row.resize(columns, String::new());
+        }
+    }
+
+    // Aligned lines: header, separator, then the body rows
+    pub fn render(&self) -> Vec<String> {
+        let widths: Vec<usize> = (0..self.columns())
+            .map(|c| {
+                std::iter::once(&self.header)
+                    .chain(&self.rows)
+                    .map(|r| r[c].chars().count())
+                    .max()
+                    .unwrap_or(0)
+                    .max(3)
+            })
+            .collect();
+        let format_row = |row: &[String]| {
+            let cells: Vec<String> = row
+                .iter()
+                .zip(&widths)
+                .zip(&self.aligns)
+                .map(|((cell, &width), align)| match align {
+                    Align::Right => format!("{:>width$}", cell),
+                    Align::Center => format!("{:^width$}", cell),
+                    _ => format!("{:<width$}", cell),
+                })
+                .collect();
+            format!("| {} |", cells.join(" | "))
+        };
+        let separator: Vec<String> = widths
+            .iter()
+            .zip(&self.aligns)
+            .map(|(&width, align)| match align {
+                Align::None => "-".repeat(width),
+                Align::Left => format!(":{}", "-".repeat(width - 1)),
+                Align::Right => format!("{}:", "-".repeat(width - 1)),
+                Align::Center => format!(":{}:", "-".repeat(width - 2)),
+            })
+            .collect();
+
+        let mut lines = vec![format_row(&self.header)];
+        lines.push(format!("| {} |", separator.join(" | ")));
+        lines.extend(self.rows.iter().map(|r| format_row(r)));
+        lines
+    }
+
+    // `at` counts body rows, so 0 inserts right under the separator
+    pub fn insert_row(&mut self, at: usize) {
+        let at = at.min(self.rows.len());
+        self.rows.insert(at, vec![String::new(); self.columns()]);
+    }
+
+    pub fn delete_row(&mut self, index: usize) {
+        if index < self.rows.len() {
+            self.rows.remove(index);
+        }
+    }
+
+    pub fn insert_column(&mut self, at: usize) {
+        let at = at.min(self.columns());
+        self.header.insert(at, String::new());
+        self.aligns.insert(at, Align::None);
+        for row in &mut self.rows {
+            row.insert(at, String::new());
+        }
+    }
+
+    pub fn delete_column(&mut self, column: usize) {
+        if self.columns() <= 1 || column >= self.columns() {
+            return;
+        }
+        self.header.remove(column);
+        self.aligns.remove(column);
+        for row in &mut self.rows {
+            row.remove(column);
+        }
+    }
+
+    pub fn sort_by(&mut self, column: usize, descending: bool) {
+        if column >= self.columns() {
+            return;
+        }
+        self.rows.sort_by(|a, b| {
+            let ordering = compare_cells(&a[column], &b[column]);
+            if descending {
+                ordering.reverse()
+            } else {
+                ordering
+            }
+        });
+    }
+}
+
+// Which cell of a row the char column is in
+pub fn cell_at(line: &str, col: usize) -> usize {
+    let bounds = cell_bounds(line);
+    bounds
+        .iter()
+        .position(|&(start, end)| col >= start && col <= end)
+        .or_else(|| bounds.iter().rposition(|&(start, _)| col >= start))
+        .unwrap_or(0)
+}
+
+// Char column where the text of `cell` starts in an aligned row
+pub fn cell_text_start(line: &str, cell: usize) -> usize {
+    let chars: Vec<char> = line.chars().collect();
+    match cell_bounds(line).get(cell) {
+        Some(&(start, end)) => {
+            let padding = chars[start..end].iter().take_while(|c| **c == ' ').count();
+            if start + padding >= end {
+                // Empty cell: just after the space following the pipe
+                (start + 1).min(end)
+            } else {
+                start + padding
+            }
+        }
+        None => chars.len(),
+    }
+}
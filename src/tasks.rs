@@ -0,0 +1,104 @@
+use regex::Regex;
+
+// `- [ ] text` items with an optional `📅 2026-10-20` or `due: 2026-10-20` date
+
+#[derive(Clone, Debug)]
+pub struct Task {
+    pub line: usize,
+    pub state: char, // ' ' open, 'x' done, or a custom state like '/', '-', '>'
+    pub text: String,
+    pub due: Option<String>,
+}
+
+impl Task {
+    pub fn is_done(&self) -> bool {
+        matches!(self.state, 'x' | 'X' | '-')
+    }
+}
+
+fn task_regex() -> Regex {
+    Regex::new(r"^(\s*(?:[-*+]|\d+[.)])\s+)\[(.)\]((?:\s.*)?)$").unwrap()
+}
+
+pub fn extract(lines: &[String]) -> Vec<Task> {
+    let task_re = task_regex();
+    let due_re = Regex::new(r"(?:📅\s*|\bdue::?\s*)(\d{4}-\d{2}-\d{2})").unwrap();
+    let mut tasks = Vec::new();
+    let mut in_code = false;
+    for (line, text) in lines.iter().enumerate() {
+        if text.trim_start().starts_with("```") {
+            in_code = !in_code;
+            continue;
+        }
+        if in_code {
+            continue;
+        }
+        let Some(cap) = task_re.captures(text) else {
+            continue;
+        };
+        tasks.push(Task {
+            line,
+            state: cap[2].chars().next().unwrap_or(' '),
+            text: cap[3].trim().to_string(),
+            due: due_re.captures(&cap[3]).map(|d| d[1].to_string()),
+        });
+    }
+    tasks
+}
+
+// The line with its checkbox set to `state`, if it is a task
+pub fn set_state(line: &str, state: char) -> Option<String> {
+    let cap = task_regex().captures(line)?;
+    Some(format!("{}[{}]{}", &cap[1], state, &cap[3]))
+}
+
+// Order `\s` steps through checkbox states
+const STATE_CYCLE: [char; 5] = [' ', '/', 'x', '-', '>'];
+
+fn set_done_date(line: &str, state: char, done_date: Option<&str>) -> String {
+    let stamp_re = Regex::new(r"\s*✅\s*\d{4}-\d{2}-\d{2}").unwrap();
+    let line = stamp_re.replace_all(line, "").to_string();
+    match done_date {
+        Some(date) if state == 'x' => format!("{} ✅ {}", line.trim_end(), date),
+        _ => line,
+    }
+}
+
+// Turn a list item or plain line into an open task
+fn make_task(line: &str) -> String {
+    let list_re = Regex::new(r"^(\s*(?:[-*+]|\d+[.)])\s+)(.*)$").unwrap();
+    if let Some(cap) = list_re.captures(line) {
+        return format!("{}[ ] {}", &cap[1], &cap[2]);
+    }
+    let indent = line.len() - line.trim_start().len();
+    format!("{}- [ ] {}", &line[..indent], line.trim_start())
+}
+
+// `[ ]` ⇄ `[x]`, stamping `✅ date` on completion when done_date is set
+pub fn toggle_line(line: &str, done_date: Option<&str>) -> String {
+    let Some(cap) = task_regex().captures(line) else {
+        return make_task(line);
+    };
+    let state = cap[2].chars().next().unwrap_or(' ');
+    let next = if matches!(state, 'x' | 'X' | '-') {
+        ' '
+    } else {
+        'x'
+    };
+    let line = set_state(line, next).unwrap_or_else(|| line.to_string());
+    set_done_date(&line, next, done_date)
+}
+
+pub fn cycle_line(line: &str, done_date: Option<&str>) -> String {
+    let Some(cap) = task_regex().captures(line) else {
+        return make_task(line);
+    };
+    let state = cap[2].chars().next().unwrap_or(' ').to_ascii_lowercase();
+    let next = STATE_CYCLE
+        .iter()
+        .position(|&s| s == state)
+        .map(|i| STATE_CYCLE[(i + 1) % STATE_CYCLE.len()])
+        .unwrap_or(' ');
+    let line = set_state(line, next).unwrap_or_else(|| line.to_string());
+    set_done_date(&line, next, done_date)
+}
@@ -0,0 +1,267 @@
+// Timestamped states of a note for `:earlier` and `:later`. States are only
+// ever appended, so going back in time and editing keeps the later ones. Only
+// the state the buffer is at is stored in full; the others are rebuilt from
+// the rows that changed between neighbouring states.
+
+const MAX_CHANGES: usize = 500;
+const MAX_CHANGE_BYTES: usize = 1 << 20; // Text kept in changes, per note
+
+// Rows start..start + before.len() replaced by `after`, leading to a state
+// recorded at `time`
+#[derive(Clone, Debug, Default)]
+pub struct Change {
+    time: i64, // Unix seconds
+    start: usize,
+    before: Vec<String>,
+    after: Vec<String>,
+}
+
+impl Change {
+    fn between(old: &[String], new: &[String], time: i64) -> Change {
+        let prefix = old.iter().zip(new).take_while(|(a, b)| a == b).count();
+        let suffix = old[prefix..]
+            .iter()
+            .rev()
+            .zip(new[prefix..].iter().rev())
+            .take_while(|(a, b)| a == b)
+            .count();
+        Change {
+            time,
+            start: prefix,
+            before: old[prefix..old.len() - suffix].to_vec(),
+            after: new[prefix..new.len() - suffix].to_vec(),
+        }
+    }
+
+    fn bytes(&self) -> usize {
+        self.before
+            .iter()
+            .chain(&self.after)
+            .map(|l| l.len() + 1)
+            .sum()
+    }
+
+    fn apply(&self, lines: &mut Vec<String>) {
+        let end = self.start + self.before.len();
+        lines.splice(self.start..end, self.after.iter().cloned());
+    }
+
+    fn revert(&self, lines: &mut Vec<String>) {
+        let end = self.start + self.after.len();
+        lines.splice(self.start..end, self.before.iter().cloned());
+    }
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct Timeline {
+    lines: Vec<String>,       // State the buffer is at
+    saved_lines: Vec<String>, // State last written to the file
+    start_time: i64,          // Time of the oldest state
+    changes: Vec<Change>,     // changes[i] leads from state i to state i + 1
+    bytes: usize,             // Text held in `changes`
+    index: usize,             // State the buffer is at
+    saved: Option<usize>,     // State last written to the file, None once dropped
+}
+
+#[derive(PartialEq, Clone, Copy, Debug)]
+pub enum Step {
+    Count(usize),
+    Seconds(i64),
+}
+
+// `10`, `30s`, `10m`, `2h` or `1d`; empty means one step
+pub fn parse_step(arg: &str) -> Option<Step> {
+    let arg = arg.trim();
+    if arg.is_empty() {
+        return Some(Step::Count(1));
+    }
+    let digits = arg.find(|c: char| !c.is_ascii_digit()).unwrap_or(arg.len());
+    let n: i64 = arg[..digits].parse().ok()?;
+    let unit = match &arg[digits..] {
+        "" => return Some(Step::Count(n as usize)),
+        "s" => 1,
+        "m" => 60,
+        "h" => 3600,
+        "d" => 86400,
+        _ => return None,
+    };
+    n.checked_mul(unit).map(Step::Seconds)
+}
+
+impl Timeline {
+    pub fn new(lines: Vec<String>, time: i64) -> Timeline {
+        Timeline {
+            saved_lines: lines.clone(),
+            lines,
+            start_time: time,
+            saved: Some(0),
+            ..Timeline::default()
+        }
+    }
+
+    pub fn current(&self) -> &[String] {
+        &self.lines
+    }
+
+    pub fn saved_lines(&self) -> &[String] {
+        &self.saved_lines
+    }
+
+    pub fn state_count(&self) -> usize {
+        self.changes.len() + 1
+    }
+
+    pub fn time(&self, index: usize) -> i64 {
+        match index {
+            0 => self.start_time,
+            i => self.changes[i - 1].time,
+        }
+    }
+
+    // Move the current state to `index`
+    pub fn go_to(&mut self, index: usize) {
+        let index = index.min(self.changes.len());
+        while self.index < index {
+            self.changes[self.index].apply(&mut self.lines);
+            self.index += 1;
+        }
+        while self.index > index {
+            self.index -= 1;
+            self.changes[self.index].revert(&mut self.lines);
+        }
+    }
+
+    // Back at the state last written to the file, as when the note is opened
+    pub fn go_to_saved(&mut self) {
+        if let Some(saved) = self.saved {
+            self.go_to(saved);
+        }
+    }
+
+    // Add the buffer as the newest state when it differs from the current one
+    pub fn record(&mut self, lines: &[String], time: i64) {
+        if self.lines == lines {
+            return;
+        }
+        self.go_to(self.changes.len());
+        let change = Change::between(&self.lines, lines, time);
+        self.bytes += change.bytes();
+        self.changes.push(change);
+        self.lines = lines.to_vec();
+        self.index = self.changes.len();
+        while self.changes.len() > 1
+            && (self.changes.len() > MAX_CHANGES || self.bytes > MAX_CHANGE_BYTES)
+        {
+            let oldest = self.changes.remove(0);
+            self.bytes -= oldest.bytes();
+            self.start_time = oldest.time;
+            self.index -= 1;
+            self.saved = self.saved.and_then(|saved| saved.checked_sub(1));
+        }
+    }
+
+    pub fn mark_saved(&mut self) {
+        self.saved = Some(self.index);
+        self.saved_lines = self.lines.clone();
+    }
+
+    // Index of the state `step` before (or after, when `later`) the current one
+    pub fn travel(&self, step: Step, later: bool) -> usize {
+        let last = self.changes.len();
+        match (step, later) {
+            (Step::Count(n), false) => self.index.saturating_sub(n),
+            (Step::Count(n), true) => self.index.saturating_add(n).min(last),
+            (Step::Seconds(secs), false) => {
+                let time = self.time(self.index).saturating_sub(secs);
+                (0..self.index)
+                    .rev()
+                    .find(|&i| self.time(i) <= time)
+                    .unwrap_or(0)
+            }
+            (Step::Seconds(secs), true) => {
+                let time = self.time(self.index).saturating_add(secs);
+                (self.index..=last)
+                    .find(|&i| self.time(i) >= time)
+                    .unwrap_or(last)
+            }
+        }
+    }
+
+    pub fn to_json(&self) -> serde_json::Value {
+        serde_json::json!({
+            "lines": self.lines,
+            "start_time": self.start_time,
+            "index": self.index,
+            "saved": self.saved,
+            "changes": self
+                .changes
+                .iter()
+                .map(|c| serde_json::json!({
+                    "time": c.time,
+                    "start": c.start,
+                    "before": c.before,
+                    "after": c.after,
+                }))
+                .collect::<Vec<_>>(),
+        })
+    }
+
+    // None when the file is unreadable or no longer knows the saved state
+    pub fn from_json(json: &serde_json::Value) -> Option<Timeline> {
+        let strings = |value: &serde_json::Value| {
+            value
+                .as_array()?
+                .iter()
+                .map(|line| line.as_str().map(|l| l.to_string()))
+                .collect::<Option<Vec<String>>>()
+        };
+        let changes = json["changes"]
+            .as_array()?
+            .iter()
+            .map(|c| {
+                Some(Change {
+                    time: c["time"].as_i64()?,
+                    start: c["start"].as_u64()? as usize,
+                    before: strings(&c["before"])?,
+                    after: strings(&c["after"])?,
+                })
+            })
+            .collect::<Option<Vec<Change>>>()?;
+        let index = json["index"].as_u64()? as usize;
+        let saved = json["saved"].as_u64()? as usize;
+        if index > changes.len() || saved > changes.len() {
+            return None;
+        }
+        let mut timeline = Timeline {
+            lines: strings(&json["lines"])?,
+            start_time: json["start_time"].as_i64()?,
+            bytes: changes.iter().map(Change::bytes).sum(),
+            changes,
+            index,
+            saved: Some(saved),
+            ..Timeline::default()
+        };
+        // The changes must fit the lines they are replayed on
+        let fits =
+            |change: &Change, lines: &[String], rows: usize| change.start + rows <= lines.len();
+        let mut lines = timeline.lines.clone();
+        for i in (0..index).rev() {
+            let change = &timeline.changes[i];
+            if !fits(change, &lines, change.after.len()) {
+                return None;
+            }
+            change.revert(&mut lines);
+        }
+        for change in &timeline.changes {
+            if !fits(change, &lines, change.before.len()) {
+                return None;
+            }
+            change.apply(&mut lines);
+        }
+        let current = timeline.index;
+        timeline.go_to(saved);
+        timeline.saved_lines = timeline.lines.clone();
+        timeline.go_to(current);
+        Some(timeline)
+    }
+}
@@ -0,0 +1,854 @@
+// Normal mode operator + count + motion grammar, like `d3w`, `c$`, `y}` or `gUiw`.
+// Positions are (row, char column); a column equal to the line length is the
+// newline at the end of the row.
+
+#[derive(PartialEq, Clone, Copy, Debug)]
+pub enum Operator {
+    Delete,
+    Change,
+    Yank,
+    Indent,
+    Outdent,
+    Lower,
+    Upper,
+    ToggleCase,
+}
+
+#[derive(PartialEq, Clone, Copy, Debug)]
+pub struct Find {
+    pub forward: bool,
+    pub till: bool,
+    pub ch: char,
+    pub repeat: bool, // Run by `;` or `,`
+}
+
+#[derive(PartialEq, Clone, Copy, Debug)]
+pub enum Motion {
+    Left,
+    Right,
+    Down,
+    Up,
+    WordStart { big: bool },
+    WordEnd { big: bool },
+    WordBack { big: bool },
+    LineStart,
+    FirstNonBlank,
+    LineEnd,
+    ParagraphBack,
+    ParagraphForward,
+    Find(Find),
+    RepeatFind { reverse: bool },
+    MatchPair,
+    FirstLine,
+    LastLine,
+    Line,                                 // The operator typed twice, like `dd`
+    Object { inner: bool, object: char }, // `iw`, `a(`, `ih`: only after an operator or in Visual
+}
+
+#[derive(PartialEq, Clone, Copy, Debug)]
+pub struct Command {
+    pub count: Option<usize>,
+    pub operator: Option<Operator>,
+    pub motion: Motion,
+}
+
+#[derive(PartialEq, Clone, Copy, Debug)]
+pub enum Parse {
+    Pending, // A prefix of a command: wait for more keys
+    Invalid, // Not part of the grammar
+    Command(Command),
+}
+
+#[derive(PartialEq, Clone, Copy, Debug)]
+pub enum Kind {
+    Exclusive,
+    Inclusive,
+    Linewise,
+}
+
+// (start, end, linewise): rows start.0..=end.0 when linewise, otherwise the
+// chars from start up to (not including) end
+pub type Range = ((usize, usize), (usize, usize), bool);
+
+// Where a motion lands and how an operator treats the text up to there
+#[derive(PartialEq, Clone, Copy, Debug)]
+pub struct Target {
+    pub pos: (usize, usize),
+    pub kind: Kind,
+}
+
+// Leading digits as a count, and the rest of the keys
+fn split_count(keys: &[char]) -> (Option<usize>, &[char]) {
+    if keys.first().is_none_or(|c| !('1'..='9').contains(c)) {
+        return (None, keys);
+    }
+    let digits = keys.iter().take_while(|c| c.is_ascii_digit()).count();
+    // Too many digits for a usize is as good as the largest count
+    let count = keys[..digits]
+        .iter()
+        .collect::<String>()
+        .parse()
+        .unwrap_or(usize::MAX);
+    (Some(count), &keys[digits..])
+}
+
+fn parse_operator(keys: &[char]) -> Option<(Operator, usize)> {
+    match keys {
+        ['d', ..] => Some((Operator::Delete, 1)),
+        ['c', ..] => Some((Operator::Change, 1)),
+        ['y', ..] => Some((Operator::Yank, 1)),
+        ['>', ..] => Some((Operator::Indent, 1)),
+        ['<', ..] => Some((Operator::Outdent, 1)),
+        ['~', ..] => Some((Operator::ToggleCase, 1)),
+        ['g', 'u', ..] => Some((Operator::Lower, 2)),
+        ['g', 'U', ..] => Some((Operator::Upper, 2)),
+        _ => None,
+    }
+}
+
+fn parse_motion(keys: &[char]) -> Parse {
+    let motion = match keys {
+        [] | ['g'] => return Parse::Pending,
+        ['f' | 't' | 'F' | 'T' | 'i' | 'a'] => return Parse::Pending,
+        [key @ ('i' | 'a'), object] => Motion::Object {
+            inner: *key == 'i',
+            object: *object,
+        },
+        [key @ ('f' | 't' | 'F' | 'T'), ch] => Motion::Find(Find {
+            forward: matches!(key, 'f' | 't'),
+            till: matches!(key, 't' | 'T'),
+            ch: *ch,
+            repeat: false,
+        }),
+        ['g', 'g'] => Motion::FirstLine,
+        [key] => match key {
+            'h' => Motion::Left,
+            'l' => Motion::Right,
+            'j' => Motion::Down,
+            'k' => Motion::Up,
+            'w' => Motion::WordStart { big: false },
+            'W' => Motion::WordStart { big: true },
+            'e' => Motion::WordEnd { big: false },
+            'E' => Motion::WordEnd { big: true },
+            'b' => Motion::WordBack { big: false },
+            'B' => Motion::WordBack { big: true },
+            '0' => Motion::LineStart,
+            '^' => Motion::FirstNonBlank,
+            '$' => Motion::LineEnd,
+            '{' => Motion::ParagraphBack,
+            '}' => Motion::ParagraphForward,
+            ';' => Motion::RepeatFind { reverse: false },
+            ',' => Motion::RepeatFind { reverse: true },
+            '%' => Motion::MatchPair,
+            'G' => Motion::LastLine,
+            _ => return Parse::Invalid,
+        },
+        _ => return Parse::Invalid,
+    };
+    Parse::Command(Command {
+        count: None,
+        operator: None,
+        motion,
+    })
+}
+
+pub fn parse(sequence: &str) -> Parse {
+    let keys: Vec<char> = sequence.chars().collect();
+    let (count, rest) = split_count(&keys);
+    let Some((operator, len)) = parse_operator(rest) else {
+        // A bare motion; plain h/j/k/l/G and gg without a count are handled as keys
+        return match parse_motion(rest) {
+            Parse::Command(command) if matches!(command.motion, Motion::Object { .. }) => {
+                Parse::Invalid
+            }
+            Parse::Command(command) => Parse::Command(Command { count, ..command }),
+            other => other,
+        };
+    };
+    let rest = &rest[len..];
+    let (motion_count, rest) = split_count(rest);
+    let count = match (count, motion_count) {
+        (None, None) => None,
+        (a, b) => Some(a.unwrap_or(1).saturating_mul(b.unwrap_or(1))),
+    };
+    // `dd`, `>>`, `gUU` and `gUgU` work on whole lines
+    let doubled = match operator {
+        Operator::Lower => rest == ['u'] || rest == ['g', 'u'],
+        Operator::Upper => rest == ['U'] || rest == ['g', 'U'],
+        _ => parse_operator(rest).is_some_and(|(o, len)| o == operator && len == rest.len()),
+    };
+    if doubled {
+        return Parse::Command(Command {
+            count,
+            operator: Some(operator),
+            motion: Motion::Line,
+        });
+    }
+    if rest == ['g'] && matches!(operator, Operator::Lower | Operator::Upper) {
+        return Parse::Pending;
+    }
+    match parse_motion(rest) {
+        Parse::Command(command) => Parse::Command(Command {
+            count,
+            operator: Some(operator),
+            motion: command.motion,
+        }),
+        other => other,
+    }
+}
+
+fn line_len(lines: &[String], row: usize) -> usize {
+    lines[row].chars().count()
+}
+
+fn char_at(lines: &[String], (row, col): (usize, usize)) -> char {
+    lines[row].chars().nth(col).unwrap_or('\n')
+}
+
+fn first_non_blank(lines: &[String], row: usize) -> usize {
+    lines[row].chars().take_while(|c| c.is_whitespace()).count()
+}
+
+fn next(lines: &[String], (row, col): (usize, usize)) -> Option<(usize, usize)> {
+    if col < line_len(lines, row) {
+        Some((row, col + 1))
+    } else if row + 1 < lines.len() {
+        Some((row + 1, 0))
+    } else {
+        None
+    }
+}
+
+fn prev(lines: &[String], (row, col): (usize, usize)) -> Option<(usize, usize)> {
+    if col > 0 {
+        Some((row, col - 1))
+    } else if row > 0 {
+        Some((row - 1, line_len(lines, row - 1)))
+    } else {
+        None
+    }
+}
+
+// 0 blank, 1 keyword, 2 punctuation; `big` words are any non-blank run
+fn class(lines: &[String], pos: (usize, usize), big: bool) -> u8 {
+    let c = char_at(lines, pos);
+    if c.is_whitespace() {
+        0
+    } else if big || c.is_alphanumeric() || c == '_' {
+        1
+    } else {
+        2
+    }
+}
+
+fn is_empty_line(lines: &[String], (row, _): (usize, usize)) -> bool {
+    lines[row].is_empty()
+}
+
+fn word_start(lines: &[String], pos: (usize, usize), big: bool) -> (usize, usize) {
+    let start_class = class(lines, pos, big);
+    let mut pos = pos;
+    loop {
+        match next(lines, pos) {
+            Some(n) => pos = n,
+            None => return pos,
+        }
+        if class(lines, pos, big) != start_class || is_empty_line(lines, pos) {
+            break;
+        }
+    }
+    while class(lines, pos, big) == 0 && !is_empty_line(lines, pos) {
+        match next(lines, pos) {
+            Some(n) => pos = n,
+            None => return pos,
+        }
+    }
+    pos
+}
+
+fn word_end(lines: &[String], pos: (usize, usize), big: bool) -> (usize, usize) {
+    let Some(mut pos) = next(lines, pos) else {
+        return pos;
+    };
+    while class(lines, pos, big) == 0 {
+        match next(lines, pos) {
+            Some(n) => pos = n,
+            None => return pos,
+        }
+    }
+    let word_class = class(lines, pos, big);
+    while let Some(n) = next(lines, pos).filter(|&n| class(lines, n, big) == word_class) {
+        pos = n;
+    }
+    pos
+}
+
+fn word_back(lines: &[String], pos: (usize, usize), big: bool) -> (usize, usize) {
+    let Some(mut pos) = prev(lines, pos) else {
+        return pos;
+    };
+    while class(lines, pos, big) == 0 && !is_empty_line(lines, pos) {
+        match prev(lines, pos) {
+            Some(p) => pos = p,
+            None => return pos,
+        }
+    }
+    if is_empty_line(lines, pos) {
+        return pos;
+    }
+    let word_class = class(lines, pos, big);
+    while let Some(p) = prev(lines, pos).filter(|&p| class(lines, p, big) == word_class) {
+        pos = p;
+    }
+    pos
+}
+
+fn is_blank_row(lines: &[String], row: usize) -> bool {
+    lines[row].trim().is_empty()
+}
+
+fn paragraph_forward(lines: &[String], row: usize) -> (usize, usize) {
+    let mut row = row;
+    while row + 1 < lines.len() && is_blank_row(lines, row) {
+        row += 1;
+    }
+    while row + 1 < lines.len() && !is_blank_row(lines, row) {
+        row += 1;
+    }
+    if is_blank_row(lines, row) {
+        (row, 0)
+    } else {
+        (row, line_len(lines, row))
+    }
+}
+
+fn paragraph_back(lines: &[String], row: usize) -> (usize, usize) {
+    let mut row = row;
+    while row > 0 && is_blank_row(lines, row) {
+        row -= 1;
+    }
+    while row > 0 && !is_blank_row(lines, row) {
+        row -= 1;
+    }
+    (row, 0)
+}
+
+fn find_char(
+    lines: &[String],
+    (row, col): (usize, usize),
+    find: Find,
+    count: usize,
+) -> Option<usize> {
+    let chars: Vec<char> = lines[row].chars().collect();
+    let mut col = col;
+    // A repeated `t` skips a match right next to the cursor so that it moves on
+    let mut skip = usize::from(find.till && find.repeat);
+    for _ in 0..count {
+        col = if find.forward {
+            (col + 1 + skip..chars.len()).find(|&c| chars[c] == find.ch)?
+        } else {
+            (0..col.saturating_sub(skip))
+                .rev()
+                .find(|&c| chars[c] == find.ch)?
+        };
+        skip = 0;
+    }
+    Some(match (find.till, find.forward) {
+        (true, true) => col - 1,
+        (true, false) => col + 1,
+        _ => col,
+    })
+}
+
+fn match_pair(lines: &[String], (row, col): (usize, usize)) -> Option<(usize, usize)> {
+    const PAIRS: [(char, char); 3] = [('(', ')'), ('[', ']'), ('{', '}')];
+    let chars: Vec<char> = lines[row].chars().collect();
+    let start =
+        (col..chars.len()).find(|&c| PAIRS.iter().any(|&(o, e)| chars[c] == o || chars[c] == e))?;
+    let bracket = chars[start];
+    let (open, close, forward) = PAIRS.iter().find_map(|&(o, e)| {
+        if bracket == o {
+            Some((o, e, true))
+        } else if bracket == e {
+            Some((o, e, false))
+        } else {
+            None
+        }
+    })?;
+    let mut depth = 0usize;
+    let mut pos = (row, start);
+    loop {
+        let c = char_at(lines, pos);
+        if c == open {
+            depth = if forward {
+                depth + 1
+            } else {
+                depth.checked_sub(1)?
+            };
+        } else if c == close {
+            depth = if forward {
+                depth.checked_sub(1)?
+            } else {
+                depth + 1
+            };
+        }
+        if depth == 0 {
+            return Some(pos);
+        }
+        pos = if forward {
+            next(lines, pos)?
+        } else {
+            prev(lines, pos)?
+        };
+    }
+}
+
+// `step` applied `n` times, or until it stops moving at the start or end of
+// the note
+fn repeat(
+    n: usize,
+    start: (usize, usize),
+    step: impl Fn((usize, usize)) -> (usize, usize),
+) -> (usize, usize) {
+    let mut pos = start;
+    for _ in 0..n {
+        let next = step(pos);
+        if next == pos {
+            break;
+        }
+        pos = next;
+    }
+    pos
+}
+
+// Where `motion` moves the cursor, `count` times. RepeatFind has to be resolved
+// to a Find by the caller, which remembers the last one.
+pub fn target(
+    lines: &[String],
+    cursor: (usize, usize),
+    motion: Motion,
+    count: Option<usize>,
+) -> Option<Target> {
+    let n = count.unwrap_or(1).max(1);
+    let (row, col) = cursor;
+    let last_row = lines.len().saturating_sub(1);
+    let (pos, kind) = match motion {
+        Motion::Left => ((row, col.saturating_sub(n)), Kind::Exclusive),
+        Motion::Right => (
+            (row, col.saturating_add(n).min(line_len(lines, row))),
+            Kind::Exclusive,
+        ),
+        Motion::Down => ((row.saturating_add(n).min(last_row), col), Kind::Linewise),
+        Motion::Up => ((row.saturating_sub(n), col), Kind::Linewise),
+        Motion::WordStart { big } => {
+            let pos = repeat(n, cursor, |pos| word_start(lines, pos, big));
+            (pos, Kind::Exclusive)
+        }
+        Motion::WordEnd { big } => {
+            let pos = repeat(n, cursor, |pos| word_end(lines, pos, big));
+            (pos, Kind::Inclusive)
+        }
+        Motion::WordBack { big } => {
+            let pos = repeat(n, cursor, |pos| word_back(lines, pos, big));
+            (pos, Kind::Exclusive)
+        }
+        Motion::LineStart => ((row, 0), Kind::Exclusive),
+        Motion::FirstNonBlank => ((row, first_non_blank(lines, row)), Kind::Exclusive),
+        Motion::LineEnd => {
+            let row = row.saturating_add(n - 1).min(last_row);
+            (
+                (row, line_len(lines, row).saturating_sub(1)),
+                Kind::Inclusive,
+            )
+        }
+        Motion::ParagraphForward => {
+            let pos = repeat(n, cursor, |(r, _)| paragraph_forward(lines, r));
+            (pos, Kind::Exclusive)
+        }
+        Motion::ParagraphBack => {
+            let pos = repeat(n, cursor, |(r, _)| paragraph_back(lines, r));
+            (pos, Kind::Exclusive)
+        }
+        Motion::Find(find) => {
+            let kind = if find.forward {
+                Kind::Inclusive
+            } else {
+                Kind::Exclusive
+            };
+            ((row, find_char(lines, cursor, find, n)?), kind)
+        }
+        Motion::RepeatFind { .. } | Motion::Object { .. } => return None,
+        Motion::MatchPair => (match_pair(lines, cursor)?, Kind::Inclusive),
+        Motion::FirstLine => (
+            (count.map_or(0, |c| c - 1).min(last_row), 0),
+            Kind::Linewise,
+        ),
+        Motion::LastLine => (
+            (count.map_or(last_row, |c| c - 1).min(last_row), 0),
+            Kind::Linewise,
+        ),
+        Motion::Line => (
+            (row.saturating_add(n - 1).min(last_row), col),
+            Kind::Linewise,
+        ),
+    };
+    Some(Target { pos, kind })
+}
+
+// The text an operator works on
+pub fn operator_range(
+    lines: &[String],
+    cursor: (usize, usize),
+    motion: Motion,
+    target: Target,
+) -> Range {
+    let (start, mut end) = if target.pos < cursor {
+        (target.pos, cursor)
+    } else {
+        (cursor, target.pos)
+    };
+    match target.kind {
+        Kind::Linewise => return ((start.0, 0), (end.0, 0), true),
+        Kind::Inclusive => {
+            end = next(lines, end)
+                .filter(|n| n.0 == end.0)
+                .unwrap_or((end.0, line_len(lines, end.0)));
+        }
+        Kind::Exclusive => {}
+    }
+    // `dw` on the last word of a line stops at the end of that line
+    if matches!(motion, Motion::WordStart { .. }) && end.0 > start.0 {
+        let row = end.0 - 1;
+        if lines[end.0].chars().take(end.1).all(char::is_whitespace) {
+            return (start, (row, line_len(lines, row)), false);
+        }
+    }
+    // An exclusive motion ending at the start of a line stops at the end of the
+    // previous one, and covers whole lines if it also began at the indent
+    if target.kind == Kind::Exclusive && end.0 > start.0 && end.1 == 0 {
+        if start.1 <= first_non_blank(lines, start.0) {
+            return ((start.0, 0), (end.0 - 1, 0), true);
+        }
+        end = (end.0 - 1, line_len(lines, end.0 - 1));
+    }
+    (start, end, false)
+}
+
+// `cw` on a word changes to its end like `ce`, even from the last char of the word
+pub fn change_word_target(
+    lines: &[String],
+    cursor: (usize, usize),
+    big: bool,
+    count: Option<usize>,
+) -> Target {
+    let start = prev(lines, cursor).unwrap_or(cursor);
+    let mut pos = repeat(count.unwrap_or(1).max(1), start, |pos| {
+        word_end(lines, pos, big)
+    });
+    if pos < cursor {
+        pos = cursor;
+    }
+    Target {
+        pos,
+        kind: Kind::Inclusive,
+    }
+}
+
+pub fn is_blank_at(lines: &[String], pos: (usize, usize)) -> bool {
+    char_at(lines, pos).is_whitespace()
+}
+
+fn byte_index(line: &str, col: usize) -> usize {
+    line.char_indices()
+        .nth(col)
+        .map(|(b, _)| b)
+        .unwrap_or(line.len())
+}
+
+// Chars from start up to end, one entry per line
+pub fn text_in(lines: &[String], start: (usize, usize), end: (usize, usize)) -> Vec<String> {
+    (start.0..=end.0)
+        .map(|row| {
+            let line = &lines[row];
+            let from = if row == start.0 {
+                byte_index(line, start.1)
+            } else {
+                0
+            };
+            let to = if row == end.0 {
+                byte_index(line, end.1)
+            } else {
+                line.len()
+            };
+            line[from..to.max(from)].to_string()
+        })
+        .collect()
+}
+
+// `c` after a case operator
+pub fn change_case(operator: Operator, c: char) -> String {
+    match operator {
+        Operator::Lower => c.to_lowercase().collect(),
+        Operator::Upper => c.to_uppercase().collect(),
+        _ if c.is_uppercase() => c.to_lowercase().collect(),
+        _ => c.to_uppercase().collect(),
+    }
+}
+
+// `J`: the lines joined by single spaces, without the indent of the later ones
+pub fn join_lines(lines: &[String]) -> String {
+    let mut joined = lines.first().cloned().unwrap_or_default();
+    for line in &lines[1.min(lines.len())..] {
+        let line = line.trim_start();
+        if line.is_empty() {
+            continue;
+        }
+        let trimmed = joined.trim_end().len();
+        joined.truncate(trimmed);
+        if !joined.is_empty() && !line.starts_with(')') {
+            joined.push(' ');
+        }
+        joined.push_str(line);
+    }
+    joined
+}
+
+// Rows start.0..=end.0 with `f` applied to the chars in the range
+pub fn map_range(
+    lines: &[String],
+    start: (usize, usize),
+    end: (usize, usize),
+    linewise: bool,
+    f: impl Fn(char) -> String,
+) -> Vec<String> {
+    (start.0..=end.0)
+        .map(|row| {
+            let line = &lines[row];
+            let (from, to) = if linewise {
+                (0, line.len())
+            } else {
+                (
+                    if row == start.0 {
+                        byte_index(line, start.1)
+                    } else {
+                        0
+                    },
+                    if row == end.0 {
+                        byte_index(line, end.1)
+                    } else {
+                        line.len()
+                    },
+                )
+            };
+            let to = to.max(from);
+            format!(
+                "{}{}{}",
+                &line[..from],
+                line[from..to].chars().map(&f).collect::<String>(),
+                &line[to..]
+            )
+        })
+        .collect()
+}
+
+fn is_code_fence(line: &str) -> bool {
+    line.trim_start().starts_with("```")
+}
+
+// Range of a text object at the cursor. `il`/`al` need the link parsing in App
+// and are not handled here.
+pub fn text_object(
+    lines: &[String],
+    cursor: (usize, usize),
+    inner: bool,
+    object: char,
+) -> Option<Range> {
+    let (row, col) = cursor;
+    let chars: Vec<char> = lines[row].chars().collect();
+    match object {
+        'w' | 'W' => {
+            let big = object == 'W';
+            if chars.is_empty() {
+                return None;
+            }
+            let col = col.min(chars.len() - 1);
+            let word_class = class(lines, (row, col), big);
+            let same = |c: usize| class(lines, (row, c), big) == word_class;
+            let mut start = col;
+            while start > 0 && same(start - 1) {
+                start -= 1;
+            }
+            let mut end = col + 1;
+            while end < chars.len() && same(end) {
+                end += 1;
+            }
+            if !inner {
+                // `aw` takes the blanks after the word, or before it at the end of a line
+                let blank = |c: usize| chars[c].is_whitespace();
+                if end < chars.len() && blank(end) && word_class != 0 {
+                    while end < chars.len() && blank(end) {
+                        end += 1;
+                    }
+                } else {
+                    while start > 0 && blank(start - 1) {
+                        start -= 1;
+                    }
+                }
+            }
+            Some(((row, start), (row, end), false))
+        }
+        '"' | '\'' | '`' => {
+            let quotes: Vec<usize> = (0..chars.len())
+                .filter(|&c| chars[c] == object && (c == 0 || chars[c - 1] != '\\'))
+                .collect();
+            // Quotes pair up from the start of the line; use the pair around the
+            // cursor, or the next one after it
+            let (open, close) = quotes
+                .chunks_exact(2)
+                .map(|pair| (pair[0], pair[1]))
+                .find(|&(_, close)| close >= col)?;
+            if inner {
+                Some(((row, open + 1), (row, close), false))
+            } else {
+                Some(((row, open), (row, close + 1), false))
+            }
+        }
+        '(' | ')' | 'b' | '[' | ']' | '{' | '}' | 'B' | '<' | '>' => {
+            let (open, close) = match object {
+                '(' | ')' | 'b' => ('(', ')'),
+                '[' | ']' => ('[', ']'),
+                '<' | '>' => ('<', '>'),
+                _ => ('{', '}'),
+            };
+            // Walk back to the unmatched opening bracket
+            let mut depth = 0usize;
+            let mut pos = cursor;
+            let open_pos = loop {
+                let c = char_at(lines, pos);
+                if c == open && depth == 0 {
+                    break pos;
+                } else if c == open {
+                    depth -= 1;
+                } else if c == close && pos != cursor {
+                    depth += 1;
+                }
+                pos = prev(lines, pos)?;
+            };
+            let mut depth = 0usize;
+            let mut pos = open_pos;
+            let close_pos = loop {
+                let c = char_at(lines, pos);
+                if c == open {
+                    depth += 1;
+                } else if c == close {
+                    depth -= 1;
+                    if depth == 0 {
+                        break pos;
+                    }
+                }
+                pos = next(lines, pos)?;
+            };
+            if inner {
+                let start = next(lines, open_pos)?;
+                Some((start, close_pos, false))
+            } else {
+                let end = next(lines, close_pos).unwrap_or((close_pos.0, close_pos.1 + 1));
+                Some((open_pos, end, false))
+            }
+        }
+        '*' | '_' => {
+            // Runs of the marker: `*a*`, `**bold**`
+            let mut runs = Vec::new();
+            let mut c = 0;
+            while c < chars.len() {
+                if chars[c] == object {
+                    let start = c;
+                    while c < chars.len() && chars[c] == object {
+                        c += 1;
+                    }
+                    runs.push((start, c));
+                } else {
+                    c += 1;
+                }
+            }
+            // Pair each run with the next one of the same width
+            let mut open: Vec<(usize, usize)> = Vec::new();
+            let mut pairs = Vec::new();
+            for run in runs {
+                match open.iter().rposition(|o| o.1 - o.0 == run.1 - run.0) {
+                    Some(i) => pairs.push((open.remove(i), run)),
+                    None => open.push(run),
+                }
+            }
+            let ((open_start, open_end), (close_start, close_end)) = pairs
+                .into_iter()
+                .filter(|((start, _), (_, end))| *start <= col && col < *end)
+                .min_by_key(|((start, _), (_, end))| end - start)?;
+            if inner {
+                Some(((row, open_end), (row, close_start), false))
+            } else {
+                Some(((row, open_start), (row, close_end), false))
+            }
+        }
+        'p' => {
+            let blank = is_blank_row(lines, row);
+            let same = |r: usize| is_blank_row(lines, r) == blank;
+            let mut start = row;
+            while start > 0 && same(start - 1) {
+                start -= 1;
+            }
+            let mut end = row;
+            while end + 1 < lines.len() && same(end + 1) {
+                end += 1;
+            }
+            if !inner {
+                // `ap` takes the blank lines after the paragraph, or before it at the end
+                if end + 1 < lines.len() && !blank {
+                    while end + 1 < lines.len() && is_blank_row(lines, end + 1) {
+                        end += 1;
+                    }
+                } else {
+                    while start > 0 && is_blank_row(lines, start - 1) {
+                        start -= 1;
+                    }
+                }
+            }
+            Some(((start, 0), (end, 0), true))
+        }
+        'c' => {
+            let mut open = None;
+            let mut fence = None;
+            for (r, line) in lines.iter().enumerate() {
+                if !is_code_fence(line) {
+                    continue;
+                }
+                match open.take() {
+                    Some(start) if r >= row && start <= row => {
+                        fence = Some((start, r));
+                        break;
+                    }
+                    Some(_) => {}
+                    None => open = Some(r),
+                }
+            }
+            let (start, end) = fence?;
+            if inner {
+                (end > start + 1).then_some(((start + 1, 0), (end - 1, 0), true))
+            } else {
+                Some(((start, 0), (end, 0), true))
+            }
+        }
+        'h' => {
+            let headings = crate::markdown::headings(lines);
+            let index = crate::markdown::heading_at(&headings, row)?;
+            let start = headings[index].line;
+            let end = crate::markdown::section_end(&headings, index, lines.len());
+            if inner {
+                (end > start + 1).then_some(((start + 1, 0), (end - 1, 0), true))
+            } else {
+                Some(((start, 0), (end - 1, 0), true))
+            }
+        }
+        _ => None,
+    }
+}
@@ -0,0 +1,154 @@
+use ratatui::layout::{Constraint, Direction, Layout, Rect};
+
+// Layout of the editor windows: each split divides its area between two
+// children, `ratio` percent going to the first.
+
+#[derive(Clone, Debug)]
+pub enum Split {
+    Window(usize),
+    Node {
+        direction: Direction, // Vertical stacks the children, Horizontal puts them side by side
+        ratio: u16,
+        first: Box<Split>,
+        second: Box<Split>,
+    },
+}
+
+impl Split {
+    // Put `new` before `target`, sharing its area
+    pub fn split(&mut self, target: usize, new: usize, direction: Direction) -> bool {
+        match self {
+            Split::Window(id) if *id == target => {
+                *self = Split::Node {
+                    direction,
+                    ratio: 50,
+                    first: Box::new(Split::Window(new)),
+                    second: Box::new(Split::Window(target)),
+                };
+                true
+            }
+            Split::Window(_) => false,
+            Split::Node { first, second, .. } => {
+                first.split(target, new, direction) || second.split(target, new, direction)
+            }
+        }
+    }
+
+    // Remove `target`, giving its area to its sibling
+    pub fn close(&mut self, target: usize) -> bool {
+        let Split::Node { first, second, .. } = self else {
+            return false;
+        };
+        let sibling = match (first.as_ref(), second.as_ref()) {
+            (Split::Window(id), _) if *id == target => second.as_ref().clone(),
+            (_, Split::Window(id)) if *id == target => first.as_ref().clone(),
+            _ => return first.close(target) || second.close(target),
+        };
+        *self = sibling;
+        true
+    }
+
+    pub fn windows(&self) -> Vec<usize> {
+        match self {
+            Split::Window(id) => vec![*id],
+            Split::Node { first, second, .. } => {
+                let mut ids = first.windows();
+                ids.extend(second.windows());
+                ids
+            }
+        }
+    }
+
+    pub fn areas(&self, area: Rect) -> Vec<(usize, Rect)> {
+        match self {
+            Split::Window(id) => vec![(*id, area)],
+            Split::Node {
+                direction,
+                ratio,
+                first,
+                second,
+            } => {
+                let chunks = Layout::default()
+                    .direction(*direction)
+                    .constraints([
+                        Constraint::Percentage(*ratio),
+                        Constraint::Percentage(100 - *ratio),
+                    ])
+                    .split(area);
+                let mut areas = first.areas(chunks[0]);
+                areas.extend(second.areas(chunks[1]));
+                areas
+            }
+        }
+    }
+
+    fn contains(&self, target: usize) -> bool {
+        match self {
+            Split::Window(id) => *id == target,
+            Split::Node { first, second, .. } => first.contains(target) || second.contains(target),
+        }
+    }
+
+    // Grow `target` by `delta` percent along `direction`, taken from the
+    // closest split in that direction
+    pub fn resize(&mut self, target: usize, direction: Direction, delta: i16) -> bool {
+        let Split::Node {
+            direction: split_direction,
+            ratio,
+            first,
+            second,
+        } = self
+        else {
+            return false;
+        };
+        let in_first = first.contains(target);
+        let child = if in_first { first } else { second };
+        if child.resize(target, direction, delta) {
+            return true;
+        }
+        if *split_direction != direction || !(in_first || child.contains(target)) {
+            return false;
+        }
+        let delta = if in_first { delta } else { -delta };
+        *ratio = (*ratio as i16 + delta).clamp(10, 90) as u16;
+        true
+    }
+
+    pub fn equalize(&mut self) {
+        if let Split::Node {
+            ratio,
+            first,
+            second,
+            ..
+        } = self
+        {
+            *ratio = 50;
+            first.equalize();
+            second.equalize();
+        }
+    }
+}
+
+// The window next to `from` in the `h`/`j`/`k`/`l` direction, preferring the
+// one that overlaps it the most
+pub fn neighbour(areas: &[(usize, Rect)], from: usize, direction: char) -> Option<usize> {
+    let (_, current) = areas.iter().find(|(id, _)| *id == from)?;
+    let overlap = |a: (u16, u16), b: (u16, u16)| a.1.min(b.1).saturating_sub(a.0.max(b.0));
+    let columns = |r: &Rect| (r.x, r.x + r.width);
+    let rows = |r: &Rect| (r.y, r.y + r.height);
+    areas
+        .iter()
+        .filter(|(id, _)| *id != from)
+        .filter_map(|(id, r)| {
+            let shared = match direction {
+                'h' if r.x + r.width == current.x => overlap(rows(r), rows(current)),
+                'l' if current.x + current.width == r.x => overlap(rows(r), rows(current)),
+                'k' if r.y + r.height == current.y => overlap(columns(r), columns(current)),
+                'j' if current.y + current.height == r.y => overlap(columns(r), columns(current)),
+                _ => 0,
+            };
+            (shared > 0).then_some((*id, shared))
+        })
+        .max_by_key(|&(_, shared)| shared)
+        .map(|(id, _)| id)
+}